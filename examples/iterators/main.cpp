@@ -0,0 +1,41 @@
+#include <algorithm>
+#include <iostream>
+#include <numeric>
+#include <vector>
+
+#include "./generated.h"
+
+int main() {
+  __zngur_dyn_api api(
+      dlopen("../../target/release/libexample_iterators.so", RTLD_NOW));
+  std::string error;
+  if (!api.init(error)) {
+    std::cerr << error << std::endl;
+    return 1;
+  }
+  using rust::crate::Inventory;
+
+  auto v = Inventory::numbers(&api, 5);
+  std::cout << "count of 3: "
+            << std::count_if(v.begin(), v.end(), [](auto x) { return *x == 3; })
+            << std::endl;
+  for (auto x : v) {
+    std::cout << *x << " ";
+  }
+  std::cout << std::endl;
+  for (auto x : v) {
+    *x *= 10;
+  }
+  const auto &cv = v;
+  std::cout << "sum: "
+            << std::accumulate(cv.begin(), cv.end(), 0,
+                               [](int32_t s, rust::Ref<int32_t> x) {
+                                 return s + *x;
+                               })
+            << std::endl;
+  std::cout << "len after loops: " << v.len(&api) << std::endl;
+
+  std::vector<int32_t> moved(std::move(v).begin(), std::move(v).end());
+  std::cout << "moved: " << moved.size() << " items, last " << moved.back()
+            << std::endl;
+}
@@ -0,0 +1,10 @@
+#[rustfmt::skip]
+mod generated;
+
+pub struct Inventory;
+
+impl Inventory {
+    pub fn numbers(n: i32) -> Vec<i32> {
+        (0..n).collect()
+    }
+}
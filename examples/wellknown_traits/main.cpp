@@ -0,0 +1,114 @@
+#include <algorithm>
+#include <cmath>
+#include <iostream>
+#include <set>
+#include <sstream>
+#include <type_traits>
+#include <unordered_set>
+#include <vector>
+#ifdef __cpp_lib_format
+#include <format>
+#endif
+
+#include "./generated.h"
+
+using rust::crate::Money;
+using rust::crate::Ratio;
+using rust::crate::Tag;
+using Vec = rust::std::vec::Vec<int32_t>;
+
+// Without `Default`, there is no way to make a `Tag` out of nothing.
+static_assert(!std::is_default_constructible_v<Tag>);
+static_assert(std::is_default_constructible_v<Vec>);
+
+// `Display` types can be printed with `std::format` where the standard library has it, and with
+// `operator<<` everywhere.
+template <typename T> std::string show(const T &value) {
+#ifdef __cpp_lib_format
+  return std::format("{}", value);
+#else
+  std::ostringstream s;
+  s << value;
+  return s.str();
+#endif
+}
+
+int main() {
+  __zngur_dyn_api api(
+      dlopen("../../target/release/libexample_wellknown_traits.so", RTLD_NOW));
+  std::string error;
+  if (!api.init(error)) {
+    std::cerr << error << std::endl;
+    return 1;
+  }
+  rust::crate::silence_panics(&api);
+
+  // Clone
+  Tag tag = Tag::new_(&api, "rust"_rs);
+  Tag copy = tag;
+  copy.rename(&api, "cpp"_rs);
+  std::cout << "copy: " << tag << " " << copy << std::endl;
+  copy = tag;
+  std::cout << "assigned: " << copy << std::endl;
+  rust::Ref<Tag> tag_ref = tag;
+  Tag cloned = tag_ref.clone();
+  std::cout << "cloned from a reference: " << cloned << std::endl;
+
+  // Default
+  Vec v;
+  v.push(&api, 3);
+  v.push(&api, 1);
+  v.push(&api, 2);
+  std::cout << "default vec has " << v.len(&api) << " items after pushes"
+            << std::endl;
+
+  // Index and IndexMut
+  *v[0] = 30;
+  std::cout << "indexed: " << *v[0] << " " << *v[1] << " " << *v[2]
+            << std::endl;
+  try {
+    std::cout << *v[3] << std::endl;
+  } catch (rust::Panic &) {
+    std::cout << "out of bounds index threw rust::Panic" << std::endl;
+  }
+
+  // PartialEq, Eq, PartialOrd and Ord
+  Vec w = v;
+  std::cout << std::boolalpha << "v == w: " << (v == w) << std::endl;
+  w.push(&api, 4);
+  std::cout << "v != w: " << (v != w) << std::endl;
+  std::vector<Tag> tags;
+  for (auto name : {"b"_rs, "c"_rs, "a"_rs}) {
+    tags.push_back(Tag::new_(&api, name));
+  }
+  std::sort(tags.begin(), tags.end());
+  std::cout << "sorted:";
+  for (const auto &t : tags) {
+    std::cout << " " << t;
+  }
+  std::cout << std::endl;
+  std::cout << "a <=> b is less: " << (tags[0] <=> tags[1] < 0) << std::endl;
+  Ratio nan = Ratio::new_(&api, NAN), one = Ratio::new_(&api, 1.0);
+  std::cout << "nan <=> 1 is unordered: "
+            << ((nan <=> one) == std::partial_ordering::unordered)
+            << ", nan == nan: " << (nan == nan) << std::endl;
+
+  // Hash
+  std::unordered_set<Tag> unique(tags.begin(), tags.end());
+  unique.insert(tag);
+  unique.insert(cloned);
+  std::cout << "unique tags: " << unique.size() << ", contains #rust: "
+            << unique.count(Tag::new_(&api, "rust"_rs)) << std::endl;
+
+  // Operators
+  Money price = Money::cents(&api, 1050);
+  Money total = price * 3 - Money::cents(&api, 25);
+  total += Money::cents(&api, 5);
+  std::cout << "total: " << show(total) << ", negated: " << show(-total)
+            << std::endl;
+  std::cout << "sum of prices: " << show(price + price)
+            << ", zero: " << bool(~(price - price)) << std::endl;
+  std::set<Money> prices{total, price, price};
+  std::cout << "distinct prices: " << prices.size() << ", cheapest "
+            << *prices.begin() << std::endl;
+}
@@ -1,25 +1,127 @@
 use std::path::PathBuf;
 
 use clap::Parser;
-use zngur::Zngur;
+use zngur::{Cfg, Zngur};
+use zngur_parser::{AriadneRenderer, ZngParser, format_zng};
 
 #[derive(Parser)]
 #[command(version)]
 enum Command {
     #[command(alias = "g")]
-    Generate { path: PathBuf },
+    Generate {
+        path: PathBuf,
+        /// Evaluates `#cfg(...)` with `NAME` or `NAME="VALUE"` set, replacing the values of the
+        /// host for `NAME`. Can be used multiple times
+        #[arg(long = "cfg", value_name = "SPEC")]
+        cfgs: Vec<String>,
+    },
+    /// Generates from a zng file in the JSON format printed by `zngur json`
+    #[command(alias = "gj")]
+    GenerateFromJson { path: PathBuf },
+    /// Prints a zng file in the JSON format of `ZngurFile`
+    Json {
+        path: PathBuf,
+        /// Same as in `generate`
+        #[arg(long = "cfg", value_name = "SPEC")]
+        cfgs: Vec<String>,
+    },
+    /// Formats zng files in place
+    Fmt {
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+        /// Don't write the files, but exit with an error if any of them is not formatted
+        #[arg(long)]
+        check: bool,
+    },
 }
 
 fn main() {
     let cmd = Command::parse();
     match cmd {
-        Command::Generate { path } => {
+        Command::Generate { path, cfgs } => {
             let pp = path.parent().unwrap();
             Zngur::from_zng_file(&path)
+                .with_cfg(cfg_from_args(&cfgs))
                 .with_cpp_file(pp.join("generated.cpp"))
                 .with_h_file(pp.join("generated.h"))
                 .with_rs_file(pp.join("src/generated.rs"))
                 .generate();
         }
+        Command::GenerateFromJson { path } => {
+            let pp = path.parent().unwrap();
+            Zngur::from_json_file(&path)
+                .with_cpp_file(pp.join("generated.cpp"))
+                .with_h_file(pp.join("generated.h"))
+                .with_rs_file(pp.join("src/generated.rs"))
+                .generate();
+        }
+        Command::Json { path, cfgs } => {
+            let name = path.to_string_lossy();
+            let text = std::fs::read_to_string(&path)
+                .unwrap_or_else(|e| panic!("Failed to read {name}: {e}"));
+            let mut parser = ZngParser::new().with_cfg(cfg_from_args(&cfgs));
+            let renderer = AriadneRenderer::new().with_source(&name, &text);
+            match parser.parse(&name, &text) {
+                Ok(zng) => {
+                    renderer.eprint(parser.warnings()).unwrap();
+                    println!("{}", zng.to_json());
+                }
+                Err(diagnostics) => {
+                    renderer.eprint(&diagnostics).unwrap();
+                    std::process::exit(1);
+                }
+            }
+        }
+        Command::Fmt { paths, check } => {
+            let mut failed = false;
+            for path in paths {
+                let name = path.to_string_lossy();
+                let text = std::fs::read_to_string(&path)
+                    .unwrap_or_else(|e| panic!("Failed to read {name}: {e}"));
+                let formatted = match format_zng(&name, &text) {
+                    Ok(x) => x,
+                    Err(diagnostics) => {
+                        AriadneRenderer::new()
+                            .with_source(&name, &text)
+                            .eprint(&diagnostics)
+                            .unwrap();
+                        failed = true;
+                        continue;
+                    }
+                };
+                if formatted == text {
+                    continue;
+                }
+                if check {
+                    eprintln!("{name} is not formatted");
+                    failed = true;
+                } else {
+                    std::fs::write(&path, formatted)
+                        .unwrap_or_else(|e| panic!("Failed to write {name}: {e}"));
+                }
+            }
+            if failed {
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+/// The host configuration, with the values given by `--cfg` flags replacing those of the host.
+fn cfg_from_args(cfgs: &[String]) -> Cfg {
+    let cfgs = cfgs
+        .iter()
+        .map(|x| match x.split_once('=') {
+            Some((name, value)) => (name.trim(), Some(value.trim().trim_matches('"'))),
+            None => (x.trim(), None),
+        })
+        .collect::<Vec<_>>();
+    let mut cfg = Cfg::host();
+    for (name, _) in &cfgs {
+        cfg.remove(name);
+    }
+    for (name, value) in cfgs {
+        cfg.insert(name, value);
     }
+    cfg
 }
@@ -0,0 +1,94 @@
+//! The JSON format of [`ZngurFile`], for tools that produce or inspect zng files without going
+//! through their text.
+//!
+//! A file is a `{ "version": 1, "file": { ... } }` object, where `file` is the serde
+//! representation of [`ZngurFile`]: structs are objects with the same field names, enums are
+//! externally tagged, and the `traits` map is a list of [`ZngurTrait`]s.
+
+use std::{collections::HashMap, fmt::Display};
+
+use itertools::Itertools;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{RustTrait, ZngurFile, ZngurTrait};
+
+/// Version of the JSON format written by [`ZngurFile::to_json`]. It is increased on every
+/// incompatible change, and [`ZngurFile::from_json`] only accepts files of this version.
+pub const JSON_FORMAT_VERSION: u32 = 1;
+
+/// Failure of [`ZngurFile::from_json`].
+#[derive(Debug)]
+pub enum JsonError {
+    /// The file is written in another version of the format.
+    UnsupportedVersion(u32),
+    Invalid(serde_json::Error),
+}
+
+impl Display for JsonError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JsonError::UnsupportedVersion(v) => write!(
+                f,
+                "Version {v} of the zngur JSON format is not supported, expected version {JSON_FORMAT_VERSION}"
+            ),
+            JsonError::Invalid(e) => write!(f, "Invalid zngur JSON file: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for JsonError {}
+
+impl ZngurFile {
+    /// Writes the file in the JSON format described in the [module documentation](self).
+    pub fn to_json(&self) -> String {
+        #[derive(Serialize)]
+        struct Versioned<'a> {
+            version: u32,
+            file: &'a ZngurFile,
+        }
+        serde_json::to_string_pretty(&Versioned {
+            version: JSON_FORMAT_VERSION,
+            file: self,
+        })
+        .expect("zngur files are always representable in JSON")
+    }
+
+    /// Reads a file written by [`ZngurFile::to_json`], or by any other tool that follows the same
+    /// format. Missing top level fields of `file` are filled with their default value.
+    pub fn from_json(text: &str) -> Result<ZngurFile, JsonError> {
+        #[derive(Deserialize)]
+        struct Header {
+            version: u32,
+        }
+        #[derive(Deserialize)]
+        struct Versioned {
+            file: ZngurFile,
+        }
+        let header: Header = serde_json::from_str(text).map_err(JsonError::Invalid)?;
+        if header.version != JSON_FORMAT_VERSION {
+            return Err(JsonError::UnsupportedVersion(header.version));
+        }
+        let versioned: Versioned = serde_json::from_str(text).map_err(JsonError::Invalid)?;
+        Ok(versioned.file)
+    }
+}
+
+/// Traits are keyed by [`RustTrait`], which can't be a JSON object key, so they are stored as a
+/// list sorted by name instead.
+pub(crate) mod traits_as_list {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        traits: &HashMap<RustTrait, ZngurTrait>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(traits.values().sorted_by_key(|x| x.tr.to_string()))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<HashMap<RustTrait, ZngurTrait>, D::Error> {
+        let traits = Vec::<ZngurTrait>::deserialize(deserializer)?;
+        Ok(traits.into_iter().map(|x| (x.tr.clone(), x)).collect())
+    }
+}
@@ -2,13 +2,41 @@ use std::{collections::HashMap, fmt::Display};
 
 use itertools::Itertools;
 
+#[cfg(feature = "serde")]
+mod json;
+#[cfg(feature = "serde")]
+pub use json::{JSON_FORMAT_VERSION, JsonError};
+
+/// Location of an item in the zng file it is declared in.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ZngurSpan {
+    pub file: String,
+    /// Byte offset of the start of the item.
+    pub start: usize,
+    /// Byte offset of the end of the item.
+    pub end: usize,
+    /// 1-based line of the start of the item.
+    pub line: usize,
+    /// 1-based column of the start of the item.
+    pub column: usize,
+}
+
+impl Display for ZngurSpan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.column)
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Mutability {
     Mut,
     Not,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ZngurMethodReceiver {
     Static,
     Ref(Mutability),
@@ -16,84 +44,326 @@ pub enum ZngurMethodReceiver {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ZngurMethod {
     pub name: String,
     pub generics: Vec<RustType>,
     pub receiver: ZngurMethodReceiver,
     pub inputs: Vec<RustType>,
     pub output: RustType,
+    /// Lines of the `///` comments above the item, without the `///`.
+    pub doc: Option<String>,
+    pub span: Option<ZngurSpan>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ZngurFn {
     pub path: RustPathAndGenerics,
     pub inputs: Vec<RustType>,
     pub output: RustType,
+    pub doc: Option<String>,
+    pub span: Option<ZngurSpan>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ZngurExternCppFn {
     pub name: String,
     pub inputs: Vec<RustType>,
     pub output: RustType,
+    pub span: Option<ZngurSpan>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ZngurExternCppImpl {
     pub tr: Option<RustTrait>,
     pub ty: RustType,
     pub methods: Vec<ZngurMethod>,
+    pub span: Option<ZngurSpan>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ZngurConstructor {
     pub name: Option<String>,
     pub inputs: Vec<(String, RustType)>,
+    pub doc: Option<String>,
+    pub span: Option<ZngurSpan>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ZngurField {
     pub name: String,
     pub ty: RustType,
     pub offset: usize,
+    pub doc: Option<String>,
+    pub span: Option<ZngurSpan>,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ZngurWellknownTrait {
     Debug,
+    Display,
     Drop,
     Unsized,
     Copy,
+    Clone,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Operator(ZngurOperator),
+    /// `IntoIterator<Item = item>`, implemented by the type itself if `by_ref` is `None`, and by a
+    /// reference to it otherwise, like `&IntoIterator<Item = &T>`.
+    IntoIterator {
+        by_ref: Option<Mutability>,
+        item: RustType,
+    },
+    /// `Hash`, hashed with `hasher` in the C++ side, or `DefaultHasher` if it is `None`.
+    Hash {
+        hasher: Option<RustType>,
+    },
+}
+
+impl ZngurWellknownTrait {
+    /// Wellknown traits that must be declared together with this one, like the supertraits of
+    /// the trait in Rust.
+    pub fn required_traits(&self) -> &'static [ZngurWellknownTrait] {
+        use ZngurWellknownTrait::*;
+        match self {
+            Eq | PartialOrd => &[PartialEq],
+            Ord => &[Eq, PartialOrd],
+            Hash { .. } => &[Eq],
+            Debug
+            | Display
+            | Drop
+            | Unsized
+            | Copy
+            | Clone
+            | Default
+            | PartialEq
+            | Operator(_)
+            | IntoIterator { .. } => &[],
+        }
+    }
+}
+
+/// An operator trait of `std::ops`, like `Add<i32, Output = crate::Foo>`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ZngurOperator {
+    pub op: ZngurOperatorTrait,
+    /// The `Rhs` of binary and compound assignment operators, which is `Self` if not given, or the
+    /// `Idx` of `Index` and `IndexMut`, which must be given.
+    pub rhs: Option<RustType>,
+    /// The `Output` of binary, unary and index operators.
+    pub output: Option<RustType>,
+}
+
+impl ZngurOperator {
+    /// Checks that only the operators that have an `Rhs` or an `Output` are given one, and that
+    /// `Output` is given for them.
+    pub fn check(&self) -> Result<(), String> {
+        let name = self.op.name();
+        if self.op.is_unary() && self.rhs.is_some() {
+            return Err(format!("`{name}` has no `Rhs` type"));
+        }
+        if self.op.is_index() && self.rhs.is_none() {
+            return Err(format!(
+                "The `Idx` type of `{name}` is not declared, like `{name}<usize, Output = T>`"
+            ));
+        }
+        if self.op.is_assign() && self.output.is_some() {
+            return Err(format!("`{name}` has no `Output` type"));
+        }
+        if !self.op.is_assign() && self.output.is_none() {
+            return Err(format!(
+                "The `Output` type of `{name}` is not declared, like `{name}<Output = T>`"
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ZngurOperatorTrait {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    BitAnd,
+    BitOr,
+    BitXor,
+    Shl,
+    Shr,
+    Neg,
+    Not,
+    AddAssign,
+    SubAssign,
+    MulAssign,
+    DivAssign,
+    RemAssign,
+    BitAndAssign,
+    BitOrAssign,
+    BitXorAssign,
+    ShlAssign,
+    ShrAssign,
+    Index,
+    IndexMut,
+}
+
+impl ZngurOperatorTrait {
+    pub const ALL: [ZngurOperatorTrait; 24] = {
+        use ZngurOperatorTrait::*;
+        [
+            Add,
+            Sub,
+            Mul,
+            Div,
+            Rem,
+            BitAnd,
+            BitOr,
+            BitXor,
+            Shl,
+            Shr,
+            Neg,
+            Not,
+            AddAssign,
+            SubAssign,
+            MulAssign,
+            DivAssign,
+            RemAssign,
+            BitAndAssign,
+            BitOrAssign,
+            BitXorAssign,
+            ShlAssign,
+            ShrAssign,
+            Index,
+            IndexMut,
+        ]
+    };
+
+    /// The name of the trait in `std::ops`.
+    pub fn name(self) -> String {
+        format!("{self:?}")
+    }
+
+    /// The name of the method of the trait, like `add` or `bitand_assign`.
+    pub fn method(self) -> String {
+        let name = self.name();
+        if self == ZngurOperatorTrait::IndexMut {
+            return "index_mut".to_owned();
+        }
+        match name.strip_suffix("Assign") {
+            Some(name) => format!("{}_assign", name.to_lowercase()),
+            None => name.to_lowercase(),
+        }
+    }
+
+    /// Whether the trait is `Neg` or `Not`, which have no `Rhs`.
+    pub fn is_unary(self) -> bool {
+        matches!(self, ZngurOperatorTrait::Neg | ZngurOperatorTrait::Not)
+    }
+
+    /// Whether the trait is a compound assignment one like `AddAssign`, which has no `Output`.
+    pub fn is_assign(self) -> bool {
+        self.name().ends_with("Assign")
+    }
+
+    /// Whether the trait is `Index` or `IndexMut`, which return a reference to their `Output`.
+    pub fn is_index(self) -> bool {
+        matches!(
+            self,
+            ZngurOperatorTrait::Index | ZngurOperatorTrait::IndexMut
+        )
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ZngurWellknownTraitData {
     Debug {
         pretty_print: String,
         debug_print: String,
+        fmt: String,
+    },
+    Display {
+        fmt: String,
     },
     Drop {
         drop_in_place: String,
     },
     Unsized,
     Copy,
+    Clone {
+        clone: String,
+    },
+    Default {
+        default: String,
+    },
+    PartialEq {
+        eq: String,
+    },
+    Eq,
+    PartialOrd {
+        partial_cmp: String,
+    },
+    Ord {
+        cmp: String,
+    },
+    Operator {
+        operator: ZngurOperator,
+        link_name: String,
+    },
+    IntoIterator {
+        by_ref: Option<Mutability>,
+        item: RustType,
+        into_iter: String,
+        next: String,
+        drop: String,
+    },
+    Hash {
+        hash: String,
+    },
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum LayoutPolicy {
-    StackAllocated { size: usize, align: usize },
+    StackAllocated {
+        size: usize,
+        align: usize,
+    },
     HeapAllocated,
     OnlyByRef,
+    /// Stack allocated with the size and align of the type for the target, which are computed
+    /// while generating. See `#layout(auto)`.
+    Auto,
+    /// Stack allocated with storage of `size` and `align`, which are upper bounds of the size and
+    /// align of the type. See `#layout_conservative`.
+    Conservative {
+        size: usize,
+        align: usize,
+    },
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ZngurMethodDetails {
     pub data: ZngurMethod,
     pub use_path: Option<Vec<String>>,
     pub deref: Option<RustType>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ZngurType {
     pub ty: RustType,
     pub layout: LayoutPolicy,
@@ -103,26 +373,97 @@ pub struct ZngurType {
     pub fields: Vec<ZngurField>,
     pub cpp_value: Option<(String, String)>,
     pub cpp_ref: Option<String>,
+    pub doc: Option<String>,
+    pub span: Option<ZngurSpan>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ZngurTrait {
     pub tr: RustTrait,
     pub methods: Vec<ZngurMethod>,
+    pub doc: Option<String>,
+    pub span: Option<ZngurSpan>,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct ZngurFile {
     pub types: Vec<ZngurType>,
+    #[cfg_attr(feature = "serde", serde(with = "json::traits_as_list"))]
     pub traits: HashMap<RustTrait, ZngurTrait>,
     pub funcs: Vec<ZngurFn>,
     pub extern_cpp_funcs: Vec<ZngurExternCppFn>,
     pub extern_cpp_impls: Vec<ZngurExternCppImpl>,
     pub additional_includes: String,
     pub convert_panic_to_exception: bool,
+    /// Names of the lints silenced by `#allow(...)` items.
+    pub allowed_lints: Vec<String>,
+    /// Predicates of the `#cfg(...)` attributes that were evaluated while parsing, with their results.
+    pub cfgs: Vec<(CfgPredicate, bool)>,
 }
 
+impl ZngurFile {
+    /// Whether the two files declare the same items, regardless of where the items are in the zng
+    /// file, like a file and its formatted version.
+    pub fn eq_ignoring_spans(&self, other: &ZngurFile) -> bool {
+        self.without_spans() == other.without_spans()
+    }
+
+    /// A copy of the file where no item has a location.
+    pub fn without_spans(&self) -> ZngurFile {
+        let mut file = self.clone();
+        for ty in &mut file.types {
+            ty.span = None;
+            for method in &mut ty.methods {
+                method.data.span = None;
+            }
+            for constructor in &mut ty.constructors {
+                constructor.span = None;
+            }
+            for field in &mut ty.fields {
+                field.span = None;
+            }
+        }
+        for tr in file.traits.values_mut() {
+            tr.span = None;
+            for method in &mut tr.methods {
+                method.span = None;
+            }
+        }
+        for func in &mut file.funcs {
+            func.span = None;
+        }
+        for func in &mut file.extern_cpp_funcs {
+            func.span = None;
+        }
+        for imp in &mut file.extern_cpp_impls {
+            imp.span = None;
+            for method in &mut imp.methods {
+                method.span = None;
+            }
+        }
+        file
+    }
+}
+
+/// The condition of a `#cfg(...)` attribute, with the same syntax as the `cfg` attribute of Rust.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CfgPredicate {
+    /// `name` or `name = "value"`.
+    Option {
+        name: String,
+        value: Option<String>,
+    },
+    Any(Vec<CfgPredicate>),
+    All(Vec<CfgPredicate>),
+    Not(Box<CfgPredicate>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum RustTrait {
     Normal(RustPathAndGenerics),
     Fn {
@@ -143,17 +484,23 @@ impl RustTrait {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum PrimitiveRustType {
     Uint(u32),
     Int(u32),
     Float(u32),
     Usize,
+    Isize,
     Bool,
+    Char,
     Str,
+    /// The never type `!`, only valid as the return type of Rust functions.
+    Never,
     ZngurCppOpaqueOwnedObject,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RustPathAndGenerics {
     pub path: Vec<String>,
     pub generics: Vec<RustType>,
@@ -161,12 +508,18 @@ pub struct RustPathAndGenerics {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum RustType {
     Primitive(PrimitiveRustType),
     Ref(Mutability, Box<RustType>),
     Raw(Mutability, Box<RustType>),
     Boxed(Box<RustType>),
     Slice(Box<RustType>),
+    Array(Box<RustType>, usize),
+    FnPtr {
+        inputs: Vec<RustType>,
+        output: Box<RustType>,
+    },
     Dyn(RustTrait, Vec<String>),
     Tuple(Vec<RustType>),
     Adt(RustPathAndGenerics),
@@ -176,6 +529,44 @@ impl RustType {
     pub const UNIT: Self = RustType::Tuple(Vec::new());
 }
 
+impl Display for ZngurWellknownTrait {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ZngurWellknownTrait::Unsized => write!(f, "?Sized"),
+            ZngurWellknownTrait::Operator(op) => write!(f, "{op}"),
+            ZngurWellknownTrait::IntoIterator { by_ref, item } => {
+                match by_ref {
+                    Some(Mutability::Not) => write!(f, "&")?,
+                    Some(Mutability::Mut) => write!(f, "&mut ")?,
+                    None => (),
+                }
+                write!(f, "IntoIterator<Item = {item}>")
+            }
+            ZngurWellknownTrait::Hash { hasher } => match hasher {
+                Some(hasher) => write!(f, "Hash<Hasher = {hasher}>"),
+                None => write!(f, "Hash"),
+            },
+            _ => write!(f, "{self:?}"),
+        }
+    }
+}
+
+impl Display for ZngurOperator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let args = self
+            .rhs
+            .iter()
+            .map(|x| x.to_string())
+            .chain(self.output.iter().map(|x| format!("Output = {x}")))
+            .collect::<Vec<_>>();
+        write!(f, "{}", self.op.name())?;
+        if !args.is_empty() {
+            write!(f, "<{}>", args.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
 impl Display for RustPathAndGenerics {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let RustPathAndGenerics {
@@ -231,8 +622,11 @@ impl Display for RustType {
                 PrimitiveRustType::Int(s) => write!(f, "i{s}"),
                 PrimitiveRustType::Float(s) => write!(f, "f{s}"),
                 PrimitiveRustType::Usize => write!(f, "usize"),
+                PrimitiveRustType::Isize => write!(f, "isize"),
                 PrimitiveRustType::Bool => write!(f, "bool"),
+                PrimitiveRustType::Char => write!(f, "char"),
                 PrimitiveRustType::Str => write!(f, "str"),
+                PrimitiveRustType::Never => write!(f, "!"),
                 PrimitiveRustType::ZngurCppOpaqueOwnedObject => {
                     write!(f, "ZngurCppOpaqueOwnedObject")
                 }
@@ -252,6 +646,29 @@ impl Display for RustType {
                 Ok(())
             }
             RustType::Slice(s) => write!(f, "[{s}]"),
+            RustType::Array(s, n) => write!(f, "[{s}; {n}]"),
+            RustType::FnPtr { inputs, output } => {
+                write!(f, "fn({})", inputs.iter().join(", "))?;
+                if **output != RustType::UNIT {
+                    write!(f, " -> {output}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Display for CfgPredicate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CfgPredicate::Option { name, value: None } => write!(f, "{name}"),
+            CfgPredicate::Option {
+                name,
+                value: Some(value),
+            } => write!(f, "{name} = \"{value}\""),
+            CfgPredicate::Any(v) => write!(f, "any({})", v.iter().join(", ")),
+            CfgPredicate::All(v) => write!(f, "all({})", v.iter().join(", ")),
+            CfgPredicate::Not(p) => write!(f, "not({p})"),
         }
     }
 }
@@ -0,0 +1,216 @@
+use itertools::Itertools;
+use zngur_def::*;
+use zngur_parser::{Diagnostic, LabelKind};
+
+use crate::rust::builtin_cpp_type;
+
+/// Finds constructs that are valid in a zng file, but the generator doesn't support yet.
+pub(crate) fn check_supported(zng: &ZngurFile) -> Vec<Diagnostic> {
+    let mut checker = Checker::default();
+    for ty_def in &zng.types {
+        let span = ty_def.span.as_ref();
+        checker.check_type(&ty_def.ty, span);
+        for method in &ty_def.methods {
+            checker.check_rust_fn(&method.data.inputs, method.data.span.as_ref());
+            checker.check_rust_fn_output(&method.data.output, method.data.span.as_ref());
+            for ty in &method.data.generics {
+                checker.check_type(ty, method.data.span.as_ref());
+            }
+            if let Some(deref) = &method.deref {
+                checker.check_type(deref, method.data.span.as_ref());
+            }
+        }
+        for constructor in &ty_def.constructors {
+            let inputs = constructor.inputs.iter().map(|x| &x.1);
+            checker.check_rust_fn(inputs, constructor.span.as_ref());
+        }
+        for field in &ty_def.fields {
+            checker.check_type(&field.ty, field.span.as_ref());
+        }
+        for tr in &ty_def.wellknown_traits {
+            match tr {
+                ZngurWellknownTrait::Operator(operator) => {
+                    for ty in operator.rhs.iter().chain(&operator.output) {
+                        checker.check_type(ty, span);
+                    }
+                }
+                ZngurWellknownTrait::IntoIterator { item, .. } => checker.check_type(item, span),
+                ZngurWellknownTrait::Hash {
+                    hasher: Some(hasher),
+                } => checker.check_type(hasher, span),
+                _ => (),
+            }
+        }
+        if ty_def.cpp_value.is_some() {
+            checker.error("`#cpp_value` is not supported by the dyn api yet", span);
+        }
+        match &ty_def.ty {
+            RustType::Boxed(b) if matches!(**b, RustType::Dyn(RustTrait::Fn { .. }, _)) => {
+                checker.error("`Box<dyn Fn>` is not supported by the dyn api yet", span);
+            }
+            RustType::Dyn(RustTrait::Fn { .. }, _) => {
+                checker.error("Declaring `dyn Fn` types is not supported yet", span);
+            }
+            _ => (),
+        }
+    }
+    let traits = zng
+        .traits
+        .values()
+        .sorted_by_key(|x| x.span.as_ref().map(|x| (x.file.clone(), x.start)));
+    for tr in traits {
+        checker.error(
+            "Traits are not supported by the dyn api yet",
+            tr.span.as_ref(),
+        );
+        for method in &tr.methods {
+            if method.receiver == ZngurMethodReceiver::Static {
+                checker.error(
+                    "Traits with static methods are not object safe",
+                    method.span.as_ref(),
+                );
+            }
+            checker.check_method(method);
+        }
+    }
+    for func in &zng.funcs {
+        checker.check_rust_fn(&func.inputs, func.span.as_ref());
+        checker.check_rust_fn_output(&func.output, func.span.as_ref());
+    }
+    for func in &zng.extern_cpp_funcs {
+        for ty in func.inputs.iter().chain(Some(&func.output)) {
+            checker.check_type(ty, func.span.as_ref());
+        }
+    }
+    for impl_block in &zng.extern_cpp_impls {
+        checker.check_type(&impl_block.ty, impl_block.span.as_ref());
+        for method in &impl_block.methods {
+            checker.check_method(method);
+        }
+    }
+    checker.diagnostics
+}
+
+pub(crate) fn is_supported(ty: &RustType) -> bool {
+    let mut checker = Checker::default();
+    checker.check_type(ty, None);
+    checker.diagnostics.is_empty()
+}
+
+#[derive(Default)]
+struct Checker {
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Checker {
+    fn error(&mut self, message: &str, span: Option<&ZngurSpan>) {
+        self.diagnostics
+            .push(Diagnostic::error(message).with_zngur_label(LabelKind::Primary, span, message));
+    }
+
+    fn check_method(&mut self, method: &ZngurMethod) {
+        for ty in method
+            .generics
+            .iter()
+            .chain(&method.inputs)
+            .chain(Some(&method.output))
+        {
+            self.check_type(ty, method.span.as_ref());
+        }
+    }
+
+    /// Checks the arguments of a Rust function that C++ calls, which can also be function pointers.
+    fn check_rust_fn<'a>(
+        &mut self,
+        inputs: impl IntoIterator<Item = &'a RustType>,
+        span: Option<&ZngurSpan>,
+    ) {
+        for ty in inputs {
+            self.check_rust_fn_type(ty, span);
+        }
+    }
+
+    /// Checks the return type of a Rust function that C++ calls, which can also be `!` or a function
+    /// pointer.
+    fn check_rust_fn_output(&mut self, output: &RustType, span: Option<&ZngurSpan>) {
+        if *output != RustType::Primitive(PrimitiveRustType::Never) {
+            self.check_rust_fn_type(output, span);
+        }
+    }
+
+    fn check_rust_fn_type(&mut self, ty: &RustType, span: Option<&ZngurSpan>) {
+        match ty {
+            RustType::FnPtr { inputs, output } => {
+                for ty in inputs.iter().chain(Some(&**output)) {
+                    self.check_type(ty, span);
+                }
+            }
+            _ => self.check_type(ty, span),
+        }
+    }
+
+    fn check_type(&mut self, ty: &RustType, span: Option<&ZngurSpan>) {
+        match ty {
+            RustType::Primitive(PrimitiveRustType::Float(bits)) if *bits != 32 && *bits != 64 => {
+                self.error(&format!("`f{bits}` is not supported"), span);
+            }
+            RustType::Primitive(PrimitiveRustType::Int(bits) | PrimitiveRustType::Uint(bits))
+                if ![8, 16, 32, 64, 128].contains(bits) =>
+            {
+                self.error(&format!("`{ty}` is not supported"), span);
+            }
+            RustType::Primitive(PrimitiveRustType::Never) => {
+                self.error(
+                    "The never type `!` is only supported as the return type of Rust functions",
+                    span,
+                );
+            }
+            RustType::Primitive(_) => (),
+            RustType::Raw(_, inner) => {
+                if builtin_cpp_type(ty).is_none() {
+                    self.error(
+                        &format!(
+                            "Raw pointers to `{inner}` are not supported. \
+Only pointers to integers, floats and other such pointers are supported."
+                        ),
+                        span,
+                    );
+                }
+            }
+            RustType::Ref(_, inner)
+            | RustType::Boxed(inner)
+            | RustType::Slice(inner)
+            | RustType::Array(inner, _) => {
+                self.check_type(inner, span);
+            }
+            RustType::FnPtr { .. } => {
+                self.error(
+                    "Function pointers are only supported as arguments and return types of Rust functions",
+                    span,
+                );
+            }
+            RustType::Tuple(fields) => {
+                for field in fields {
+                    self.check_type(field, span);
+                }
+            }
+            RustType::Adt(pg) => self.check_path_and_generics(pg, span),
+            RustType::Dyn(RustTrait::Normal(pg), _) => self.check_path_and_generics(pg, span),
+            RustType::Dyn(RustTrait::Fn { inputs, output, .. }, _) => {
+                for ty in inputs.iter().chain(Some(&**output)) {
+                    self.check_type(ty, span);
+                }
+            }
+        }
+    }
+
+    fn check_path_and_generics(&mut self, pg: &RustPathAndGenerics, span: Option<&ZngurSpan>) {
+        for ty in pg
+            .generics
+            .iter()
+            .chain(pg.named_generics.iter().map(|x| &x.1))
+        {
+            self.check_type(ty, span);
+        }
+    }
+}
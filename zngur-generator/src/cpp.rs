@@ -5,7 +5,10 @@ use std::{
     fmt::{Display, Write},
     iter, panic,
 };
-use zngur_def::{Mutability, RustTrait, ZngurField, ZngurMethodReceiver};
+use zngur_def::{
+    CfgPredicate, Mutability, RustTrait, RustType, ZngurField, ZngurMethodReceiver, ZngurOperator,
+    ZngurOperatorTrait,
+};
 
 use crate::{ZngurWellknownTraitData, rust::IntoCpp};
 
@@ -41,6 +44,9 @@ impl CppPath {
             && self.0 != ["rust", "Unit"]
             && self.0 != ["rust", "Ref"]
             && self.0 != ["rust", "RefMut"]
+            && self.0 != ["rust", "Array"]
+            && self.0 != ["rust", "I128"]
+            && self.0 != ["rust", "U128"]
     }
 
     pub(crate) fn from_rust_path(path: &[String]) -> CppPath {
@@ -74,56 +80,87 @@ impl Display for CppPath {
 }
 
 #[derive(Debug)]
-pub struct CppType {
-    pub path: CppPath,
-    pub generic_args: Vec<CppType>,
+pub enum CppType {
+    /// A type named by `path`, like `::rust::Ref< ::int32_t >`.
+    Path {
+        path: CppPath,
+        generic_args: Vec<CppType>,
+    },
+    /// A template argument that is a value, which is the length of `rust::Array`.
+    Value(usize),
+    /// The `void` that functions returning `!` return, which makes them `[[noreturn]]`.
+    Never,
 }
 
 impl CppType {
     pub fn into_ref(self) -> CppType {
-        CppType {
+        CppType::Path {
             path: CppPath::from("rust::Ref"),
             generic_args: vec![self],
         }
     }
 
+    /// The path of the type, which the types of definitions always have.
+    pub(crate) fn path(&self) -> &CppPath {
+        match self {
+            CppType::Path { path, .. } => path,
+            CppType::Value(_) | CppType::Never => unreachable!("`{self}` has no path"),
+        }
+    }
+
+    fn generic_args(&self) -> &[CppType] {
+        match self {
+            CppType::Path { generic_args, .. } => generic_args,
+            CppType::Value(_) | CppType::Never => &[],
+        }
+    }
+
     fn emit_specialization_decl(&self, state: &mut State) -> std::fmt::Result {
-        if self.generic_args.is_empty() {
-            write!(state, "struct {}", self.path.name())?;
+        if self.generic_args().is_empty() {
+            write!(state, "struct {}", self.path().name())?;
         } else {
             write!(
                 state,
                 "template<> struct {}< {} >",
-                self.path.name(),
-                self.generic_args.iter().join(", ")
+                self.path().name(),
+                self.generic_args().iter().join(", ")
             )?;
         }
         Ok(())
     }
 
     fn emit_header(&self, state: &mut State) -> std::fmt::Result {
-        for x in &self.generic_args {
+        let CppType::Path { path, generic_args } = self else {
+            return Ok(());
+        };
+        for x in generic_args {
             x.emit_header(state)?;
         }
-        if !self.path.need_header() {
+        if !path.need_header() {
             return Ok(());
         }
-        self.path.emit_in_namespace(state, |state| {
-            if !self.generic_args.is_empty() {
+        path.emit_in_namespace(state, |state| {
+            if !generic_args.is_empty() {
                 writeln!(state, "template<typename ...T>")?;
             }
-            writeln!(state, "struct {};", self.path.name())
+            writeln!(state, "struct {};", path.name())
         })
     }
 }
 
 impl Display for CppType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.path)?;
-        if !self.generic_args.is_empty() {
-            write!(f, "< {} >", self.generic_args.iter().join(", "))?;
+        match self {
+            CppType::Path { path, generic_args } => {
+                write!(f, "{path}")?;
+                if !generic_args.is_empty() {
+                    write!(f, "< {} >", generic_args.iter().join(", "))?;
+                }
+                Ok(())
+            }
+            CppType::Value(value) => write!(f, "{value}"),
+            CppType::Never => write!(f, "void"),
         }
-        Ok(())
     }
 }
 
@@ -163,13 +200,13 @@ impl From<&str> for CppType {
     fn from(value: &str) -> Self {
         let value = value.trim();
         match value.split_once('<') {
-            None => CppType {
+            None => CppType::Path {
                 path: CppPath::from(value),
                 generic_args: vec![],
             },
             Some((path, generics)) => {
                 let generics = generics.strip_suffix('>').unwrap();
-                CppType {
+                CppType::Path {
                     path: CppPath::from(path),
                     generic_args: split_string(generics).map(|x| CppType::from(&*x)).collect(),
                 }
@@ -218,6 +255,7 @@ pub struct CppTraitMethod {
     pub rust_link_name: String,
     pub inputs: Vec<CppType>,
     pub output: CppType,
+    pub doc: Option<String>,
 }
 
 #[derive(Debug)]
@@ -228,6 +266,15 @@ pub struct CppFnSig {
 }
 
 impl CppFnSig {
+    /// Functions returning `!` are mapped to `[[noreturn]]` functions returning `void`.
+    fn noreturn(&self) -> &'static str {
+        if matches!(self.output, CppType::Never) {
+            "[[noreturn]] "
+        } else {
+            ""
+        }
+    }
+
     fn emit_rust_link(&self, state: &mut State) -> std::fmt::Result {
         write!(state, "void {}(", self.rust_link_name)?;
         for n in 0..self.inputs.len() {
@@ -280,15 +327,24 @@ impl CppFnSig {
             output,
             rust_link_name,
         } = self;
+        let noreturn = self.noreturn();
+        let (declare_o, o_ptr, return_o) = if noreturn.is_empty() {
+            (
+                format!("{output} o = ::rust::__zngur_internal_uninit< {output} >();"),
+                "::rust::__zngur_internal_data_ptr(o)",
+                "::rust::__zngur_internal_assume_init(o);\n            return o;",
+            )
+        } else {
+            (String::new(), "nullptr", "::std::abort();")
+        };
         writeln!(
             state,
-            "inline {output} {fn_name}({dyn_api_def}{input_defs}) noexcept {{
-            {output} o{{}};
+            "{noreturn}inline {output} {fn_name}({dyn_api_def}{input_defs}) noexcept {{
+            {declare_o}
             {deinits}
-            api->{rust_link_name}({input_args}::rust::__zngur_internal_data_ptr(o));
+            api->{rust_link_name}({input_args}{o_ptr});
             {panic_handler}
-            ::rust::__zngur_internal_assume_init(o);
-            return o;
+            {return_o}
         }}",
             dyn_api_def = if inputs.is_empty() {
                 "__zngur_dyn_api* api"
@@ -314,6 +370,7 @@ impl CppFnSig {
 pub struct CppFnDefinition {
     pub name: CppPath,
     pub sig: CppFnSig,
+    pub doc: Option<String>,
 }
 
 pub struct CppExportedFnDefinition {
@@ -330,6 +387,7 @@ pub struct CppExportedImplDefinition {
 impl CppFnDefinition {
     fn emit_cpp_def(&self, state: &mut State) -> std::fmt::Result {
         self.name.emit_in_namespace(state, |state| {
+            emit_doc(state, self.doc.as_deref())?;
             self.sig.emit_cpp_def(state, self.name.name())
         })
     }
@@ -340,6 +398,7 @@ pub struct CppMethod {
     pub name: String,
     pub kind: ZngurMethodReceiver,
     pub sig: CppFnSig,
+    pub doc: Option<String>,
 }
 
 #[derive(Debug)]
@@ -357,6 +416,7 @@ pub enum CppTraitDefinition {
         methods: Vec<CppTraitMethod>,
         link_name: String,
         link_name_ref: String,
+        doc: Option<String>,
     },
 }
 
@@ -400,11 +460,13 @@ impl CppTraitDefinition {
             methods,
             link_name: _,
             link_name_ref: _,
+            doc,
         } = self
         else {
             return Ok(());
         };
-        as_ty.path.emit_in_namespace(state, |state| {
+        as_ty.path().emit_in_namespace(state, |state| {
+            emit_doc(state, doc.as_deref())?;
             as_ty.emit_specialization_decl(state)?;
             write!(
                 state,
@@ -412,9 +474,10 @@ impl CppTraitDefinition {
     public:
         virtual ~{}() {{}}
     "#,
-                as_ty.path.name(),
+                as_ty.path().name(),
             )?;
             for method in methods {
+                emit_doc(state, method.doc.as_deref())?;
                 write!(
                     state,
                     r#"
@@ -447,6 +510,7 @@ impl CppTraitDefinition {
                 methods,
                 link_name: _,
                 link_name_ref: _,
+                doc: _,
             } => {
                 for method in methods {
                     write!(state, "void {}(uint8_t* data", method.rust_link_name)?;
@@ -487,6 +551,13 @@ pub enum CppLayoutPolicy {
         size: usize,
         align: usize,
     },
+    /// Stack allocated in storage of `size` and `align`, which might be larger than the type. The
+    /// actual size is returned by `size_fn`.
+    Conservative {
+        size: usize,
+        align: usize,
+        size_fn: String,
+    },
     HeapAllocated {
         size_fn: String,
         alloc_fn: String,
@@ -500,13 +571,15 @@ pub struct CppTypeDefinition {
     pub ty: CppType,
     pub layout: CppLayoutPolicy,
     pub methods: Vec<CppMethod>,
-    pub constructors: Vec<CppFnSig>,
+    /// Signatures of the C++ constructors, with their doc comments.
+    pub constructors: Vec<(CppFnSig, Option<String>)>,
     pub fields: Vec<ZngurField>,
     pub from_trait: Option<RustTrait>,
     pub from_trait_ref: Option<RustTrait>,
     pub wellknown_traits: Vec<ZngurWellknownTraitData>,
     pub cpp_value: Option<(String, String)>,
     pub cpp_ref: Option<String>,
+    pub doc: Option<String>,
 }
 
 impl Default for CppTypeDefinition {
@@ -522,11 +595,124 @@ impl Default for CppTypeDefinition {
             from_trait_ref: None,
             cpp_value: None,
             cpp_ref: None,
+            doc: None,
         }
     }
 }
 
 impl CppTypeDefinition {
+    /// The `IntoIterator` of the type if `by_ref` is `None`, or of the reference to it otherwise,
+    /// as the item type and the names of the `into_iter`, `next` and `drop` functions.
+    fn iterator_of(&self, by_ref: Option<Mutability>) -> Option<IntoIteratorFns<'_>> {
+        self.wellknown_traits.iter().find_map(|x| match x {
+            ZngurWellknownTraitData::IntoIterator {
+                by_ref: b,
+                item,
+                into_iter,
+                next,
+                drop,
+            } if *b == by_ref => Some((item, &**into_iter, &**next, &**drop)),
+            _ => None,
+        })
+    }
+
+    /// The `IntoIterator` used by `begin()` of `ref_kind`, where a `RefMut` falls back to the one of
+    /// `&T`, like methods that take `&self`.
+    fn ref_iterator_of(&self, ref_kind: &str) -> Option<IntoIteratorFns<'_>> {
+        match ref_kind {
+            "Ref" => self.iterator_of(Some(Mutability::Not)),
+            _ => self
+                .iterator_of(Some(Mutability::Mut))
+                .or_else(|| self.iterator_of(Some(Mutability::Not))),
+        }
+    }
+
+    /// The `begin()` overloads of the owned type, as the ref qualifier of each one, the reference
+    /// that it iterates through, or `None` if it consumes the value, and its `IntoIterator`. Only an
+    /// rvalue is consumed, so that iterating a named value in C++ doesn't move out of it.
+    fn owned_iterators(&self) -> Vec<(&'static str, Option<&'static str>, IntoIteratorFns<'_>)> {
+        [
+            ("const &", Some("Ref"), Some(Mutability::Not)),
+            ("&", Some("RefMut"), Some(Mutability::Mut)),
+            ("&&", None, None),
+        ]
+        .into_iter()
+        .filter_map(|(qualifier, via, by_ref)| Some((qualifier, via, self.iterator_of(by_ref)?)))
+        .collect()
+    }
+
+    /// The name of the function that formats the type for `to_string` and `operator<<`, which
+    /// uses `Display` if the type has it, and `Debug` otherwise.
+    fn fmt_fn(&self) -> Option<&str> {
+        let display = self.wellknown_traits.iter().find_map(|x| match x {
+            ZngurWellknownTraitData::Display { fmt } => Some(&**fmt),
+            _ => None,
+        });
+        display.or_else(|| {
+            self.wellknown_traits.iter().find_map(|x| match x {
+                ZngurWellknownTraitData::Debug { fmt, .. } => Some(&**fmt),
+                _ => None,
+            })
+        })
+    }
+
+    /// The `Index` and `IndexMut` operators of the type, with the names of their Rust functions.
+    fn index_operators(&self) -> impl Iterator<Item = (&ZngurOperator, &str)> {
+        self.wellknown_traits.iter().filter_map(|x| match x {
+            ZngurWellknownTraitData::Operator {
+                operator,
+                link_name,
+            } if operator.op.is_index() => Some((operator, &**link_name)),
+            _ => None,
+        })
+    }
+
+    /// The `operator[]`s of `ref_kind`, which is `Ref` or `RefMut`. A `RefMut` uses `IndexMut`
+    /// instead of `Index` when both take the same index type.
+    fn ref_index_operators<'a>(
+        &'a self,
+        ref_kind: &'a str,
+    ) -> impl Iterator<Item = (&'a ZngurOperator, &'a str)> {
+        self.index_operators().filter(move |(operator, _)| {
+            let is_mut = operator.op == ZngurOperatorTrait::IndexMut;
+            match ref_kind {
+                "Ref" => !is_mut,
+                _ => {
+                    is_mut
+                        || !self.index_operators().any(|(other, _)| {
+                            other.op == ZngurOperatorTrait::IndexMut && other.rhs == operator.rhs
+                        })
+                }
+            }
+        })
+    }
+
+    /// The name of the `Default::default` function, if the type has `Default`.
+    fn default_fn(&self) -> Option<&str> {
+        self.wellknown_traits.iter().find_map(|x| match x {
+            ZngurWellknownTraitData::Default { default } => Some(&**default),
+            _ => None,
+        })
+    }
+
+    /// The name of the `Clone::clone` function, if the type has `Clone`.
+    fn clone_fn(&self) -> Option<&str> {
+        self.wellknown_traits.iter().find_map(|x| match x {
+            ZngurWellknownTraitData::Clone { clone } => Some(&**clone),
+            _ => None,
+        })
+    }
+
+    /// The element type and the length of `rust::Array` types.
+    fn array_element(&self) -> Option<(&CppType, usize)> {
+        match self.ty.generic_args() {
+            [element, CppType::Value(len)] if self.ty.path().0 == ["rust", "Array"] => {
+                Some((element, *len))
+            }
+            _ => None,
+        }
+    }
+
     fn emit_field_specialization(&self, state: &mut State) -> std::fmt::Result {
         for field_kind in ["FieldOwned", "FieldRef", "FieldRefMut"] {
             writeln!(
@@ -539,6 +725,7 @@ impl CppTypeDefinition {
                 ty = self.ty,
             )?;
             for field in &self.fields {
+                emit_doc(state, field.doc.as_deref())?;
                 writeln!(
                     state,
                     "[[no_unique_address]] {field_kind}<{}, OFFSET + {}> {};",
@@ -557,9 +744,11 @@ impl CppTypeDefinition {
                         inputs,
                         output,
                     } = &method.sig;
+                    emit_doc(state, method.doc.as_deref())?;
                     writeln!(
                         state,
-                        "{output} {fn_name}({input_defs}) const noexcept ;",
+                        "{noreturn}{output} {fn_name}({input_defs}) const noexcept ;",
+                        noreturn = method.sig.noreturn(),
                         fn_name = &method.name,
                         input_defs = inputs
                             .iter()
@@ -580,7 +769,7 @@ impl CppTypeDefinition {
             let is_unsized = self
                 .wellknown_traits
                 .contains(&ZngurWellknownTraitData::Unsized);
-            if self.ty.path.to_string() == "::rust::Str" && ref_kind == "Ref" {
+            if self.ty.path().to_string() == "::rust::Str" && ref_kind == "Ref" {
                 writeln!(
                     state,
                     r#"
@@ -631,6 +820,7 @@ struct {ref_kind}< {ty} > {{
                     )?;
                 }
                 for field in &self.fields {
+                    emit_doc(state, field.doc.as_deref())?;
                     writeln!(
                         state,
                         "[[no_unique_address]] ::rust::Field{ref_kind}<{}, {}> {};",
@@ -721,7 +911,7 @@ private:
                         r#"
 inline {ty}({as_std_function} f);
 "#,
-                        ty = self.ty.path.name(),
+                        ty = self.ty.path().name(),
                     )?;
                 }
                 Some(tr @ RustTrait::Normal { .. }) => {
@@ -768,9 +958,11 @@ inline {ty}({as_std_function} f);
                         inputs,
                         output,
                     } = &method.sig;
+                    emit_doc(state, method.doc.as_deref())?;
                     writeln!(
                         state,
-                        "{output} {fn_name}({dyn_api_def}{input_defs}) const noexcept ;",
+                        "{noreturn}{output} {fn_name}({dyn_api_def}{input_defs}) const noexcept ;",
+                        noreturn = method.sig.noreturn(),
                         dyn_api_def = if inputs.len().saturating_sub(1) == 0 {
                             "__zngur_dyn_api* api"
                         } else {
@@ -786,7 +978,37 @@ inline {ty}({as_std_function} f);
                     )?;
                 }
             }
-            if self.ty.path.to_string() == "::rust::Str" && ref_kind == "Ref" {
+            if let Some((element, len)) = self.array_element() {
+                writeln!(
+                    state,
+                    r#"
+    static constexpr size_t size() noexcept {{ return {len}; }}
+    ::rust::{ref_kind}< {element} > operator[](size_t i) const noexcept ;"#
+                )?;
+            }
+            if self.clone_fn().is_some() && self.layout != CppLayoutPolicy::OnlyByRef {
+                writeln!(state, "    {ty} clone() const noexcept ;", ty = self.ty)?;
+            }
+            if self.fmt_fn().is_some() {
+                writeln!(state, "    ::std::string to_string() const;")?;
+            }
+            for (operator, _) in self.ref_index_operators(ref_kind) {
+                let (output_kind, idx, output) = index_operator_types(operator);
+                writeln!(
+                    state,
+                    "    ::rust::{output_kind}< {output} > operator[]({idx} i0) const noexcept ;"
+                )?;
+            }
+            if let Some((item, ..)) = self.ref_iterator_of(ref_kind) {
+                writeln!(
+                    state,
+                    r#"
+    ::rust::ZngurIterator< {item} > begin() const noexcept ;
+    ::rust::ZngurIterator< {item} > end() const noexcept ;"#,
+                    item = item.into_cpp(),
+                )?;
+            }
+            if self.ty.path().to_string() == "::rust::Str" && ref_kind == "Ref" {
                 writeln!(
                     state,
                     r#"
@@ -825,7 +1047,7 @@ inline size_t __zngur_internal_size_of< {ref_kind} < {ty} > >() noexcept {{
                 ty = self.ty,
                 size = if is_unsized { 16 } else { 8 },
             )?;
-            if self.ty.path.to_string() == "::rust::Str" && ref_kind == "Ref" {
+            if self.ty.path().to_string() == "::rust::Str" && ref_kind == "Ref" {
                 writeln!(
                     state,
                     r#"
@@ -863,14 +1085,15 @@ namespace rust {{
 }}"#,
             ty = self.ty,
         )?;
-        self.ty.path.emit_in_namespace(state, |state| {
-            if self.ty.path.0 == ["rust", "Unit"] {
+        self.ty.path().emit_in_namespace(state, |state| {
+            if self.ty.path().0 == ["rust", "Unit"] {
                 write!(
                     state,
                     "template<> struct Tuple<> {{ ::std::array< ::uint8_t, 1> data; }};"
                 )?;
                 return Ok(());
             } else {
+                emit_doc(state, self.doc.as_deref())?;
                 self.ty.emit_specialization_decl(state)?;
             }
             match self.layout {
@@ -882,12 +1105,15 @@ namespace rust {{
 public:
     {ty}() = delete;
     "#,
-                        ty = self.ty.path.name(),
+                        ty = self.ty.path().name(),
                     )?;
                 }
-                CppLayoutPolicy::HeapAllocated { .. } | CppLayoutPolicy::StackAllocated { .. } => {
+                CppLayoutPolicy::HeapAllocated { .. }
+                | CppLayoutPolicy::StackAllocated { .. }
+                | CppLayoutPolicy::Conservative { .. } => {
                     match self.layout {
-                        CppLayoutPolicy::StackAllocated { size, align } => {
+                        CppLayoutPolicy::StackAllocated { size, align }
+                        | CppLayoutPolicy::Conservative { size, align, .. } => {
                             writeln!(
                                 state,
                                 r#"
@@ -920,7 +1146,7 @@ private:
 "#,
                         ty = self.ty,
                     )?;
-                    if self.ty.path.to_string() == "::rust::Bool" {
+                    if self.ty.path().to_string() == "::rust::Bool" {
                         assert_eq!(
                             self.layout,
                             CppLayoutPolicy::StackAllocated { size: 1, align: 1 }
@@ -936,6 +1162,35 @@ public:
     Bool(bool b) {{
         data[0] = b;
     }}
+private:
+    "#,
+                        )?;
+                    }
+                    if self.ty.path().to_string() == "::rust::Char" {
+                        assert_eq!(
+                            self.layout,
+                            CppLayoutPolicy::StackAllocated { size: 4, align: 4 }
+                        );
+                        assert!(is_copy);
+                        writeln!(
+                            state,
+                            r#"
+public:
+    static bool is_valid(char32_t c) {{
+        return c < 0xD800 || (c > 0xDFFF && c <= 0x10FFFF);
+    }}
+    operator char32_t() const {{
+        char32_t c;
+        memcpy(&c, data.data(), 4);
+        return c;
+    }}
+    Char(char32_t c) {{
+        if (!is_valid(c)) {{
+            ::std::cerr << "Invalid Unicode scalar value for `char`: " << static_cast<uint32_t>(c) << ::std::endl;
+            ::std::abort();
+        }}
+        memcpy(data.data(), &c, 4);
+    }}
 private:
     "#,
                         )?;
@@ -944,7 +1199,8 @@ private:
                         writeln!(state, "   bool drop_flag;")?;
                     }
                     let (alloc_heap, free_heap, copy_data) = match &self.layout {
-                        CppLayoutPolicy::StackAllocated { .. } => (
+                        CppLayoutPolicy::StackAllocated { .. }
+                        | CppLayoutPolicy::Conservative { .. } => (
                             "".to_owned(),
                             "".to_owned(),
                             "this->data = other.data;".to_owned(),
@@ -961,11 +1217,25 @@ private:
                         CppLayoutPolicy::OnlyByRef => unreachable!(),
                     };
                     writeln!(state, "public:")?;
+                    let init_drop_flag = if is_copy { "" } else { "drop_flag = true;" };
+                    let default_constructor = match self.default_fn() {
+                        Some(default) => format!(
+                            r#"{ty}() : {ty}(::rust::__zngur_internal_uninit_tag{{}}) {{
+        {default}(&data[0]);
+        {panic_handler}
+        {init_drop_flag}
+    }}"#,
+                            ty = self.ty.path().name(),
+                            panic_handler = state.panic_handler(),
+                        ),
+                        None => format!("{}() = delete;", self.ty.path().name()),
+                    };
                     if is_copy {
                         writeln!(
                             state,
                             r#"
-    {ty}() {{ {alloc_heap} }}
+    explicit {ty}(::rust::__zngur_internal_uninit_tag) {{ {alloc_heap} }}
+    {default_constructor}
     ~{ty}() {{ {free_heap} }}
     {ty}(const {ty}& other) {{
         {alloc_heap}
@@ -984,7 +1254,7 @@ private:
         return *this;
     }}
     "#,
-                            ty = self.ty.path.name(),
+                            ty = self.ty.path().name(),
                         )?;
                     } else {
                         let drop_in_place = self
@@ -997,18 +1267,52 @@ private:
                                 _ => None,
                             })
                             .unwrap();
+                        let copy = match self.clone_fn() {
+                            Some(clone) => {
+                                let panic_handler = state.panic_handler();
+                                format!(
+                                    r#"
+    {ty}(const {ty}& other) : drop_flag(false) {{
+        {alloc_heap}
+        *this = other;
+    }}
+    {ty}& operator=(const {ty}& other) {{
+        if (this != &other)
+        {{
+            if (drop_flag) {{
+                {drop_in_place}(&data[0]);
+                drop_flag = false;
+            }}
+            if (other.drop_flag) {{
+                uint8_t* other_ref = &other.data[0];
+                {clone}(reinterpret_cast<uint8_t*>(&other_ref), &data[0]);
+                {panic_handler}
+                drop_flag = true;
+            }}
+        }}
+        return *this;
+    }}"#,
+                                    ty = self.ty.path().name(),
+                                )
+                            }
+                            None => format!(
+                                r#"
+    {ty}(const {ty}& other) = delete;
+    {ty}& operator=(const {ty}& other) = delete;"#,
+                                ty = self.ty.path().name(),
+                            ),
+                        };
                         writeln!(
                             state,
                             r#"
-    {ty}() : drop_flag(false) {{ {alloc_heap} }}
+    explicit {ty}(::rust::__zngur_internal_uninit_tag) : drop_flag(false) {{ {alloc_heap} }}
+    {default_constructor}
     ~{ty}() {{
         if (drop_flag) {{
             {drop_in_place}(&data[0]);
         }}
         {free_heap}
-    }}
-    {ty}(const {ty}& other) = delete;
-    {ty}& operator=(const {ty}& other) = delete;
+    }}{copy}
     {ty}({ty}&& other) : drop_flag(false) {{
         {alloc_heap}
         *this = ::std::move(other);
@@ -1026,7 +1330,7 @@ private:
         return *this;
     }}
     "#,
-                            ty = self.ty.path.name(),
+                            ty = self.ty.path().name(),
                         )?;
                     }
                     match &self.from_trait {
@@ -1041,7 +1345,7 @@ private:
                                 r#"
     static inline {ty} make_box({as_std_function} f);
     "#,
-                                ty = self.ty.path.name(),
+                                ty = self.ty.path().name(),
                             )?;
                         }
                         Some(RustTrait::Normal { .. }) => {
@@ -1051,7 +1355,7 @@ private:
                         template<typename T, typename... Args>
                         static {ty} make_box(Args&&... args);
                         "#,
-                                ty = self.ty.path.name(),
+                                ty = self.ty.path().name(),
                             )?;
                         }
                         None => (),
@@ -1068,7 +1372,8 @@ private:
                 )?;
             }
             for method in &self.methods {
-                write!(state, "static ")?;
+                emit_doc(state, method.doc.as_deref())?;
+                write!(state, "{}static ", method.sig.noreturn())?;
                 method.sig.emit_cpp_header(state, &method.name)?;
                 if method.kind != ZngurMethodReceiver::Static {
                     let CppFnSig {
@@ -1076,9 +1381,11 @@ private:
                         inputs,
                         output,
                     } = &method.sig;
+                    emit_doc(state, method.doc.as_deref())?;
                     writeln!(
                         state,
-                        "{output} {fn_name}({dyn_api_def}{input_defs}) {const_kw} noexcept ;",
+                        "{noreturn}{output} {fn_name}({dyn_api_def}{input_defs}) {const_kw} noexcept ;",
+                        noreturn = method.sig.noreturn(),
                         fn_name = &method.name,
                         dyn_api_def = if inputs.len().saturating_sub(1) == 0 {
                             "__zngur_dyn_api* api"
@@ -1099,11 +1406,12 @@ private:
                     )?;
                 }
             }
-            for constructor in &self.constructors {
+            for (constructor, doc) in &self.constructors {
+                emit_doc(state, doc.as_deref())?;
                 writeln!(
                     state,
                     "{fn_name}({input_defs}) noexcept ;",
-                    fn_name = &self.ty.path.0.last().unwrap(),
+                    fn_name = &self.ty.path().0.last().unwrap(),
                     input_defs = constructor
                         .inputs
                         .iter()
@@ -1113,6 +1421,7 @@ private:
                 )?;
             }
             for field in &self.fields {
+                emit_doc(state, field.doc.as_deref())?;
                 writeln!(
                     state,
                     "[[no_unique_address]] ::rust::FieldOwned<{}, {}> {};",
@@ -1121,6 +1430,41 @@ private:
                     cpp_handle_field_name(&field.name),
                 )?;
             }
+            if let Some((element, len)) = self.array_element() {
+                if self.layout != CppLayoutPolicy::OnlyByRef {
+                    writeln!(
+                        state,
+                        r#"
+    static constexpr size_t size() noexcept {{ return {len}; }}
+    ::rust::Ref< {element} > operator[](size_t i) const noexcept ;
+    ::rust::RefMut< {element} > operator[](size_t i) noexcept ;"#
+                    )?;
+                }
+            }
+            if self.layout != CppLayoutPolicy::OnlyByRef {
+                for (qualifier, _, (item, ..)) in self.owned_iterators() {
+                    writeln!(
+                        state,
+                        r#"
+    ::rust::ZngurIterator< {item} > begin() {qualifier} noexcept ;
+    ::rust::ZngurIterator< {item} > end() {qualifier} noexcept ;"#,
+                        item = item.into_cpp(),
+                    )?;
+                }
+            }
+            if self.fmt_fn().is_some() && self.layout != CppLayoutPolicy::OnlyByRef {
+                writeln!(state, "    ::std::string to_string() const;")?;
+            }
+            if self.layout != CppLayoutPolicy::OnlyByRef {
+                for (operator, _) in self.index_operators() {
+                    let (output_kind, idx, output) = index_operator_types(operator);
+                    let const_kw = if output_kind == "Ref" { "const" } else { "" };
+                    writeln!(
+                        state,
+                        "    ::rust::{output_kind}< {output} > operator[]({idx} i0) {const_kw} noexcept ;"
+                    )?;
+                }
+            }
             writeln!(state, "}};")
         })?;
         let ty = &self.ty;
@@ -1138,7 +1482,8 @@ namespace rust {{
         "#,
                     )?;
                 }
-                CppLayoutPolicy::HeapAllocated { size_fn, .. } => {
+                CppLayoutPolicy::HeapAllocated { size_fn, .. }
+                | CppLayoutPolicy::Conservative { size_fn, .. } => {
                     writeln!(
                         state,
                         r#"
@@ -1221,8 +1566,8 @@ namespace rust {{
             .contains(&ZngurWellknownTraitData::Unsized);
         let cpp_type = &self.ty.to_string();
         let my_name = cpp_type.strip_prefix("::").unwrap();
-        for c in &self.constructors {
-            let fn_name = my_name.to_owned() + "::" + self.ty.path.0.last().unwrap();
+        for (c, _) in &self.constructors {
+            let fn_name = my_name.to_owned() + "::" + self.ty.path().0.last().unwrap();
             let CppFnSig {
                 inputs,
                 output: _,
@@ -1278,7 +1623,7 @@ namespace rust {{
                     r#"
 {my_name} {my_name}::make_box({as_std_function} f) {{
 auto data = new {as_std_function}(f);
-{my_name} o;
+{my_name} o = ::rust::__zngur_internal_uninit< {my_name} >();
 ::rust::__zngur_internal_assume_init(o);
 {link_name}(
 reinterpret_cast<uint8_t*>(data),
@@ -1300,6 +1645,7 @@ return o;
                 methods: _,
                 link_name,
                 link_name_ref: _,
+                doc: _,
             }) => {
                 writeln!(
                     state,
@@ -1308,7 +1654,7 @@ template<typename T, typename... Args>
 {my_name} {my_name}::make_box(Args&&... args) {{
 auto data = new T(::std::forward<Args>(args)...);
 auto data_as_impl = dynamic_cast< {as_ty}*>(data);
-{my_name} o;
+{my_name} o = ::rust::__zngur_internal_uninit< {my_name} >();
 ::rust::__zngur_internal_assume_init(o);
 {link_name}(
 reinterpret_cast<uint8_t*>(data_as_impl),
@@ -1328,13 +1674,14 @@ return o;
         }
         match self.from_trait_ref.as_ref().and_then(|k| traits.get(k)) {
             Some(CppTraitDefinition::Fn { .. }) => {
-                todo!()
+                unreachable!("rejected by `check_supported`")
             }
             Some(CppTraitDefinition::Normal {
                 as_ty,
                 methods: _,
                 link_name: _,
                 link_name_ref,
+                doc: _,
             }) => {
                 for ref_kind in ["Ref", " RefMut"] {
                     writeln!(
@@ -1358,9 +1705,147 @@ auto data_as_impl = &args;
             }
             None => (),
         }
+        if let Some((element, len)) = self.array_element() {
+            let bounds_check = format!(
+                r#"if (i >= {len}) {{
+        ::std::cerr << "index out of bounds: the len is {len} but the index is " << i << ::std::endl;
+        ::std::abort();
+    }}"#
+            );
+            if self.layout != CppLayoutPolicy::OnlyByRef {
+                for (ref_kind, const_kw) in [("Ref", "const"), ("RefMut", "")] {
+                    writeln!(
+                        state,
+                        r#"
+inline ::rust::{ref_kind}< {element} > {my_name}::operator[](size_t i) {const_kw} noexcept {{
+    ::rust::__zngur_internal_check_init< {cpp_type} >(*this);
+    {bounds_check}
+    return ::rust::__zngur_internal_array_element< ::rust::{ref_kind}< {element} >, {element} >(
+        ::rust::__zngur_internal_data_ptr(*this), i);
+}}"#
+                    )?;
+                }
+            }
+            for ref_kind in ["Ref", "RefMut"] {
+                writeln!(
+                    state,
+                    r#"
+inline ::rust::{ref_kind}< {element} > rust::{ref_kind}< {cpp_type} >::operator[](size_t i) const noexcept {{
+    {bounds_check}
+    return ::rust::__zngur_internal_array_element< ::rust::{ref_kind}< {element} >, {element} >(
+        reinterpret_cast<uint8_t*>(data), i);
+}}"#
+                )?;
+            }
+        }
+        if let Some(clone) = self.clone_fn() {
+            if self.layout != CppLayoutPolicy::OnlyByRef {
+                let panic_handler = state.panic_handler();
+                for ref_kind in ["Ref", "RefMut"] {
+                    writeln!(
+                        state,
+                        r#"
+inline {cpp_type} rust::{ref_kind}< {cpp_type} >::clone() const noexcept {{
+    {cpp_type} o = ::rust::__zngur_internal_uninit< {cpp_type} >();
+    {clone}(::rust::__zngur_internal_data_ptr(*this), ::rust::__zngur_internal_data_ptr(o));
+    {panic_handler}
+    ::rust::__zngur_internal_assume_init(o);
+    return o;
+}}"#
+                    )?;
+                }
+            }
+        }
+        if let Some(fmt) = self.fmt_fn() {
+            let panic_handler = state.panic_handler();
+            for ref_kind in ["Ref", "RefMut"] {
+                writeln!(
+                    state,
+                    r#"
+inline ::std::string rust::{ref_kind}< {cpp_type} >::to_string() const {{
+    ::std::string o;
+    {fmt}(::rust::__zngur_internal_data_ptr(*this), reinterpret_cast<uint8_t*>(&o), ::rust::__zngur_internal_append_to_string);
+    {panic_handler}
+    return o;
+}}"#
+                )?;
+            }
+            if self.layout != CppLayoutPolicy::OnlyByRef {
+                writeln!(
+                    state,
+                    r#"
+inline ::std::string {my_name}::to_string() const {{
+    return ::rust::Ref< {cpp_type} >(*this).to_string();
+}}"#
+                )?;
+            }
+        }
+        for ref_kind in ["Ref", "RefMut"] {
+            for (operator, link_name) in self.ref_index_operators(ref_kind) {
+                let (output_kind, idx, output) = index_operator_types(operator);
+                let panic_handler = state.panic_handler();
+                writeln!(
+                    state,
+                    r#"
+inline ::rust::{output_kind}< {output} > rust::{ref_kind}< {cpp_type} >::operator[]({idx} i0) const noexcept {{
+    auto o = ::rust::__zngur_internal_uninit< ::rust::{output_kind}< {output} > >();
+    ::rust::__zngur_internal_assume_deinit(i0);
+    {link_name}(::rust::__zngur_internal_data_ptr(*this), ::rust::__zngur_internal_data_ptr(i0), ::rust::__zngur_internal_data_ptr(o));
+    {panic_handler}
+    ::rust::__zngur_internal_assume_init(o);
+    return o;
+}}"#
+                )?;
+            }
+        }
+        if self.layout != CppLayoutPolicy::OnlyByRef {
+            for (operator, _) in self.index_operators() {
+                let (output_kind, idx, output) = index_operator_types(operator);
+                let const_kw = if output_kind == "Ref" { "const" } else { "" };
+                writeln!(
+                    state,
+                    r#"
+inline ::rust::{output_kind}< {output} > {my_name}::operator[]({idx} i0) {const_kw} noexcept {{
+    return ::rust::{output_kind}< {cpp_type} >(*this)[::std::move(i0)];
+}}"#
+                )?;
+            }
+        }
+        if self.layout != CppLayoutPolicy::OnlyByRef {
+            for (qualifier, via, into_iterator) in self.owned_iterators() {
+                match via {
+                    Some(ref_kind) => {
+                        let item = into_iterator.0.into_cpp();
+                        writeln!(
+                            state,
+                            r#"
+inline ::rust::ZngurIterator< {item} > {my_name}::begin() {qualifier} noexcept {{
+    return ::rust::{ref_kind}< {cpp_type} >(*this).begin();
+}}
+
+inline ::rust::ZngurIterator< {item} > {my_name}::end() {qualifier} noexcept {{
+    return ::rust::ZngurIterator< {item} >();
+}}"#
+                        )?;
+                    }
+                    None => emit_into_iterator(state, my_name, qualifier, true, into_iterator)?,
+                }
+            }
+        }
+        for ref_kind in ["Ref", "RefMut"] {
+            if let Some(into_iterator) = self.ref_iterator_of(ref_kind) {
+                let this = format!("rust::{ref_kind}< {cpp_type} >");
+                emit_into_iterator(state, &this, "const", false, into_iterator)?;
+            }
+        }
         for method in &self.methods {
             let fn_name = my_name.to_owned() + "::" + &method.name;
             method.sig.emit_cpp_def(state, &fn_name)?;
+            let return_kw = if method.sig.noreturn().is_empty() {
+                "return "
+            } else {
+                ""
+            };
             if let ZngurMethodReceiver::Ref(m) = method.kind {
                 let ref_kinds: &[&str] = match m {
                     Mutability::Mut => &["RefMut"],
@@ -1380,7 +1865,7 @@ auto data_as_impl = &args;
                         state,
                         "template<size_t OFFSET>
                         inline {output} rust::{field_kind}< {ty}, OFFSET >::{method_name}({input_defs}) const noexcept {{
-                    return {fn_name}(*this{input_args});
+                    {return_kw}{fn_name}(*this{input_args});
                 }}",
                         ty = &self.ty,
                         method_name = &method.name,
@@ -1404,7 +1889,7 @@ auto data_as_impl = &args;
                     writeln!(
                         state,
                         "inline {output} rust::{ref_kind}< {ty} >::{method_name}({dyn_api_def}{input_defs}) const noexcept {{
-                    return {fn_name}(api, *this{input_args});
+                    {return_kw}{fn_name}(api, *this{input_args});
                 }}",
                         ty = &self.ty,
                         method_name = &method.name,
@@ -1437,7 +1922,7 @@ auto data_as_impl = &args;
                 writeln!(
                     state,
                     "inline {output} {fn_name}({dyn_api_def}{input_defs}) {const_kw} noexcept {{
-                    return {fn_name}(api, {this_arg}{input_args});
+                    {return_kw}{fn_name}(api, {this_arg}{input_args});
                 }}",
                     this_arg = match method.kind {
                         ZngurMethodReceiver::Ref(_) => "*this",
@@ -1474,6 +1959,7 @@ auto data_as_impl = &args;
                 ZngurWellknownTraitData::Debug {
                     pretty_print,
                     debug_print: _, // TODO: use it
+                    fmt: _,
                 } => {
                     if !is_unsized {
                         writeln!(
@@ -1552,20 +2038,285 @@ auto data_as_impl = &args;
                         )?;
                     }
                 }
+                ZngurWellknownTraitData::PartialEq { eq } => {
+                    let panic_handler = state.panic_handler();
+                    self.ty.path().emit_in_namespace(state, |state| {
+                        writeln!(
+                            state,
+                            r#"
+inline bool operator==(::rust::Ref< {ty} > a, ::rust::Ref< {ty} > b) {{
+    bool o;
+    {eq}(::rust::__zngur_internal_data_ptr(a), ::rust::__zngur_internal_data_ptr(b), reinterpret_cast<uint8_t*>(&o));
+    {panic_handler}
+    return o;
+}}
+
+inline bool operator!=(::rust::Ref< {ty} > a, ::rust::Ref< {ty} > b) {{
+    return !(a == b);
+}}"#,
+                            ty = self.ty,
+                        )
+                    })?;
+                }
+                ZngurWellknownTraitData::PartialOrd { partial_cmp } => {
+                    let has_ord = self
+                        .wellknown_traits
+                        .iter()
+                        .any(|x| matches!(x, ZngurWellknownTraitData::Ord { .. }));
+                    let panic_handler = state.panic_handler();
+                    self.ty.path().emit_in_namespace(state, |state| {
+                        // `partial_cmp` returns -1, 0 and 1 for `Less`, `Equal` and `Greater`,
+                        // and 2 for `None`.
+                        for (op, result) in [
+                            ("<", "o == -1"),
+                            ("<=", "o == -1 || o == 0"),
+                            (">", "o == 1"),
+                            (">=", "o == 0 || o == 1"),
+                        ] {
+                            writeln!(
+                                state,
+                                r#"
+inline bool operator{op}(::rust::Ref< {ty} > a, ::rust::Ref< {ty} > b) {{
+    int8_t o;
+    {partial_cmp}(::rust::__zngur_internal_data_ptr(a), ::rust::__zngur_internal_data_ptr(b), reinterpret_cast<uint8_t*>(&o));
+    {panic_handler}
+    return {result};
+}}"#,
+                                ty = self.ty,
+                            )?;
+                        }
+                        if !has_ord {
+                            writeln!(
+                                state,
+                                r#"
+#ifdef __cpp_impl_three_way_comparison
+inline ::std::partial_ordering operator<=>(::rust::Ref< {ty} > a, ::rust::Ref< {ty} > b) {{
+    int8_t o;
+    {partial_cmp}(::rust::__zngur_internal_data_ptr(a), ::rust::__zngur_internal_data_ptr(b), reinterpret_cast<uint8_t*>(&o));
+    {panic_handler}
+    switch (o) {{
+        case -1: return ::std::partial_ordering::less;
+        case 0: return ::std::partial_ordering::equivalent;
+        case 1: return ::std::partial_ordering::greater;
+        default: return ::std::partial_ordering::unordered;
+    }}
+}}
+#endif"#,
+                                ty = self.ty,
+                            )?;
+                        }
+                        Ok(())
+                    })?;
+                }
+                ZngurWellknownTraitData::Ord { cmp } => {
+                    let panic_handler = state.panic_handler();
+                    self.ty.path().emit_in_namespace(state, |state| {
+                        writeln!(
+                            state,
+                            r#"
+#ifdef __cpp_impl_three_way_comparison
+inline ::std::strong_ordering operator<=>(::rust::Ref< {ty} > a, ::rust::Ref< {ty} > b) {{
+    int8_t o;
+    {cmp}(::rust::__zngur_internal_data_ptr(a), ::rust::__zngur_internal_data_ptr(b), reinterpret_cast<uint8_t*>(&o));
+    {panic_handler}
+    return o < 0 ? ::std::strong_ordering::less
+        : o > 0 ? ::std::strong_ordering::greater
+        : ::std::strong_ordering::equal;
+}}
+#endif"#,
+                            ty = self.ty,
+                        )
+                    })?;
+                }
+                ZngurWellknownTraitData::Operator {
+                    operator,
+                    link_name,
+                } => {
+                    // `operator[]` can only be a member, so it is declared in the classes.
+                    if !operator.op.is_index() {
+                        self.ty.path().emit_in_namespace(state, |state| {
+                            self.emit_operator(state, operator, link_name)
+                        })?;
+                    }
+                }
+                ZngurWellknownTraitData::Hash { hash } => {
+                    let panic_handler = state.panic_handler();
+                    writeln!(
+                        state,
+                        r#"
+namespace std {{
+template<>
+struct hash< ::rust::Ref< {ty} > > {{
+    size_t operator()(::rust::Ref< {ty} > const& v) const noexcept {{
+        uint64_t o;
+        {hash}(::rust::__zngur_internal_data_ptr(v), reinterpret_cast<uint8_t*>(&o));
+        {panic_handler}
+        return static_cast<size_t>(o);
+    }}
+}};"#,
+                        ty = self.ty,
+                    )?;
+                    if !is_unsized {
+                        writeln!(
+                            state,
+                            r#"
+template<>
+struct hash< {ty} > {{
+    size_t operator()({ty} const& v) const noexcept {{
+        return hash< ::rust::Ref< {ty} > >()(v);
+    }}
+}};"#,
+                            ty = self.ty,
+                        )?;
+                    }
+                    writeln!(state, "}}")?;
+                }
                 ZngurWellknownTraitData::Unsized
                 | ZngurWellknownTraitData::Copy
-                | ZngurWellknownTraitData::Drop { .. } => {}
+                | ZngurWellknownTraitData::Drop { .. }
+                | ZngurWellknownTraitData::Clone { .. }
+                | ZngurWellknownTraitData::Default { .. }
+                | ZngurWellknownTraitData::Eq
+                | ZngurWellknownTraitData::IntoIterator { .. }
+                | ZngurWellknownTraitData::Display { .. } => {}
             }
         }
+        if self.fmt_fn().is_some() {
+            self.emit_fmt_support(state, is_unsized)?;
+        }
         Ok(())
     }
 
+    /// Emits `operator<<` and the `std::formatter` specializations of the type and its references,
+    /// which use their `to_string()`.
+    fn emit_fmt_support(&self, state: &mut State, is_unsized: bool) -> std::fmt::Result {
+        let ty = &self.ty;
+        self.ty.path().emit_in_namespace(state, |state| {
+            writeln!(
+                state,
+                r#"
+inline ::std::ostream& operator<<(::std::ostream& os, ::rust::Ref< {ty} > v) {{
+    return os << v.to_string();
+}}"#
+            )
+        })?;
+        let mut formatted = vec![
+            format!("::rust::Ref< {ty} >"),
+            format!("::rust::RefMut< {ty} >"),
+        ];
+        if !is_unsized && self.layout != CppLayoutPolicy::OnlyByRef {
+            formatted.push(ty.to_string());
+        }
+        writeln!(state, "#ifdef __cpp_lib_format")?;
+        for formatted in formatted {
+            writeln!(
+                state,
+                r#"
+template<>
+struct std::formatter< {formatted} > : ::std::formatter< ::std::string_view > {{
+    template<typename FormatContext>
+    auto format(const {formatted}& v, FormatContext& ctx) const {{
+        return ::std::formatter< ::std::string_view >::format(v.to_string(), ctx);
+    }}
+}};"#
+            )?;
+        }
+        writeln!(state, "#endif")
+    }
+
+    /// Emits the C++ operator of `operator`, which calls the Rust function `link_name`. Operands
+    /// are taken by value, like in Rust. Compound assignments take a `RefMut` and return it, with
+    /// an overload that takes and returns a C++ reference for owned types.
+    fn emit_operator(
+        &self,
+        state: &mut State,
+        operator: &ZngurOperator,
+        link_name: &str,
+    ) -> std::fmt::Result {
+        use ZngurOperatorTrait::*;
+        let ty = &self.ty;
+        let cpp_op = match operator.op {
+            Add | AddAssign => "+",
+            Sub | SubAssign | Neg => "-",
+            Mul | MulAssign => "*",
+            Div | DivAssign => "/",
+            Rem | RemAssign => "%",
+            BitAnd | BitAndAssign => "&",
+            BitOr | BitOrAssign => "|",
+            BitXor | BitXorAssign => "^",
+            Shl | ShlAssign => "<<",
+            Shr | ShrAssign => ">>",
+            // `Not` of integers is the bitwise not.
+            Not => "~",
+            Index | IndexMut => unreachable!("`operator[]` is emitted as a member"),
+        };
+        let rhs = match &operator.rhs {
+            Some(rhs) => rhs.into_cpp().to_string(),
+            None => ty.to_string(),
+        };
+        let panic_handler = state.panic_handler();
+        if operator.op.is_assign() {
+            writeln!(
+                state,
+                r#"
+inline ::rust::RefMut< {ty} > operator{cpp_op}=(::rust::RefMut< {ty} > i0, {rhs} i1) noexcept {{
+    ::rust::Unit o{{}};
+    ::rust::__zngur_internal_assume_deinit(i1);
+    {link_name}(::rust::__zngur_internal_data_ptr(i0), ::rust::__zngur_internal_data_ptr(i1), ::rust::__zngur_internal_data_ptr(o));
+    {panic_handler}
+    return i0;
+}}"#
+            )?;
+            if self.layout != CppLayoutPolicy::OnlyByRef {
+                writeln!(
+                    state,
+                    r#"
+inline {ty}& operator{cpp_op}=({ty}& i0, {rhs} i1) noexcept {{
+    ::rust::RefMut< {ty} >(i0) {cpp_op}= ::std::move(i1);
+    return i0;
+}}"#
+                )?;
+            }
+            return Ok(());
+        }
+        let output = operator.output.as_ref().unwrap().into_cpp();
+        let (inputs, args) = if operator.op.is_unary() {
+            (
+                format!("{ty} i0"),
+                "::rust::__zngur_internal_data_ptr(i0), ",
+            )
+        } else {
+            (
+                format!("{ty} i0, {rhs} i1"),
+                "::rust::__zngur_internal_data_ptr(i0), ::rust::__zngur_internal_data_ptr(i1), ",
+            )
+        };
+        let deinits = if operator.op.is_unary() {
+            "::rust::__zngur_internal_assume_deinit(i0);"
+        } else {
+            "::rust::__zngur_internal_assume_deinit(i0);
+    ::rust::__zngur_internal_assume_deinit(i1);"
+        };
+        writeln!(
+            state,
+            r#"
+inline {output} operator{cpp_op}({inputs}) noexcept {{
+    {output} o = ::rust::__zngur_internal_uninit< {output} >();
+    {deinits}
+    {link_name}({args}::rust::__zngur_internal_data_ptr(o));
+    {panic_handler}
+    ::rust::__zngur_internal_assume_init(o);
+    return o;
+}}"#
+        )
+    }
+
     fn get_link_names(&self) -> Vec<&str> {
         let mut names: Vec<&str> = Vec::new();
         for method in &self.methods {
             names.push(&method.sig.rust_link_name);
         }
-        for c in &self.constructors {
+        for (c, _) in &self.constructors {
             names.push(&c.rust_link_name);
         }
         assert!(
@@ -1582,29 +2333,54 @@ auto data_as_impl = &args;
             names.push(alloc_fn);
             names.push(free_fn);
         }
+        if let CppLayoutPolicy::Conservative { size_fn, .. } = &self.layout {
+            names.push(size_fn);
+        }
         for tr in &self.wellknown_traits {
             match tr {
                 ZngurWellknownTraitData::Debug {
                     pretty_print,
                     debug_print,
+                    fmt,
                 } => {
                     names.push(pretty_print);
                     names.push(debug_print);
+                    names.push(fmt);
                 }
-                ZngurWellknownTraitData::Unsized | ZngurWellknownTraitData::Copy => (),
+                ZngurWellknownTraitData::Display { fmt } => names.push(fmt),
+                ZngurWellknownTraitData::Unsized
+                | ZngurWellknownTraitData::Copy
+                | ZngurWellknownTraitData::Eq => (),
                 ZngurWellknownTraitData::Drop { drop_in_place } => {
                     names.push(drop_in_place);
                 }
+                ZngurWellknownTraitData::Clone { clone } => names.push(clone),
+                ZngurWellknownTraitData::Default { default } => names.push(default),
+                ZngurWellknownTraitData::PartialEq { eq } => names.push(eq),
+                ZngurWellknownTraitData::PartialOrd { partial_cmp } => names.push(partial_cmp),
+                ZngurWellknownTraitData::Ord { cmp } => names.push(cmp),
+                ZngurWellknownTraitData::Hash { hash } => names.push(hash),
+                ZngurWellknownTraitData::Operator { link_name, .. } => names.push(link_name),
+                ZngurWellknownTraitData::IntoIterator {
+                    into_iter,
+                    next,
+                    drop,
+                    ..
+                } => {
+                    names.push(into_iter);
+                    names.push(next);
+                    names.push(drop);
+                }
             }
         }
-        return names;
+        names
     }
 
     fn emit_rust_links(&self, state: &mut State) -> std::fmt::Result {
         for method in &self.methods {
             method.sig.emit_rust_link_decl(state)?;
         }
-        for c in &self.constructors {
+        for (c, _) in &self.constructors {
             c.emit_rust_link_decl(state)?;
         }
         if let Some(cpp_value) = &self.cpp_value {
@@ -1624,19 +2400,81 @@ auto data_as_impl = &args;
             writeln!(state, "uint8_t* {alloc_fn}();")?;
             writeln!(state, "void {free_fn}(uint8_t*);")?;
         }
+        if let CppLayoutPolicy::Conservative { size_fn, .. } = &self.layout {
+            writeln!(state, "size_t {size_fn}();")?;
+        }
         for tr in &self.wellknown_traits {
             match tr {
                 ZngurWellknownTraitData::Debug {
                     pretty_print,
                     debug_print,
+                    fmt,
                 } => {
                     writeln!(state, "void {pretty_print}(uint8_t *data);")?;
                     writeln!(state, "void {debug_print}(uint8_t *data);")?;
+                    emit_fmt_link(state, fmt)?;
                 }
-                ZngurWellknownTraitData::Unsized | ZngurWellknownTraitData::Copy => (),
+                ZngurWellknownTraitData::Display { fmt } => emit_fmt_link(state, fmt)?,
+                ZngurWellknownTraitData::Unsized
+                | ZngurWellknownTraitData::Copy
+                | ZngurWellknownTraitData::Eq => (),
                 ZngurWellknownTraitData::Drop { drop_in_place } => {
                     writeln!(state, "void {drop_in_place}(uint8_t *data);")?;
                 }
+                ZngurWellknownTraitData::Clone { clone } => {
+                    writeln!(state, "void {clone}(uint8_t *v, uint8_t *o) noexcept ;")?;
+                }
+                ZngurWellknownTraitData::Default { default } => {
+                    writeln!(state, "void {default}(uint8_t *o) noexcept ;")?;
+                }
+                ZngurWellknownTraitData::PartialEq { eq } => {
+                    writeln!(
+                        state,
+                        "void {eq}(uint8_t *a, uint8_t *b, uint8_t *o) noexcept ;"
+                    )?;
+                }
+                ZngurWellknownTraitData::PartialOrd { partial_cmp } => {
+                    writeln!(
+                        state,
+                        "void {partial_cmp}(uint8_t *a, uint8_t *b, uint8_t *o) noexcept ;"
+                    )?;
+                }
+                ZngurWellknownTraitData::Ord { cmp } => {
+                    writeln!(
+                        state,
+                        "void {cmp}(uint8_t *a, uint8_t *b, uint8_t *o) noexcept ;"
+                    )?;
+                }
+                ZngurWellknownTraitData::Hash { hash } => {
+                    writeln!(state, "void {hash}(uint8_t *v, uint8_t *o) noexcept ;")?;
+                }
+                ZngurWellknownTraitData::Operator {
+                    operator,
+                    link_name,
+                } => {
+                    let inputs = if operator.op.is_unary() { 1 } else { 2 };
+                    write!(state, "void {link_name}(")?;
+                    for n in 0..inputs {
+                        write!(state, "uint8_t* i{n},")?;
+                    }
+                    writeln!(state, "uint8_t* o) noexcept ;")?;
+                }
+                ZngurWellknownTraitData::IntoIterator {
+                    into_iter,
+                    next,
+                    drop,
+                    ..
+                } => {
+                    writeln!(
+                        state,
+                        "void {into_iter}(uint8_t* i0, uint8_t* o) noexcept ;"
+                    )?;
+                    writeln!(
+                        state,
+                        "void {next}(uint8_t* i0, uint8_t* o, uint8_t* found) noexcept ;"
+                    )?;
+                    writeln!(state, "void {drop}(uint8_t* i0) noexcept ;")?;
+                }
             }
         }
         Ok(())
@@ -1652,6 +2490,9 @@ pub struct CppFile {
     pub exported_impls: Vec<CppExportedImplDefinition>,
     pub additional_includes: String,
     pub panic_to_exception: bool,
+    /// `#cfg(...)` predicates of the zng file with their results, checked by the preprocessor when
+    /// possible.
+    pub cfgs: Vec<(CfgPredicate, bool)>,
 }
 
 impl CppFile {
@@ -1663,12 +2504,40 @@ impl CppFile {
 #include <cstdint>
 #include <cstring>
 #include <csignal>
+#include <cstdlib>
 #include <array>
 #include <iostream>
 #include <functional>
+#include <iterator>
+#include <memory>
+#include <string>
+#include <type_traits>
+#include <utility>
 #include <math.h>
 #include <dlfcn.h>
+#ifdef __cpp_impl_three_way_comparison
+#include <compare>
+#endif
+#if __cplusplus >= 202002L && __has_include(<format>)
+#include <format>
+#endif
 "#;
+        for (predicate, result) in &self.cfgs {
+            let predicate = match result {
+                true => predicate.clone(),
+                false => CfgPredicate::Not(Box::new(predicate.clone())),
+            };
+            let Some(condition) = cfg_to_cpp(&predicate) else {
+                continue;
+            };
+            writeln!(state, "#if !({condition})")?;
+            writeln!(
+                state,
+                r#"#error "This header is generated for targets where `cfg({})` holds""#,
+                predicate.to_string().replace('"', r#"\""#)
+            )?;
+            writeln!(state, "#endif")?;
+        }
         state.text += &self.additional_includes;
         if self.panic_to_exception {
             state.text += indoc::indoc! { r#"
@@ -1697,6 +2566,26 @@ namespace rust {
     template<typename T>
     inline size_t __zngur_internal_size_of() noexcept ;
 
+    // Tag of the constructor that creates an uninitialized value, see `__zngur_internal_uninit`.
+    struct __zngur_internal_uninit_tag {};
+
+    // An uninitialized value of `T`, which Rust must write before `__zngur_internal_assume_init`
+    // marks it as initialized. The default constructor of a type is deleted unless it has
+    // `Default`, so this is the only way to create a value without initializing it.
+    template<typename T>
+    inline T __zngur_internal_uninit() noexcept {
+        if constexpr (::std::is_constructible<T, __zngur_internal_uninit_tag>::value) {
+            return T(__zngur_internal_uninit_tag{});
+        } else {
+            return T();
+        }
+    }
+
+    // Appends formatted text from Rust to the `std::string` at `s`.
+    inline void __zngur_internal_append_to_string(uint8_t* s, const uint8_t* data, size_t len) {
+        reinterpret_cast<::std::string*>(s)->append(reinterpret_cast<const char*>(data), len);
+    }
+
     template<typename T>
     inline void __zngur_internal_move_to_rust(uint8_t* dst, T& t) noexcept {
         memcpy(dst, ::rust::__zngur_internal_data_ptr(t), ::rust::__zngur_internal_size_of<T>());
@@ -1705,7 +2594,7 @@ namespace rust {
 
     template<typename T>
     inline T __zngur_internal_move_from_rust(uint8_t* src) noexcept {
-        T t;
+        T t = ::rust::__zngur_internal_uninit<T>();
         ::rust::__zngur_internal_assume_init(t);
         memcpy(::rust::__zngur_internal_data_ptr(t), src, ::rust::__zngur_internal_size_of<T>());
         return t;
@@ -1754,8 +2643,125 @@ namespace rust {
     template<typename... T>
     struct Tuple;
 
+    template<typename T, size_t N>
+    struct Array;
+
+    // The `i`th element of an array stored at `data`. Like the field types, this relies on
+    // references to sized types being a single pointer.
+    template<typename R, typename T>
+    inline R __zngur_internal_array_element(uint8_t* data, size_t i) noexcept {
+        size_t address = reinterpret_cast<size_t>(data) + i * ::rust::__zngur_internal_size_of<T>();
+        R r;
+        memcpy(reinterpret_cast<uint8_t*>(&r), &address, sizeof(size_t));
+        return r;
+    }
+
     using Unit = Tuple<>;
 
+#ifdef __SIZEOF_INT128__
+    using I128 = __int128;
+    using U128 = unsigned __int128;
+
+    inline void __zngur_print_int128(bool negative, U128 value) {
+        char buffer[41];
+        char* p = buffer + sizeof(buffer);
+        *--p = '\0';
+        do {
+            *--p = static_cast<char>('0' + value % 10);
+            value /= 10;
+        } while (value != 0);
+        if (negative) {
+            *--p = '-';
+        }
+        ::std::cerr << p << ::std::endl;
+    }
+#else
+    // Stand-ins with the layout of Rust's `i128` and `u128` for compilers without `__int128`.
+    struct alignas(16) I128 { uint8_t bytes[16]; };
+    struct alignas(16) U128 { uint8_t bytes[16]; };
+#endif
+
+    static_assert(sizeof(I128) == 16 && alignof(I128) == 16, "`i128` layout doesn't match Rust");
+    static_assert(sizeof(U128) == 16 && alignof(U128) == 16, "`u128` layout doesn't match Rust");
+
+"#;
+        write!(
+            state,
+            r#"
+    template<typename R>
+    struct __zngur_fn_ptr_output {{
+        using type = R;
+    }};
+
+    template<>
+    struct __zngur_fn_ptr_output<Unit> {{
+        using type = void;
+    }};
+
+    // A `fn` pointer that is passed between C++ and Rust. It is called through `call`, which moves
+    // the arguments into the function and the result out of it. That is `invoke` for a C++ function,
+    // and a shim on the Rust side for a Rust function that was returned to C++.
+    template<typename R, typename... Args>
+    struct FnPtr {{
+        using Output = typename __zngur_fn_ptr_output<R>::type;
+
+        FnPtr(Output (*f)(Args...)) noexcept
+            : call(&FnPtr::invoke), f(reinterpret_cast<void (*)()>(f)) {{}}
+
+        FnPtr(__zngur_internal_uninit_tag) noexcept : call(nullptr), f(nullptr) {{}}
+
+        Output operator()(Args... args) const noexcept {{
+            uint8_t* i[sizeof...(Args) + 1] = {{ ::rust::__zngur_internal_data_ptr(args)..., nullptr }};
+            (::rust::__zngur_internal_assume_deinit(args), ...);
+            if constexpr (::std::is_void<Output>::value) {{
+                uint8_t o;
+                call(f, i, &o);
+                {panic_handler}
+            }} else {{
+                R o = ::rust::__zngur_internal_uninit<R>();
+                call(f, i, ::rust::__zngur_internal_data_ptr(o));
+                {panic_handler}
+                ::rust::__zngur_internal_assume_init(o);
+                return o;
+            }}
+        }}
+
+        void (*call)(void (*)(), uint8_t**, uint8_t*);
+        void (*f)();
+
+    private:
+        static void invoke(void (*f)(), uint8_t** i, uint8_t* o) {{
+            invoke_with(reinterpret_cast<Output (*)(Args...)>(f), i, o, ::std::index_sequence_for<Args...>{{}});
+        }}
+
+        template<size_t... I>
+        static void invoke_with(Output (*f)(Args...), uint8_t** i, uint8_t* o, ::std::index_sequence<I...>) {{
+            if constexpr (::std::is_void<Output>::value) {{
+                (void)i;
+                (void)o;
+                f(::rust::__zngur_internal_move_from_rust<Args>(i[I])...);
+            }} else {{
+                Output r = f(::rust::__zngur_internal_move_from_rust<Args>(i[I])...);
+                ::rust::__zngur_internal_move_to_rust(o, r);
+            }}
+        }}
+    }};
+
+    template<typename R, typename... Args>
+    inline uint8_t* __zngur_internal_data_ptr(const FnPtr<R, Args...>& t) noexcept {{
+        return const_cast<uint8_t*>(reinterpret_cast<const uint8_t*>(&t));
+    }}
+
+    template<typename R, typename... Args>
+    inline void __zngur_internal_assume_init(FnPtr<R, Args...>&) noexcept {{}}
+
+    template<typename R, typename... Args>
+    inline void __zngur_internal_assume_deinit(FnPtr<R, Args...>&) noexcept {{}}
+"#,
+            panic_handler = state.panic_handler(),
+        )?;
+        state.text += r#"
+
     template<typename T>
     struct ZngurPrettyPrinter;
 
@@ -1771,25 +2777,100 @@ namespace rust {
         return ::std::forward<T>(input);
     }
 "#;
-        for ty in [8, 16, 32, 64]
+        let panic_handler = state.panic_handler();
+        writeln!(
+            state,
+            r#"
+    // Iterator over the items of a Rust iterator, returned by `begin()` of types with
+    // `IntoIterator`. It is an input iterator, so copies share the position, and dereferencing it
+    // moves the item out.
+    template<typename T>
+    class ZngurIterator {{
+        struct State {{
+            uint8_t* iter;
+            void (*next)(uint8_t*, uint8_t*, uint8_t*);
+            void (*drop)(uint8_t*);
+            T item;
+
+            ~State() {{
+                drop(iter);
+            }}
+        }};
+        ::std::shared_ptr<State> state;
+
+        void advance() {{
+            state->item = ::rust::__zngur_internal_uninit<T>();
+            bool found = false;
+            state->next(state->iter, ::rust::__zngur_internal_data_ptr(state->item), reinterpret_cast<uint8_t*>(&found));
+            {panic_handler}
+            if (found) {{
+                ::rust::__zngur_internal_assume_init(state->item);
+            }} else {{
+                state = nullptr;
+            }}
+        }}
+
+    public:
+        using iterator_category = ::std::input_iterator_tag;
+        using value_type = T;
+        using difference_type = ::std::ptrdiff_t;
+        using pointer = T*;
+        using reference = T&&;
+
+        // The end of every iterator.
+        ZngurIterator() noexcept {{}}
+
+        ZngurIterator(uint8_t* iter, void (*next)(uint8_t*, uint8_t*, uint8_t*), void (*drop)(uint8_t*)) noexcept {{
+            state.reset(new State{{iter, next, drop, ::rust::__zngur_internal_uninit<T>()}});
+            advance();
+        }}
+
+        T&& operator*() const noexcept {{
+            return ::std::move(state->item);
+        }}
+
+        ZngurIterator& operator++() noexcept {{
+            advance();
+            return *this;
+        }}
+
+        void operator++(int) noexcept {{
+            advance();
+        }}
+
+        bool operator==(const ZngurIterator& other) const noexcept {{
+            return state == other.state;
+        }}
+
+        bool operator!=(const ZngurIterator& other) const noexcept {{
+            return state != other.state;
+        }}
+    }};
+"#
+        )?;
+        let ints = [8, 16, 32, 64]
             .into_iter()
             .flat_map(|x| [format!("int{x}_t"), format!("uint{x}_t")])
-            .chain([8, 16, 32, 64].into_iter().flat_map(|x| {
-                [
-                    format!("::rust::Ref<int{x}_t>"),
-                    format!("::rust::Ref<uint{x}_t>"),
-                    format!("::rust::RefMut<int{x}_t>"),
-                    format!("::rust::RefMut<uint{x}_t>"),
-                ]
-            }))
+            .chain(["::rust::I128".to_string(), "::rust::U128".to_string()])
+            .collect_vec();
+        for ty in ints
+            .iter()
+            .cloned()
+            .chain(
+                ints.iter()
+                    .flat_map(|x| [format!("::rust::Ref<{x}>"), format!("::rust::RefMut<{x}>")]),
+            )
             .chain([
                 "::rust::ZngurCppOpaqueOwnedObject".to_string(),
                 "::double_t".to_string(),
                 "::float_t".to_string(),
                 "::size_t".to_string(),
+                "::ptrdiff_t".to_string(),
             ])
         {
-            if ty == "::size_t" {
+            // On Apple platforms `size_t` and `ptrdiff_t` are distinct from the fixed width
+            // integers, elsewhere they are the same types.
+            if ty == "::size_t" || ty == "::ptrdiff_t" {
                 writeln!(state, "#ifdef __APPLE__")?;
             }
             writeln!(
@@ -1870,6 +2951,7 @@ namespace rust {
             if ty.starts_with("int")
                 || ty.starts_with("uint")
                 || ty.starts_with("::size_t")
+                || ty.starts_with("::ptrdiff_t")
                 || ty.starts_with("::double")
                 || ty.starts_with("::float")
             {
@@ -1885,7 +2967,27 @@ namespace rust {
                     "#
                 )?;
             }
-            if ty == "::size_t" {
+            if ty == "::rust::I128" || ty == "::rust::U128" {
+                let negative = if ty == "::rust::I128" {
+                    "t < 0"
+                } else {
+                    "false"
+                };
+                writeln!(
+                    state,
+                    r#"
+#ifdef __SIZEOF_INT128__
+    template<>
+    struct ZngurPrettyPrinter<{ty}> {{
+        static inline void print({ty} const& t) {{
+            __zngur_print_int128({negative}, {negative} ? -static_cast<U128>(t) : static_cast<U128>(t));
+        }}
+    }};
+#endif
+                    "#
+                )?;
+            }
+            if ty == "::size_t" || ty == "::ptrdiff_t" {
                 writeln!(state, "#endif")?;
             }
         }
@@ -1897,7 +2999,7 @@ namespace rust {
         for td in &self.type_defs {
             td.emit_rust_links(state)?;
         }
-        for (_, td) in &self.trait_defs {
+        for td in self.trait_defs.values() {
             td.emit_rust_links(state)?;
         }
         writeln!(state, "}}")?;
@@ -1974,7 +3076,7 @@ namespace rust {
                 tr.emit_header(state)?;
             }
         }
-        for (_, td) in &self.trait_defs {
+        for td in self.trait_defs.values() {
             td.emit(state)?;
         }
         for td in &self.type_defs {
@@ -2100,6 +3202,121 @@ namespace rust {
     }
 }
 
+/// Declares the Rust function `fmt`, which formats a value into a `std::string`. See
+/// `__zngur_internal_append_to_string`.
+fn emit_fmt_link(state: &mut State, fmt: &str) -> std::fmt::Result {
+    writeln!(
+        state,
+        "void {fmt}(uint8_t *v, uint8_t *buf, void (*append)(uint8_t *, const uint8_t *, size_t));"
+    )
+}
+
+/// The kind of the reference that `operator[]` of `operator` returns, and its index and output
+/// types in C++.
+fn index_operator_types(operator: &ZngurOperator) -> (&'static str, CppType, CppType) {
+    let output_kind = match operator.op {
+        ZngurOperatorTrait::IndexMut => "RefMut",
+        _ => "Ref",
+    };
+    (
+        output_kind,
+        operator.rhs.as_ref().unwrap().into_cpp(),
+        operator.output.as_ref().unwrap().into_cpp(),
+    )
+}
+
+/// The `Item` of an `IntoIterator`, and the names of its `into_iter`, `next` and `drop` functions.
+type IntoIteratorFns<'a> = (&'a RustType, &'a str, &'a str, &'a str);
+
+/// Emits `begin()` and `end()` of `this`, which is an owned type or a reference, with the
+/// `qualifiers` of the member functions. An owned value is moved into its iterator, like in a Rust
+/// `for` loop.
+fn emit_into_iterator(
+    state: &mut State,
+    this: &str,
+    qualifiers: &str,
+    is_owned: bool,
+    (item, into_iter, next, drop): IntoIteratorFns<'_>,
+) -> std::fmt::Result {
+    let item = item.into_cpp();
+    let deinit = if is_owned {
+        "::rust::__zngur_internal_assume_deinit(*this);"
+    } else {
+        ""
+    };
+    let panic_handler = state.panic_handler();
+    writeln!(
+        state,
+        r#"
+inline ::rust::ZngurIterator< {item} > {this}::begin() {qualifiers} noexcept {{
+    uint8_t* iter;
+    {deinit}
+    {into_iter}(::rust::__zngur_internal_data_ptr(*this), reinterpret_cast<uint8_t*>(&iter));
+    {panic_handler}
+    return ::rust::ZngurIterator< {item} >(iter, {next}, {drop});
+}}
+
+inline ::rust::ZngurIterator< {item} > {this}::end() {qualifiers} noexcept {{
+    return ::rust::ZngurIterator< {item} >();
+}}"#
+    )
+}
+
+/// Emits `doc` as a Doxygen comment of the declaration that follows it.
+fn emit_doc(state: &mut State, doc: Option<&str>) -> std::fmt::Result {
+    for line in doc.into_iter().flat_map(|x| x.lines()) {
+        match line {
+            "" => writeln!(state, "///")?,
+            line => writeln!(state, "/// {line}")?,
+        }
+    }
+    Ok(())
+}
+
+/// A preprocessor condition equivalent to `predicate`, if all of its options have one.
+fn cfg_to_cpp(predicate: &CfgPredicate) -> Option<String> {
+    let join = |v: &[CfgPredicate], op: &str, empty: &str| -> Option<String> {
+        if v.is_empty() {
+            return Some(empty.to_owned());
+        }
+        let parts = v.iter().map(cfg_to_cpp).collect::<Option<Vec<_>>>()?;
+        Some(format!("({})", parts.join(op)))
+    };
+    let unix = "(defined(__unix__) || defined(__APPLE__))";
+    let windows = "defined(_WIN32)";
+    match predicate {
+        CfgPredicate::Any(v) => join(v, " || ", "0"),
+        CfgPredicate::All(v) => join(v, " && ", "1"),
+        CfgPredicate::Not(p) => Some(format!("!{}", cfg_to_cpp(p)?)),
+        CfgPredicate::Option { name, value } => {
+            let condition = match (name.as_str(), value.as_deref()) {
+                ("unix", None) | ("target_family", Some("unix")) => unix,
+                ("windows", None) | ("target_family", Some("windows")) => windows,
+                ("target_pointer_width", Some("16")) => "(UINTPTR_MAX == 0xFFFF)",
+                ("target_pointer_width", Some("32")) => "(UINTPTR_MAX == 0xFFFFFFFF)",
+                ("target_pointer_width", Some("64")) => "(UINTPTR_MAX == 0xFFFFFFFFFFFFFFFF)",
+                ("target_endian", Some("little")) => {
+                    "(defined(__BYTE_ORDER__) && __BYTE_ORDER__ == __ORDER_LITTLE_ENDIAN__)"
+                }
+                ("target_endian", Some("big")) => {
+                    "(defined(__BYTE_ORDER__) && __BYTE_ORDER__ == __ORDER_BIG_ENDIAN__)"
+                }
+                ("target_os", Some("linux")) => "(defined(__linux__) && !defined(__ANDROID__))",
+                ("target_os", Some("android")) => "defined(__ANDROID__)",
+                ("target_os", Some("windows")) => windows,
+                ("target_os", Some("freebsd")) => "defined(__FreeBSD__)",
+                ("target_arch", Some("x86_64")) => "(defined(__x86_64__) || defined(_M_X64))",
+                ("target_arch", Some("x86")) => "(defined(__i386__) || defined(_M_IX86))",
+                ("target_arch", Some("aarch64")) => "(defined(__aarch64__) || defined(_M_ARM64))",
+                ("target_arch", Some("arm")) => "(defined(__arm__) || defined(_M_ARM))",
+                ("target_arch", Some("wasm32")) => "defined(__wasm32__)",
+                _ => return None,
+            };
+            Some(condition.to_owned())
+        }
+    }
+}
+
 pub fn cpp_handle_keyword(name: &str) -> &str {
     match name {
         "new" => "new_",
@@ -14,23 +14,131 @@ use cpp::cpp_handle_keyword;
 use itertools::Itertools;
 use rust::IntoCpp;
 
+mod check;
 pub mod cpp;
 mod rust;
+#[cfg(test)]
+mod tests;
 
 pub use rust::RustFile;
-pub use zngur_parser::ParsedZngFile;
+use zngur_parser::LabelKind;
+pub use zngur_parser::{
+    AriadneRenderer, Cfg, Diagnostic, ParsedZngFile, ZngParser, ZngurFileBuilder, ZngurFileExt,
+    ZngurTypeBuilder,
+};
 
 pub use zngur_def::*;
 
 pub struct ZngurGenerator(ZngurFile);
 
+/// How `ty` is spelled in the generated C++ code, or `None` if the generator doesn't support it.
+pub fn cpp_type_name(ty: &RustType) -> Option<String> {
+    check::is_supported(ty).then(|| ty.into_cpp().to_string())
+}
+
+/// What checking a Rust file rendered by [`ZngurGenerator::render_layout_probe`] reported.
+#[derive(Debug, PartialEq, Eq)]
+pub struct LayoutProbeOutput {
+    /// The `(size, align)` of the types with `#layout(auto)` in the order of the zng file, or `None`
+    /// for the types that are not reported.
+    pub layouts: Vec<Option<(usize, usize)>>,
+    /// The rendered errors that don't report a layout.
+    pub errors: Vec<String>,
+}
+
+/// Reads the layouts of the `count` types with `#layout(auto)` from the JSON messages of cargo or
+/// rustc with `--message-format=json`, one per line, for a Rust file rendered by
+/// [`ZngurGenerator::render_layout_probe`].
+pub fn read_layout_probe(messages: &str, count: usize) -> LayoutProbeOutput {
+    let mut output = LayoutProbeOutput {
+        layouts: vec![None; count],
+        errors: vec![],
+    };
+    for line in messages.lines() {
+        let Ok(message) = serde_json::from_str::<serde_json::Value>(line) else {
+            continue;
+        };
+        // Cargo wraps the diagnostics of rustc in its own messages.
+        let diagnostic = match &message["message"] {
+            x if x.is_object() => x,
+            _ => &message,
+        };
+        let notes = diagnostic["children"].as_array().into_iter().flatten();
+        let probe = notes
+            .filter_map(|x| x["message"].as_str())
+            .find_map(read_layout_probe_note);
+        match probe {
+            Some((index, layout)) => {
+                if let Some(x) = index.checked_sub(1).and_then(|x| output.layouts.get_mut(x)) {
+                    *x = Some(layout);
+                }
+            }
+            None if diagnostic["level"] == "error" => {
+                if let Some(rendered) = diagnostic["rendered"].as_str() {
+                    output.errors.push(rendered.to_owned());
+                }
+            }
+            None => (),
+        }
+    }
+    output
+}
+
+/// The index and the `(size, align)` in a note like
+/// ``found struct `ZngurLayoutProbe<1, 24, 8>` `` of the type mismatch that the probe causes.
+fn read_layout_probe_note(note: &str) -> Option<(usize, (usize, usize))> {
+    let (_, found) = note.split_once("found struct `")?;
+    let (_, args) = found.split_once("ZngurLayoutProbe<")?;
+    let (args, _) = args.split_once('>')?;
+    let args = args
+        .split(',')
+        .map(|x| x.trim().parse().ok())
+        .collect::<Option<Vec<usize>>>()?;
+    match args[..] {
+        [index, size, align] => Some((index, (size, align))),
+        _ => None,
+    }
+}
+
 impl ZngurGenerator {
+    /// Creates a generator for `zng`, which is either parsed from a zng file or built by other means,
+    /// like [`ZngurFile::from_json`] with the `serde` feature.
     pub fn build_from_zng(zng: ZngurFile) -> Self {
         ZngurGenerator(zng)
     }
 
-    pub fn render(self) -> (String, String, Option<String>) {
+    /// Renders the Rust, C++ header and C++ source files. Fails with diagnostics pointing to the zng
+    /// file if it contains constructs that are not supported.
+    pub fn render(self) -> Result<(String, String, Option<String>), Vec<Diagnostic>> {
+        self.render_with(false)
+    }
+
+    /// Renders the files like [`ZngurGenerator::render`], except that compiling the Rust file fails
+    /// with errors that report the layout of every type with `#layout(auto)`, which can be read by
+    /// [`read_layout_probe`] from the JSON messages of the compiler. The C++ files use a placeholder layout for these types.
+    pub fn render_layout_probe(self) -> Result<(String, String, Option<String>), Vec<Diagnostic>> {
+        self.render_with(true)
+    }
+
+    fn render_with(
+        self,
+        layout_probe: bool,
+    ) -> Result<(String, String, Option<String>), Vec<Diagnostic>> {
         let mut zng = self.0;
+        let mut diagnostics = check::check_supported(&zng);
+        if !layout_probe {
+            for ty_def in zng.types.iter().filter(|x| x.layout == LayoutPolicy::Auto) {
+                let message = format!("Layout of `{}` is not computed", ty_def.ty);
+                diagnostics.push(Diagnostic::error(message).with_zngur_label(
+                    LabelKind::Primary,
+                    ty_def.span.as_ref(),
+                    "`#layout(auto)` is only supported by the `zngur` crate and CLI",
+                ));
+            }
+        }
+        if !diagnostics.is_empty() {
+            return Err(diagnostics);
+        }
 
         // Unit type is a bit special, and almost everyone needs it, so we add it ourself.
         zng.types.push(ZngurType {
@@ -42,9 +150,14 @@ impl ZngurGenerator {
             fields: vec![],
             cpp_value: None,
             cpp_ref: None,
+            doc: None,
+            span: None,
         });
-        let mut cpp_file = CppFile::default();
-        cpp_file.additional_includes = zng.additional_includes;
+        let mut cpp_file = CppFile {
+            additional_includes: zng.additional_includes,
+            cfgs: zng.cfgs,
+            ..Default::default()
+        };
         let mut rust_file = RustFile::default();
         cpp_file.trait_defs = zng
             .traits
@@ -55,18 +168,36 @@ impl ZngurGenerator {
             rust_file.enable_panic_to_exception();
             cpp_file.panic_to_exception = true;
         }
-        for ty_def in zng.types {
+        let mut probe_index = 0;
+        for mut ty_def in zng.types {
             let is_copy = ty_def.wellknown_traits.contains(&ZngurWellknownTrait::Copy);
             match ty_def.layout {
                 LayoutPolicy::StackAllocated { size, align } => {
-                    rust_file.add_static_size_assert(&ty_def.ty, size);
-                    rust_file.add_static_align_assert(&ty_def.ty, align);
+                    rust_file.add_static_layout_assert(
+                        &ty_def.ty,
+                        size,
+                        align,
+                        ty_def.span.as_ref(),
+                    );
+                }
+                LayoutPolicy::Conservative { size, align } => {
+                    rust_file.add_static_layout_bound_assert(
+                        &ty_def.ty,
+                        size,
+                        align,
+                        ty_def.span.as_ref(),
+                    );
+                }
+                LayoutPolicy::Auto => {
+                    probe_index += 1;
+                    rust_file.add_layout_probe(&ty_def.ty, probe_index);
+                    ty_def.layout = LayoutPolicy::StackAllocated { size: 1, align: 1 };
                 }
                 LayoutPolicy::HeapAllocated => (),
                 LayoutPolicy::OnlyByRef => (),
             }
             if is_copy {
-                rust_file.add_static_is_copy_assert(&ty_def.ty);
+                rust_file.add_static_is_copy_assert(&ty_def.ty, ty_def.span.as_ref());
             }
             let mut cpp_methods = vec![];
             let mut constructors = vec![];
@@ -87,6 +218,7 @@ impl ZngurGenerator {
                                 inputs: constructor.inputs.iter().map(|x| x.1.into_cpp()).collect(),
                                 output: ty_def.ty.into_cpp(),
                             },
+                            doc: constructor.doc,
                         });
                         cpp_methods.push(CppMethod {
                             name: format!("matches_{}", name),
@@ -96,17 +228,21 @@ impl ZngurGenerator {
                                 inputs: vec![ty_def.ty.into_cpp().into_ref()],
                                 output: CppType::from("uint8_t"),
                             },
+                            doc: None,
                         });
                     }
                     None => {
                         let rust_link_name = rust_file
                             .add_constructor(&format!("{}", ty_def.ty), &constructor.inputs)
                             .constructor;
-                        constructors.push(CppFnSig {
-                            rust_link_name,
-                            inputs: constructor.inputs.iter().map(|x| x.1.into_cpp()).collect(),
-                            output: ty_def.ty.into_cpp(),
-                        });
+                        constructors.push((
+                            CppFnSig {
+                                rust_link_name,
+                                inputs: constructor.inputs.iter().map(|x| x.1.into_cpp()).collect(),
+                                output: ty_def.ty.into_cpp(),
+                            },
+                            constructor.doc,
+                        ));
                     }
                 }
             }
@@ -116,12 +252,15 @@ impl ZngurGenerator {
             }
             if let RustType::Tuple(fields) = &ty_def.ty {
                 if !fields.is_empty() {
-                    let rust_link_name = rust_file.add_tuple_constructor(&fields);
-                    constructors.push(CppFnSig {
-                        rust_link_name,
-                        inputs: fields.iter().map(|x| x.into_cpp()).collect(),
-                        output: ty_def.ty.into_cpp(),
-                    });
+                    let rust_link_name = rust_file.add_tuple_constructor(fields);
+                    constructors.push((
+                        CppFnSig {
+                            rust_link_name,
+                            inputs: fields.iter().map(|x| x.into_cpp()).collect(),
+                            output: ty_def.ty.into_cpp(),
+                        },
+                        None,
+                    ));
                 }
             }
             let is_unsized = ty_def
@@ -158,6 +297,7 @@ impl ZngurGenerator {
                         inputs,
                         output: method.output.into_cpp(),
                     },
+                    doc: method.doc,
                 });
             }
             cpp_file.type_defs.push(CppTypeDefinition {
@@ -172,6 +312,7 @@ impl ZngurGenerator {
                     (rust_link_name, cpp_type)
                 }),
                 cpp_ref: ty_def.cpp_ref,
+                doc: ty_def.doc,
                 from_trait: if let RustType::Boxed(b) = &ty_def.ty {
                     if let RustType::Dyn(tr, _) = b.as_ref() {
                         if let RustTrait::Fn {
@@ -221,6 +362,7 @@ impl ZngurGenerator {
                     inputs: func.inputs.into_iter().map(|x| x.into_cpp()).collect(),
                     output: func.output.into_cpp(),
                 },
+                doc: func.doc,
             });
         }
         for func in zng.extern_cpp_funcs {
@@ -263,7 +405,7 @@ impl ZngurGenerator {
             });
         }
         let (h, cpp) = cpp_file.render();
-        (rust_file.text, h, cpp)
+        Ok((rust_file.text, h, cpp))
     }
 }
 
@@ -9,6 +9,7 @@ use crate::{
 
 use zngur_def::*;
 
+#[allow(clippy::wrong_self_convention)]
 pub trait IntoCpp {
     fn into_cpp(&self) -> CppType;
 }
@@ -21,7 +22,7 @@ impl IntoCpp for RustPathAndGenerics {
             named_generics,
         } = self;
         let named_generics = named_generics.iter().sorted_by_key(|x| &x.0).map(|x| &x.1);
-        CppType {
+        CppType::Path {
             path: CppPath::from_rust_path(path),
             generic_args: generics
                 .iter()
@@ -40,7 +41,7 @@ impl IntoCpp for RustTrait {
                 name,
                 inputs,
                 output,
-            } => CppType {
+            } => CppType::Path {
                 path: CppPath::from(&*format!("rust::{name}")),
                 generic_args: inputs
                     .iter()
@@ -52,71 +53,94 @@ impl IntoCpp for RustTrait {
     }
 }
 
+/// The C++ type for Rust types that are mapped to a C++ builtin type, like integers and pointers
+/// to them.
+pub(crate) fn builtin_cpp_type(ty: &RustType) -> Option<CppType> {
+    match ty {
+        RustType::Primitive(s) => match s {
+            PrimitiveRustType::Uint(128) => Some(CppType::from("rust::U128")),
+            PrimitiveRustType::Int(128) => Some(CppType::from("rust::I128")),
+            PrimitiveRustType::Uint(s) => Some(CppType::from(&*format!("uint{s}_t"))),
+            PrimitiveRustType::Int(s) => Some(CppType::from(&*format!("int{s}_t"))),
+            PrimitiveRustType::Float(32) => Some(CppType::from("float_t")),
+            PrimitiveRustType::Float(64) => Some(CppType::from("double_t")),
+            PrimitiveRustType::Float(_) => None,
+            PrimitiveRustType::Usize => Some(CppType::from("size_t")),
+            PrimitiveRustType::Isize => Some(CppType::from("ptrdiff_t")),
+            PrimitiveRustType::Bool
+            | PrimitiveRustType::Char
+            | PrimitiveRustType::Str
+            | PrimitiveRustType::Never => None,
+            PrimitiveRustType::ZngurCppOpaqueOwnedObject => {
+                Some(CppType::from("rust::ZngurCppOpaqueOwnedObject"))
+            }
+        },
+        RustType::Raw(Mutability::Mut, t) => Some(CppType::from(&*format!(
+            "{}*",
+            builtin_cpp_type(t)?.to_string().strip_prefix("::")?
+        ))),
+        RustType::Raw(Mutability::Not, t) => Some(CppType::from(&*format!(
+            "{} const*",
+            builtin_cpp_type(t)?.to_string().strip_prefix("::")?
+        ))),
+        _ => None,
+    }
+}
+
 impl IntoCpp for RustType {
     fn into_cpp(&self) -> CppType {
-        fn for_builtin(this: &RustType) -> Option<CppType> {
-            match this {
-                RustType::Primitive(s) => match s {
-                    PrimitiveRustType::Uint(s) => Some(CppType::from(&*format!("uint{s}_t"))),
-                    PrimitiveRustType::Int(s) => Some(CppType::from(&*format!("int{s}_t"))),
-                    PrimitiveRustType::Float(32) => Some(CppType::from("float_t")),
-                    PrimitiveRustType::Float(64) => Some(CppType::from("double_t")),
-                    PrimitiveRustType::Float(_) => unreachable!(),
-                    PrimitiveRustType::Usize => Some(CppType::from("size_t")),
-                    PrimitiveRustType::Bool | PrimitiveRustType::Str => None,
-                    PrimitiveRustType::ZngurCppOpaqueOwnedObject => {
-                        Some(CppType::from("rust::ZngurCppOpaqueOwnedObject"))
-                    }
-                },
-                RustType::Raw(Mutability::Mut, t) => Some(CppType::from(&*format!(
-                    "{}*",
-                    for_builtin(t)?.to_string().strip_prefix("::")?
-                ))),
-                RustType::Raw(Mutability::Not, t) => Some(CppType::from(&*format!(
-                    "{} const*",
-                    for_builtin(t)?.to_string().strip_prefix("::")?
-                ))),
-                _ => None,
-            }
-        }
-        if let Some(builtin) = for_builtin(self) {
+        if let Some(builtin) = builtin_cpp_type(self) {
             return builtin;
         }
         match self {
             RustType::Primitive(s) => match s {
                 PrimitiveRustType::Bool => CppType::from("rust::Bool"),
+                PrimitiveRustType::Char => CppType::from("rust::Char"),
                 PrimitiveRustType::Str => CppType::from("rust::Str"),
-                _ => unreachable!(),
+                PrimitiveRustType::Never => CppType::Never,
+                _ => unreachable!("rejected by `check_supported`"),
             },
-            RustType::Boxed(t) => CppType {
+            RustType::Boxed(t) => CppType::Path {
                 path: CppPath::from("rust::Box"),
                 generic_args: vec![t.into_cpp()],
             },
-            RustType::Ref(m, t) => CppType {
+            RustType::Ref(m, t) => CppType::Path {
                 path: match m {
                     Mutability::Mut => CppPath::from("rust::RefMut"),
                     Mutability::Not => CppPath::from("rust::Ref"),
                 },
                 generic_args: vec![t.into_cpp()],
             },
-            RustType::Slice(s) => CppType {
+            RustType::Slice(s) => CppType::Path {
                 path: CppPath::from("rust::Slice"),
                 generic_args: vec![s.into_cpp()],
             },
-            RustType::Raw(_, _) => todo!(),
+            RustType::Array(s, n) => CppType::Path {
+                path: CppPath::from("rust::Array"),
+                generic_args: vec![s.into_cpp(), CppType::Value(*n)],
+            },
+            RustType::FnPtr { inputs, output } => CppType::Path {
+                path: CppPath::from("rust::FnPtr"),
+                generic_args: Some(&**output)
+                    .into_iter()
+                    .chain(inputs)
+                    .map(|x| x.into_cpp())
+                    .collect(),
+            },
+            RustType::Raw(_, _) => unreachable!("rejected by `check_supported`"),
             RustType::Adt(pg) => pg.into_cpp(),
             RustType::Tuple(v) => {
                 if v.is_empty() {
                     return CppType::from("rust::Unit");
                 }
-                CppType {
+                CppType::Path {
                     path: CppPath::from("rust::Tuple"),
-                    generic_args: v.into_iter().map(|x| x.into_cpp()).collect(),
+                    generic_args: v.iter().map(|x| x.into_cpp()).collect(),
                 }
             }
             RustType::Dyn(tr, marker_bounds) => {
                 let tr_as_cpp_type = tr.into_cpp();
-                CppType {
+                CppType::Path {
                     path: CppPath::from("rust::Dyn"),
                     generic_args: [tr_as_cpp_type]
                         .into_iter()
@@ -135,8 +159,13 @@ impl IntoCpp for RustType {
 pub struct RustFile {
     pub text: String,
     pub panic_to_exception: bool,
+    /// Function pointer types that already have a `from_cpp` module.
+    fn_ptr_converters: Vec<RustType>,
 }
 
+/// How many distinct C++ functions can be converted to each Rust `fn` pointer type.
+const FN_PTR_TRAMPOLINES: usize = 64;
+
 impl Default for RustFile {
     fn default() -> Self {
         Self {
@@ -169,15 +198,53 @@ mod zngur_types {
             (self.destructor)(self.data)
         }
     }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub struct ZngurCppFnPtr {
+        pub call: extern "C" fn(*const u8, *mut *mut u8, *mut u8),
+        pub f: *const u8,
+    }
+
+    unsafe impl Send for ZngurCppFnPtr {}
+    unsafe impl Sync for ZngurCppFnPtr {}
+
+    // Layout assertions compare against this type, so that their compile errors show the actual
+    // size and align as its arguments.
+    pub struct ZngurLayout<const SIZE: usize, const ALIGN: usize>;
+
+    // `bound` if `actual` fits in it, otherwise `actual`, so that the errors of upper bound
+    // assertions show the actual value.
+    pub const fn layout_bound(actual: usize, bound: usize) -> usize {
+        if actual <= bound { bound } else { actual }
+    }
+
+    pub struct ZngurLayoutProbe<const INDEX: usize, const SIZE: usize, const ALIGN: usize>;
+
+    // Formats into a `std::string` of the C++ side, by calling `append` with each piece.
+    pub struct ZngurCppWriter {
+        pub buf: *mut u8,
+        pub append: extern "C" fn(*mut u8, *const u8, usize),
+    }
+
+    impl ::std::fmt::Write for ZngurCppWriter {
+        fn write_str(&mut self, s: &str) -> ::std::fmt::Result {
+            (self.append)(self.buf, s.as_ptr(), s.len());
+            Ok(())
+        }
+    }
 }
 
 #[allow(unused_imports)]
 pub use zngur_types::ZngurCppOpaqueOwnedObject;
 #[allow(unused_imports)]
 pub use zngur_types::ZngurCppOpaqueBorrowedObject;
+#[allow(unused_imports)]
+pub use zngur_types::ZngurCppFnPtr;
 "#
             .to_owned(),
             panic_to_exception: false,
+            fn_ptr_converters: vec![],
         }
     }
 }
@@ -200,6 +267,12 @@ macro_rules! wln {
     };
 }
 
+/// A trailing comment for generated assertions, so that compiler errors in them show the zng item
+/// they come from.
+fn zng_location(span: Option<&ZngurSpan>) -> String {
+    span.map(|x| format!(" // {x}")).unwrap_or_default()
+}
+
 fn mangle_name(name: &str) -> String {
     let mut name = "__zngur_"
         .chars()
@@ -221,6 +294,7 @@ fn mangle_name(name: &str) -> String {
         (2, "+", 'l'),
         (2, "(", 'p'),
         (2, ")", 'q'),
+        (2, ";", 'f'),
     ];
     while let Some((pos, which)) = bads.iter().filter_map(|x| Some((name.find(x.1)?, x))).min() {
         name.replace_range(pos..pos + which.1.len(), "_");
@@ -248,27 +322,186 @@ impl RustFile {
         wln!(self, "r.assume_init()");
     }
 
-    pub fn add_static_is_copy_assert(&mut self, ty: &RustType) {
+    /// An expression that moves the argument of type `ty` out of the pointer `ptr` that C++ passed.
+    fn read_input(&mut self, ptr: &str, ty: &RustType) -> String {
+        match ty {
+            RustType::FnPtr { .. } => {
+                let converter = self.add_fn_ptr_converter(ty);
+                format!("{converter}::from_cpp(::std::ptr::read({ptr} as *mut ZngurCppFnPtr))")
+            }
+            _ => format!("::std::ptr::read({ptr} as *mut {ty})"),
+        }
+    }
+
+    /// An expression that moves `value` of type `ty` into the pointer `o` that C++ passed.
+    fn write_output(&mut self, ty: &RustType, value: &str) -> String {
+        match ty {
+            RustType::FnPtr { .. } => {
+                let converter = self.add_fn_ptr_converter(ty);
+                format!("::std::ptr::write(o as *mut ZngurCppFnPtr, {converter}::to_cpp({value}))")
+            }
+            _ => format!("::std::ptr::write(o as *mut {ty}, {value})"),
+        }
+    }
+
+    /// Emits a module that converts between C++ functions and the Rust `fn` pointer type `ty`. A
+    /// Rust function goes to C++ along with `call`, which calls it. A `fn` pointer can't carry the C++
+    /// function, so each distinct C++ function gets one of a fixed set of trampolines, which reads it
+    /// from a static slot. Slots are never freed, so a C++ function keeps its trampoline, and the
+    /// program aborts if more than `FN_PTR_TRAMPOLINES` of them are converted.
+    fn add_fn_ptr_converter(&mut self, ty: &RustType) -> String {
+        let module = mangle_name(&format!("{ty}=from_cpp"));
+        if self.fn_ptr_converters.contains(ty) {
+            return module;
+        }
+        self.fn_ptr_converters.push(ty.clone());
+        let RustType::FnPtr { inputs, output } = ty else {
+            unreachable!("only called for function pointers");
+        };
+        let n = FN_PTR_TRAMPOLINES;
+        let i = if inputs.is_empty() { "_" } else { "i" };
+        let panic_payload = if self.panic_to_exception {
+            "\n    use super::PANIC_PAYLOAD;"
+        } else {
+            ""
+        };
+        wln!(
+            self,
+            r#"
+#[allow(non_snake_case, dead_code)]
+mod {module} {{
+    use super::ZngurCppFnPtr;{panic_payload}
+
+    extern "C" fn call(f: *const u8, {i}: *mut *mut u8, o: *mut u8) {{ unsafe {{
+    let f = ::std::mem::transmute::<*const u8, {ty}>(f);"#
+        );
+        let args = inputs
+            .iter()
+            .enumerate()
+            .map(|(n, ty)| format!("::std::ptr::read(*i.add({n}) as *mut {ty})"))
+            .join(", ");
+        self.wrap_in_catch_unwind(|this| {
+            wln!(
+                this,
+                "    ::std::ptr::write(o as *mut {output}, f({args}));"
+            );
+        });
+        wln!(
+            self,
+            r#" }} }}
+
+    pub fn to_cpp(f: {ty}) -> ZngurCppFnPtr {{
+        ZngurCppFnPtr {{ call, f: f as *const u8 }}
+    }}
+
+    static FUNCS: [::std::sync::OnceLock<ZngurCppFnPtr>; {n}] =
+        [const {{ ::std::sync::OnceLock::new() }}; {n}];
+
+    fn trampoline<const N: usize>({}) -> {output} {{ unsafe {{
+        let f = FUNCS[N].get().unwrap();"#,
+            inputs
+                .iter()
+                .enumerate()
+                .map(|(n, ty)| format!("i{n}: {ty}"))
+                .join(", "),
+        );
+        for n in 0..inputs.len() {
+            wln!(self, "let mut i{n} = ::core::mem::MaybeUninit::new(i{n});");
+        }
+        wln!(
+            self,
+            "let mut i: [*mut u8; {}] = [{}];",
+            inputs.len(),
+            (0..inputs.len())
+                .map(|n| format!("i{n}.as_mut_ptr() as *mut u8"))
+                .join(", ")
+        );
+        wln!(
+            self,
+            r#"
+        let mut r = ::core::mem::MaybeUninit::uninit();
+        (f.call)(f.f, i.as_mut_ptr(), r.as_mut_ptr() as *mut u8);
+        r.assume_init()
+    }} }}
+
+    const TRAMPOLINES: [{ty}; {n}] = [{}];
+
+    pub fn from_cpp(f: ZngurCppFnPtr) -> {ty} {{
+        let rust_call: extern "C" fn(*const u8, *mut *mut u8, *mut u8) = call;
+        if ::std::ptr::fn_addr_eq(f.call, rust_call) {{
+            return unsafe {{ ::std::mem::transmute::<*const u8, {ty}>(f.f) }};
+        }}
+        for (slot, trampoline) in FUNCS.iter().zip(TRAMPOLINES) {{
+            if slot.get_or_init(|| f).f == f.f {{
+                return trampoline;
+            }}
+        }}
+        ::std::eprintln!(
+            "zngur: at most {n} distinct C++ functions can be converted to `{ty}`, \
+since each one needs a trampoline of its own. Take a `Box<dyn Fn>` instead to accept any number of them."
+        );
+        ::std::process::abort();
+    }}
+}}"#,
+            (0..n).map(|x| format!("trampoline::<{x}>")).join(", "),
+        );
+        module
+    }
+
+    pub fn add_static_is_copy_assert(&mut self, ty: &RustType, span: Option<&ZngurSpan>) {
+        let location = zng_location(span);
         wln!(
             self,
             r#"const _: () = {{
                 const fn static_assert_is_copy<T: Copy>() {{}}
-                static_assert_is_copy::<{ty}>();
+                static_assert_is_copy::<{ty}>();{location}
             }};"#
         );
     }
 
-    pub fn add_static_size_assert(&mut self, ty: &RustType, size: usize) {
+    pub fn add_static_layout_assert(
+        &mut self,
+        ty: &RustType,
+        size: usize,
+        align: usize,
+        span: Option<&ZngurSpan>,
+    ) {
+        let location = zng_location(span);
+        wln!(
+            self,
+            r#"const _: zngur_types::ZngurLayout<{size}, {align}> = zngur_types::ZngurLayout::<
+    {{ ::std::mem::size_of::<{ty}>() }},
+    {{ ::std::mem::align_of::<{ty}>() }},
+>;{location}"#
+        );
+    }
+
+    /// Like [`add_static_layout_assert`](Self::add_static_layout_assert), but `size` and `align`
+    /// only need to be upper bounds of the layout of `ty`.
+    pub fn add_static_layout_bound_assert(
+        &mut self,
+        ty: &RustType,
+        size: usize,
+        align: usize,
+        span: Option<&ZngurSpan>,
+    ) {
+        let location = zng_location(span);
         wln!(
             self,
-            r#"const _: [(); {size}] = [(); ::std::mem::size_of::<{ty}>()];"#
+            r#"const _: zngur_types::ZngurLayout<{size}, {align}> = zngur_types::ZngurLayout::<
+    {{ zngur_types::layout_bound(::std::mem::size_of::<{ty}>(), {size}) }},
+    {{ zngur_types::layout_bound(::std::mem::align_of::<{ty}>(), {align}) }},
+>;{location}"#
         );
     }
 
-    pub fn add_static_align_assert(&mut self, ty: &RustType, align: usize) {
+    /// Adds a constant that fails to compile with an error that contains the layout of `ty`, and
+    /// `index`, which should not be zero. None of the expected arguments can be equal to the actual
+    /// ones, because rustc elides those in the error.
+    pub fn add_layout_probe(&mut self, ty: &RustType, index: usize) {
         wln!(
             self,
-            r#"const _: [(); {align}] = [(); ::std::mem::align_of::<{ty}>()];"#
+            r#"const _: zngur_types::ZngurLayoutProbe<0, {{ usize::MAX }}, 0> = zngur_types::ZngurLayoutProbe::<{index}, {{ ::std::mem::size_of::<{ty}>() }}, {{ ::std::mem::align_of::<{ty}>() }}>;"#
         );
     }
 
@@ -305,10 +538,12 @@ impl RustFile {
                     rust_link_name,
                     inputs: x.inputs.into_iter().map(|x| x.into_cpp()).collect(),
                     output: x.output.into_cpp(),
+                    doc: x.doc,
                 })
                 .collect(),
             link_name,
             link_name_ref,
+            doc: tr.doc.clone(),
         }
     }
 
@@ -343,7 +578,7 @@ pub extern "C" fn {mangled_name}(
             w!(self, "        fn {}(", method.name);
             match method.receiver {
                 crate::ZngurMethodReceiver::Static => {
-                    panic!("traits with static methods are not object safe");
+                    unreachable!("rejected by `check_supported`");
                 }
                 crate::ZngurMethodReceiver::Ref(Mutability::Not) => w!(self, "&self"),
                 crate::ZngurMethodReceiver::Ref(Mutability::Mut) => w!(self, "&mut self"),
@@ -401,7 +636,7 @@ pub extern "C" fn {mangled_name}(
             w!(self, "        fn {}(", method.name);
             match method.receiver {
                 crate::ZngurMethodReceiver::Static => {
-                    panic!("traits with static methods are not object safe");
+                    unreachable!("rejected by `check_supported`");
                 }
                 crate::ZngurMethodReceiver::Ref(Mutability::Not) => w!(self, "&self"),
                 crate::ZngurMethodReceiver::Ref(Mutability::Mut) => w!(self, "&mut self"),
@@ -509,6 +744,10 @@ pub extern "C" fn {constructor}("#
     ) -> ConstructorMangledNames {
         let constructor = mangle_name(rust_name);
         let match_check = format!("{constructor}_check");
+        let fields = args
+            .iter()
+            .map(|(name, ty)| format!("{name}: {}, ", self.read_input(&format!("f_{name}"), ty)))
+            .join("");
         w!(
             self,
             r#"
@@ -522,11 +761,8 @@ pub extern "C" fn {constructor}("#
         w!(
             self,
             r#"o: *mut u8) {{ unsafe {{
-    ::std::ptr::write(o as *mut _, {rust_name} {{ "#
+    ::std::ptr::write(o as *mut _, {rust_name} {{ {fields}"#
         );
-        for (name, ty) in args {
-            w!(self, "{name}: ::std::ptr::read(f_{name} as *mut {ty}), ");
-        }
         wln!(self, "}}) }} }}");
         w!(
             self,
@@ -544,14 +780,21 @@ pub extern "C" fn {match_check}(i: *mut u8, o: *mut u8) {{ unsafe {{
     }
 
     pub(crate) fn add_field_assertions(&mut self, field: &ZngurField, owner: &RustType) {
-        let ZngurField { name, ty, offset } = field;
+        let ZngurField {
+            name,
+            ty,
+            offset,
+            doc: _,
+            span,
+        } = field;
+        let location = zng_location(span.as_ref());
         wln!(
             self,
             r#"
-            const _: [(); {offset}] = [(); ::std::mem::offset_of!({owner}, {name})];
+            const _: [(); {offset}] = [(); ::std::mem::offset_of!({owner}, {name})];{location}
             const _: () = {{
                 fn check_field(value: {owner}) -> {ty} {{
-                    value.{name}
+                    value.{name}{location}
                 }}
             }};
             "#
@@ -682,6 +925,19 @@ pub extern "C" fn {mangled_name}(d: *mut u8) -> *mut ZngurCppOpaqueOwnedObject {
             mangled_name += "_deref_";
             mangled_name += &mangle_name(&inputs[0].to_string());
         }
+        let args = inputs
+            .iter()
+            .enumerate()
+            .map(|(n, ty)| format!("{}, ", self.read_input(&format!("i{n}"), ty)))
+            .join("");
+        let deref = if deref { "&" } else { "" };
+        let call = format!("{rust_name}({deref}{args})");
+        let diverges = *output == RustType::Primitive(PrimitiveRustType::Never);
+        let body = if diverges {
+            call
+        } else {
+            self.write_output(output, &call)
+        };
         w!(
             self,
             r#"
@@ -692,7 +948,8 @@ pub extern "C" fn {mangled_name}("#
         for n in 0..inputs.len() {
             w!(self, "i{n}: *mut u8, ");
         }
-        wln!(self, "o: *mut u8) {{ unsafe {{");
+        let o = if diverges { "_" } else { "o" };
+        wln!(self, "{o}: *mut u8) {{ unsafe {{");
         self.wrap_in_catch_unwind(|this| {
             if let Some(use_path) = use_path {
                 if use_path.first().is_some_and(|x| x == "crate") {
@@ -701,17 +958,7 @@ pub extern "C" fn {mangled_name}("#
                     wln!(this, "    use ::{};", use_path.iter().join("::"));
                 }
             }
-            w!(
-                this,
-                "    ::std::ptr::write(o as *mut {output}, {rust_name}("
-            );
-            if deref {
-                w!(this, "&");
-            }
-            for (n, ty) in inputs.iter().enumerate() {
-                w!(this, "::std::ptr::read(i{n} as *mut {ty}), ");
-            }
-            wln!(this, "));");
+            wln!(this, "    {body};");
         });
         wln!(self, " }} }}");
         mangled_name
@@ -765,9 +1012,249 @@ pub extern "C" fn {debug_print}(v: *mut u8) {{
     eprintln!("{{:?}}", unsafe {{ &*(v as *mut {dbg_ty}) }});
 }}"#
                 );
+                let fmt = self.add_fmt_fn(ty, "debug_fmt", "{:?}");
                 ZngurWellknownTraitData::Debug {
                     pretty_print,
                     debug_print,
+                    fmt,
+                }
+            }
+            ZngurWellknownTrait::Display => ZngurWellknownTraitData::Display {
+                fmt: self.add_fmt_fn(ty, "display_fmt", "{}"),
+            },
+            ZngurWellknownTrait::Clone => {
+                let clone = mangle_name(&format!("{ty}=clone"));
+                wln!(
+                    self,
+                    r#"
+#[allow(non_snake_case)]
+#[unsafe(no_mangle)]
+pub extern "C" fn {clone}(v: *mut u8, o: *mut u8) {{ unsafe {{"#
+                );
+                self.wrap_in_catch_unwind(|this| {
+                    wln!(
+                        this,
+                        "    ::std::ptr::write(o as *mut {ty}, <{ty} as ::std::clone::Clone>::clone(*(v as *mut &{ty})));"
+                    );
+                });
+                wln!(self, " }} }}");
+                ZngurWellknownTraitData::Clone { clone }
+            }
+            ZngurWellknownTrait::Default => {
+                let default = mangle_name(&format!("{ty}=default"));
+                wln!(
+                    self,
+                    r#"
+#[allow(non_snake_case)]
+#[unsafe(no_mangle)]
+pub extern "C" fn {default}(o: *mut u8) {{ unsafe {{"#
+                );
+                self.wrap_in_catch_unwind(|this| {
+                    wln!(
+                        this,
+                        "    ::std::ptr::write(o as *mut {ty}, <{ty} as ::std::default::Default>::default());"
+                    );
+                });
+                wln!(self, " }} }}");
+                ZngurWellknownTraitData::Default { default }
+            }
+            ZngurWellknownTrait::PartialEq => {
+                let eq = mangle_name(&format!("{ty}=eq"));
+                wln!(
+                    self,
+                    r#"
+#[allow(non_snake_case)]
+#[unsafe(no_mangle)]
+pub extern "C" fn {eq}(a: *mut u8, b: *mut u8, o: *mut u8) {{ unsafe {{"#
+                );
+                self.wrap_in_catch_unwind(|this| {
+                    wln!(
+                        this,
+                        "    ::std::ptr::write(o as *mut bool, <{ty} as ::std::cmp::PartialEq>::eq(*(a as *mut &{ty}), *(b as *mut &{ty})));"
+                    );
+                });
+                wln!(self, " }} }}");
+                ZngurWellknownTraitData::PartialEq { eq }
+            }
+            ZngurWellknownTrait::Eq => {
+                wln!(
+                    self,
+                    r#"const _: () = {{
+                const fn static_assert_is_eq<T: ?Sized + ::std::cmp::Eq>() {{}}
+                static_assert_is_eq::<{ty}>();
+            }};"#
+                );
+                ZngurWellknownTraitData::Eq
+            }
+            ZngurWellknownTrait::PartialOrd => {
+                let partial_cmp = mangle_name(&format!("{ty}=partial_cmp"));
+                wln!(
+                    self,
+                    r#"
+#[allow(non_snake_case)]
+#[unsafe(no_mangle)]
+pub extern "C" fn {partial_cmp}(a: *mut u8, b: *mut u8, o: *mut u8) {{ unsafe {{"#
+                );
+                self.wrap_in_catch_unwind(|this| {
+                    wln!(
+                        this,
+                        "    let ordering = <{ty} as ::std::cmp::PartialOrd>::partial_cmp(*(a as *mut &{ty}), *(b as *mut &{ty}));
+    ::std::ptr::write(o as *mut i8, ordering.map_or(2, |ordering| ordering as i8));"
+                    );
+                });
+                wln!(self, " }} }}");
+                ZngurWellknownTraitData::PartialOrd { partial_cmp }
+            }
+            ZngurWellknownTrait::Ord => {
+                let cmp = mangle_name(&format!("{ty}=cmp"));
+                wln!(
+                    self,
+                    r#"
+#[allow(non_snake_case)]
+#[unsafe(no_mangle)]
+pub extern "C" fn {cmp}(a: *mut u8, b: *mut u8, o: *mut u8) {{ unsafe {{"#
+                );
+                self.wrap_in_catch_unwind(|this| {
+                    wln!(
+                        this,
+                        "    ::std::ptr::write(o as *mut i8, <{ty} as ::std::cmp::Ord>::cmp(*(a as *mut &{ty}), *(b as *mut &{ty})) as i8);"
+                    );
+                });
+                wln!(self, " }} }}");
+                ZngurWellknownTraitData::Ord { cmp }
+            }
+            ZngurWellknownTrait::Hash { hasher } => {
+                let hash = mangle_name(&format!("{ty}=hash"));
+                let hasher = match hasher {
+                    Some(hasher) => hasher.to_string(),
+                    None => "::std::collections::hash_map::DefaultHasher".to_owned(),
+                };
+                wln!(
+                    self,
+                    r#"
+#[allow(non_snake_case)]
+#[unsafe(no_mangle)]
+pub extern "C" fn {hash}(v: *mut u8, o: *mut u8) {{ unsafe {{"#
+                );
+                self.wrap_in_catch_unwind(|this| {
+                    wln!(
+                        this,
+                        "    let mut hasher = <{hasher} as ::std::default::Default>::default();
+    <{ty} as ::std::hash::Hash>::hash(*(v as *mut &{ty}), &mut hasher);
+    ::std::ptr::write(o as *mut u64, ::std::hash::Hasher::finish(&hasher));"
+                    );
+                });
+                wln!(self, " }} }}");
+                ZngurWellknownTraitData::Hash { hash }
+            }
+            ZngurWellknownTrait::Operator(operator) => {
+                let op = operator.op;
+                let rhs = operator.rhs.clone().unwrap_or_else(|| ty.clone());
+                let (rust_name, inputs, output) = if op.is_index() {
+                    let m = match op {
+                        ZngurOperatorTrait::IndexMut => Mutability::Mut,
+                        _ => Mutability::Not,
+                    };
+                    let output = operator.output.clone().unwrap();
+                    (
+                        format!(
+                            "<{ty} as ::std::ops::{}<{rhs}>>::{}",
+                            op.name(),
+                            op.method()
+                        ),
+                        vec![RustType::Ref(m, Box::new(ty.clone())), rhs],
+                        RustType::Ref(m, Box::new(output)),
+                    )
+                } else if op.is_unary() {
+                    (
+                        format!("<{ty} as ::std::ops::{}>::{}", op.name(), op.method()),
+                        vec![ty.clone()],
+                        operator.output.clone().unwrap(),
+                    )
+                } else if op.is_assign() {
+                    (
+                        format!(
+                            "<{ty} as ::std::ops::{}<{rhs}>>::{}",
+                            op.name(),
+                            op.method()
+                        ),
+                        vec![RustType::Ref(Mutability::Mut, Box::new(ty.clone())), rhs],
+                        RustType::UNIT,
+                    )
+                } else {
+                    (
+                        format!(
+                            "<{ty} as ::std::ops::{}<{rhs}>>::{}",
+                            op.name(),
+                            op.method()
+                        ),
+                        vec![ty.clone(), rhs],
+                        operator.output.clone().unwrap(),
+                    )
+                };
+                let link_name = self.add_function(&rust_name, &inputs, &output, None, false);
+                ZngurWellknownTraitData::Operator {
+                    operator,
+                    link_name,
+                }
+            }
+            ZngurWellknownTrait::IntoIterator { by_ref, item } => {
+                let self_ty = match by_ref {
+                    Some(m) => RustType::Ref(m, Box::new(ty.clone())),
+                    None => ty.clone(),
+                };
+                let into_iter = mangle_name(&format!("{self_ty}=into_iter"));
+                let next = mangle_name(&format!("{self_ty}=iter_next"));
+                let drop = mangle_name(&format!("{self_ty}=iter_drop"));
+                let iter_ty = format!("<{self_ty} as ::std::iter::IntoIterator>::IntoIter");
+                let input = self.read_input("i0", &self_ty);
+                wln!(
+                    self,
+                    r#"
+#[allow(non_snake_case)]
+#[unsafe(no_mangle)]
+pub extern "C" fn {into_iter}(i0: *mut u8, o: *mut u8) {{ unsafe {{"#
+                );
+                self.wrap_in_catch_unwind(|this| {
+                    wln!(
+                        this,
+                        "    let iter = <{self_ty} as ::std::iter::IntoIterator>::into_iter({input});
+    ::std::ptr::write(o as *mut *mut {iter_ty}, ::std::boxed::Box::into_raw(::std::boxed::Box::new(iter)));"
+                    );
+                });
+                wln!(self, " }} }}");
+                wln!(
+                    self,
+                    r#"
+#[allow(non_snake_case)]
+#[unsafe(no_mangle)]
+pub extern "C" fn {next}(i0: *mut u8, o: *mut u8, found: *mut u8) {{ unsafe {{"#
+                );
+                self.wrap_in_catch_unwind(|this| {
+                    wln!(
+                        this,
+                        "    if let Some(item) = ::std::iter::Iterator::next(&mut *(i0 as *mut {iter_ty})) {{
+        ::std::ptr::write(o as *mut {item}, item);
+        ::std::ptr::write(found as *mut bool, true);
+    }}"
+                    );
+                });
+                wln!(self, " }} }}");
+                wln!(
+                    self,
+                    r#"
+#[allow(non_snake_case)]
+#[unsafe(no_mangle)]
+pub extern "C" fn {drop}(i0: *mut u8) {{ unsafe {{
+    ::std::mem::drop(::std::boxed::Box::from_raw(i0 as *mut {iter_ty}));
+}} }}"#
+                );
+                ZngurWellknownTraitData::IntoIterator {
+                    by_ref,
+                    item,
+                    into_iter,
+                    next,
+                    drop,
                 }
             }
         }
@@ -802,6 +1289,28 @@ pub extern "C" fn {debug_print}(v: *mut u8) {{
         self.panic_to_exception = true;
     }
 
+    /// Adds a function that formats a `&ty` with `format`, and appends the result to a C++
+    /// `std::string`.
+    fn add_fmt_fn(&mut self, ty: &RustType, name: &str, format: &str) -> String {
+        let fmt = mangle_name(&format!("{ty}={name}"));
+        wln!(
+            self,
+            r#"
+#[allow(non_snake_case)]
+#[unsafe(no_mangle)]
+pub extern "C" fn {fmt}(v: *mut u8, buf: *mut u8, append: extern "C" fn(*mut u8, *const u8, usize)) {{ unsafe {{"#
+        );
+        self.wrap_in_catch_unwind(|this| {
+            wln!(
+                this,
+                r#"    let mut writer = zngur_types::ZngurCppWriter {{ buf, append }};
+    _ = ::std::fmt::Write::write_fmt(&mut writer, format_args!("{format}", *(v as *mut &{ty})));"#
+            );
+        });
+        wln!(self, " }} }}");
+        fmt
+    }
+
     fn wrap_in_catch_unwind(&mut self, f: impl FnOnce(&mut RustFile)) {
         if !self.panic_to_exception {
             f(self);
@@ -825,19 +1334,18 @@ pub extern "C" fn {debug_print}(v: *mut u8) {{
             LayoutPolicy::StackAllocated { size, align } => {
                 CppLayoutPolicy::StackAllocated { size, align }
             }
+            LayoutPolicy::Conservative { size, align } => CppLayoutPolicy::Conservative {
+                size,
+                align,
+                size_fn: self.add_size_fn(ty),
+            },
             LayoutPolicy::HeapAllocated => {
-                let size_fn = mangle_name(&format!("{ty}_size_fn"));
+                let size_fn = self.add_size_fn(ty);
                 let alloc_fn = mangle_name(&format!("{ty}_alloc_fn"));
                 let free_fn = mangle_name(&format!("{ty}_free_fn"));
                 wln!(
                     self,
                     r#"
-                #[allow(non_snake_case)]
-                #[unsafe(no_mangle)]
-                pub fn {size_fn}() -> usize {{
-                    ::std::mem::size_of::<{ty}>()
-                }}
-        
                 #[allow(non_snake_case)]
                 #[unsafe(no_mangle)]
                 pub fn {alloc_fn}() -> *mut u8 {{
@@ -858,6 +1366,22 @@ pub extern "C" fn {debug_print}(v: *mut u8) {{
                 }
             }
             LayoutPolicy::OnlyByRef => CppLayoutPolicy::OnlyByRef,
+            LayoutPolicy::Auto => unreachable!("replaced while rendering"),
         }
     }
+
+    /// Adds a function that returns the size of `ty`, for C++ code that can't know it statically.
+    fn add_size_fn(&mut self, ty: &RustType) -> String {
+        let size_fn = mangle_name(&format!("{ty}_size_fn"));
+        wln!(
+            self,
+            r#"
+                #[allow(non_snake_case)]
+                #[unsafe(no_mangle)]
+                pub fn {size_fn}() -> usize {{
+                    ::std::mem::size_of::<{ty}>()
+                }}"#
+        );
+        size_fn
+    }
 }
@@ -0,0 +1,125 @@
+use crate::{LayoutProbeOutput, ParsedZngFile, ZngurGenerator, read_layout_probe};
+
+/// The output of `cargo check --message-format=json` for the probe of `PROBE_ZNG`, in a crate with
+/// an unrelated type error.
+const PROBE_MESSAGES: &str = include_str!("../testdata/layout_probe.jsonl");
+
+const PROBE_ZNG: &str = r#"
+type ::std::vec::Vec<u8> {
+    #layout(auto);
+    fn len(&self) -> usize;
+}
+
+type crate::Empty {
+    #layout(auto);
+}
+"#;
+
+#[test]
+fn render_layout_probe() {
+    let zng = ParsedZngFile::parse("main.zng", PROBE_ZNG).unwrap();
+    let (rust, _, _) = ZngurGenerator::build_from_zng(zng)
+        .render_layout_probe()
+        .unwrap();
+    for probe in [
+        "ZngurLayoutProbe::<1, { ::std::mem::size_of::<::std::vec::Vec::<u8>>() }",
+        "ZngurLayoutProbe::<2, { ::std::mem::size_of::<crate::Empty>() }",
+    ] {
+        assert!(rust.contains(probe), "`{probe}` is not in the probe");
+    }
+}
+
+#[test]
+fn read_layout_probe_from_cargo() {
+    let output = read_layout_probe(PROBE_MESSAGES, 2);
+    assert_eq!(output.layouts, [Some((24, 8)), Some((0, 1))]);
+    assert_eq!(output.errors.len(), 1);
+    assert!(output.errors[0].contains(r#"pub fn broken() -> u32 { "x" }"#));
+}
+
+#[test]
+fn read_layout_probe_from_rustc() {
+    let messages = PROBE_MESSAGES
+        .lines()
+        .filter_map(|line| {
+            let message = serde_json::from_str::<serde_json::Value>(line).unwrap();
+            message["message"]
+                .is_object()
+                .then(|| message["message"].to_string())
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    assert_eq!(
+        read_layout_probe(&messages, 2),
+        read_layout_probe(PROBE_MESSAGES, 2)
+    );
+}
+
+#[test]
+fn read_layout_probe_missing() {
+    assert_eq!(
+        read_layout_probe(PROBE_MESSAGES, 3).layouts,
+        [Some((24, 8)), Some((0, 1)), None]
+    );
+    assert_eq!(
+        read_layout_probe(PROBE_MESSAGES, 1).layouts,
+        [Some((24, 8))]
+    );
+    assert_eq!(
+        read_layout_probe("error: could not compile `pj`\n", 1),
+        LayoutProbeOutput {
+            layouts: vec![None],
+            errors: vec![],
+        }
+    );
+}
+
+fn render_header(zng: &str) -> String {
+    let zng = ParsedZngFile::parse("main.zng", zng).unwrap();
+    let (_, h, _) = ZngurGenerator::build_from_zng(zng).render().unwrap();
+    h
+}
+
+#[test]
+fn render_array() {
+    let h = render_header(
+        r#"
+type [u32; 3] {
+    #layout(size = 12, align = 4);
+    wellknown_traits(Copy);
+}
+"#,
+    );
+    assert!(h.contains("struct Array< ::uint32_t, 3 >"));
+    assert!(h.contains("if (i >= 3) {"));
+}
+
+#[test]
+fn render_never() {
+    let h = render_header(
+        r#"
+mod crate {
+    fn exit(i32) -> !;
+}
+"#,
+    );
+    assert!(h.contains("[[noreturn]] inline void exit(__zngur_dyn_api* api, ::int32_t i0)"));
+    assert!(!h.contains("::void"));
+}
+
+#[test]
+fn render_display() {
+    let h = render_header(
+        r#"
+type crate::Money {
+    #layout(size = 8, align = 8);
+    wellknown_traits(Display);
+}
+"#,
+    );
+    assert!(h.contains(
+        "::std::ostream& operator<<(::std::ostream& os, ::rust::Ref< ::rust::crate::Money > v)"
+    ));
+    let formatter = h.find("#ifdef __cpp_lib_format").unwrap();
+    assert!(h[formatter..].contains("struct std::formatter< ::rust::crate::Money >"));
+}
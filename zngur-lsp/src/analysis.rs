@@ -0,0 +1,271 @@
+use lsp_types::{CompletionItem, CompletionItemKind, Position, Range, SymbolKind};
+use zngur_def::ZngurOperatorTrait;
+use zngur_generator::cpp_type_name;
+use zngur_parser::{Diagnostic, LabelKind, ReferenceTarget, Symbol, SymbolIndex, ZngParser};
+
+/// Attributes that are valid inside a `type` block, with their syntax.
+const TYPE_ATTRIBUTES: &[(&str, &str)] = &[
+    ("layout", "#layout(size = X, align = Y);"),
+    (
+        "layout_conservative",
+        "#layout_conservative(size = X, align = Y);",
+    ),
+    ("heap_allocated", "#heap_allocated;"),
+    ("only_by_ref", "#only_by_ref;"),
+    ("cpp_value", "#cpp_value \"field\" \"::cpp::Type\";"),
+    ("cpp_ref", "#cpp_ref \"::cpp::Type\";"),
+];
+
+/// Attributes that are valid outside of `type` blocks, with their syntax.
+const FILE_ATTRIBUTES: &[(&str, &str)] = &[
+    ("convert_panic_to_exception", "#convert_panic_to_exception"),
+    (
+        "cpp_additional_includes",
+        "#cpp_additional_includes \"#include <...>\"",
+    ),
+    ("allow", "#allow(lint, ...);"),
+];
+
+/// Traits accepted by `wellknown_traits(...)`.
+const WELLKNOWN_TRAITS: &[&str] = &[
+    "Debug",
+    "Display",
+    "Copy",
+    "Clone",
+    "Default",
+    "?Sized",
+    "PartialEq",
+    "Eq",
+    "PartialOrd",
+    "Ord",
+    "Hash",
+    "IntoIterator",
+];
+
+/// A location in a zng file, which might be a different file than the analyzed one.
+pub struct FileSpan {
+    pub file: String,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Result of parsing a single version of an open zng file.
+pub struct Analysis {
+    file: String,
+    text: String,
+    diagnostics: Vec<Diagnostic>,
+    index: SymbolIndex,
+}
+
+impl Analysis {
+    /// Parses `text`. A panic in the parser is reported as a diagnostic at the start of the file,
+    /// so that a single document can't stop the server.
+    pub fn new(file: &str, text: &str) -> Self {
+        let analyze = || {
+            let mut parser = ZngParser::new();
+            let diagnostics = match parser.parse(file, text) {
+                Ok(_) => parser.warnings().to_vec(),
+                Err(diagnostics) => diagnostics,
+            };
+            (diagnostics, parser.index().clone())
+        };
+        let (diagnostics, index) = std::panic::catch_unwind(analyze).unwrap_or_else(|payload| {
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|x| x.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic".to_owned());
+            let diagnostic = Diagnostic::error(format!(
+                "Internal error while analyzing this file: {message}. This is a bug in zngur."
+            ))
+            .with_label(LabelKind::Primary, file, (0..0).into(), "");
+            (vec![diagnostic], SymbolIndex::default())
+        });
+        Analysis {
+            file: file.to_owned(),
+            text: text.to_owned(),
+            diagnostics,
+            index,
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn file(&self) -> &str {
+        &self.file
+    }
+
+    /// Errors, or warnings if there are no errors, of this file and the files it imports.
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    /// The resolved Rust type under `offset` and how it is spelled in C++, as markdown.
+    pub fn hover(&self, offset: usize) -> Option<(String, FileSpan)> {
+        self.index
+            .references_at(&self.file, offset)
+            .find_map(|r| match &r.target {
+                ReferenceTarget::Type(ty) => {
+                    let mut text = format!("```rust\n{ty}\n```");
+                    if let Some(cpp) = cpp_type_name(ty) {
+                        text += &format!("\n```cpp\n{cpp}\n```");
+                    }
+                    Some((text, FileSpan::from(&r.span)))
+                }
+                ReferenceTarget::Alias(_) => None,
+            })
+    }
+
+    /// The alias or `type` block that the path under `offset` refers to.
+    pub fn definition(&self, offset: usize) -> Option<FileSpan> {
+        let references = self
+            .index
+            .references_at(&self.file, offset)
+            .collect::<Vec<_>>();
+        let alias = references.iter().find_map(|r| match &r.target {
+            ReferenceTarget::Alias(definition) => Some(definition),
+            ReferenceTarget::Type(_) => None,
+        });
+        let ty = || {
+            references.iter().find_map(|r| match &r.target {
+                ReferenceTarget::Type(ty) => self.index.type_declaration(ty),
+                ReferenceTarget::Alias(_) => None,
+            })
+        };
+        alias.or_else(ty).map(FileSpan::from)
+    }
+
+    pub fn document_symbols(&self) -> Vec<lsp_types::DocumentSymbol> {
+        self.index
+            .file_symbols(&self.file)
+            .map(|x| self.document_symbol(x))
+            .collect()
+    }
+
+    fn document_symbol(&self, symbol: &Symbol) -> lsp_types::DocumentSymbol {
+        #[allow(deprecated)]
+        lsp_types::DocumentSymbol {
+            name: symbol.name.clone(),
+            detail: None,
+            kind: match symbol.kind {
+                zngur_parser::SymbolKind::Mod => SymbolKind::MODULE,
+                zngur_parser::SymbolKind::Type => SymbolKind::STRUCT,
+                zngur_parser::SymbolKind::Trait => SymbolKind::INTERFACE,
+            },
+            tags: None,
+            deprecated: None,
+            range: range(&self.text, symbol.span.start, symbol.span.end),
+            selection_range: range(&self.text, symbol.name_span.start, symbol.name_span.end),
+            children: Some(
+                symbol
+                    .children
+                    .iter()
+                    .map(|x| self.document_symbol(x))
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Completes `#` attributes and the traits of `wellknown_traits(...)`.
+    pub fn completions(&self, offset: usize) -> Vec<CompletionItem> {
+        let line = &self.text[self.text[..offset].rfind('\n').map_or(0, |x| x + 1)..offset];
+        if let Some(open) = line.rfind("wellknown_traits(") {
+            if !line[open..].contains(')') {
+                let operators = ZngurOperatorTrait::ALL.iter().map(|x| x.name());
+                return WELLKNOWN_TRAITS
+                    .iter()
+                    .map(|name| name.to_string())
+                    .chain(operators)
+                    .map(|label| CompletionItem {
+                        label,
+                        kind: Some(CompletionItemKind::INTERFACE),
+                        ..Default::default()
+                    })
+                    .collect();
+            }
+        }
+        let word = line.trim_end_matches(|c: char| c.is_alphanumeric() || c == '_');
+        if !word.ends_with('#') {
+            return vec![];
+        }
+        let attributes = if self.in_type_block(offset) {
+            TYPE_ATTRIBUTES
+        } else {
+            FILE_ATTRIBUTES
+        };
+        attributes
+            .iter()
+            .map(|(name, syntax)| CompletionItem {
+                label: name.to_string(),
+                kind: Some(CompletionItemKind::KEYWORD),
+                detail: Some(syntax.to_string()),
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    fn in_type_block(&self, offset: usize) -> bool {
+        fn find(symbols: &[Symbol], offset: usize) -> bool {
+            symbols.iter().any(|x| {
+                x.span.start < offset
+                    && offset < x.span.end
+                    && (x.kind == zngur_parser::SymbolKind::Type || find(&x.children, offset))
+            })
+        }
+        find(
+            &self
+                .index
+                .file_symbols(&self.file)
+                .cloned()
+                .collect::<Vec<_>>(),
+            offset,
+        )
+    }
+}
+
+impl From<&zngur_def::ZngurSpan> for FileSpan {
+    fn from(span: &zngur_def::ZngurSpan) -> Self {
+        FileSpan {
+            file: span.file.clone(),
+            start: span.start,
+            end: span.end,
+        }
+    }
+}
+
+/// Converts a byte offset of `text` to a position, which counts UTF-16 code units in a line.
+pub fn position(text: &str, offset: usize) -> Position {
+    let before = &text[..offset.min(text.len())];
+    let line_start = before.rfind('\n').map_or(0, |x| x + 1);
+    Position {
+        line: before.matches('\n').count() as u32,
+        character: before[line_start..].encode_utf16().count() as u32,
+    }
+}
+
+/// Converts a position to a byte offset of `text`. Positions past the end of a line are clamped.
+pub fn offset(text: &str, position: Position) -> usize {
+    let line_start = text
+        .split_inclusive('\n')
+        .take(position.line as usize)
+        .map(|x| x.len())
+        .sum::<usize>();
+    let line = text[line_start..].split('\n').next().unwrap_or("");
+    let mut units = 0;
+    for (i, c) in line.char_indices() {
+        if units >= position.character as usize {
+            return line_start + i;
+        }
+        units += c.len_utf16();
+    }
+    line_start + line.len()
+}
+
+pub fn range(text: &str, start: usize, end: usize) -> Range {
+    Range {
+        start: position(text, start),
+        end: position(text, end),
+    }
+}
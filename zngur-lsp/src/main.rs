@@ -0,0 +1,271 @@
+//! Language server for zng files, speaking the language server protocol over stdio.
+
+use std::{collections::HashMap, error::Error, path::Path};
+
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::{
+    CompletionOptions, CompletionResponse, DiagnosticRelatedInformation, DiagnosticSeverity,
+    DocumentSymbolResponse, GotoDefinitionResponse, Hover, HoverContents, HoverProviderCapability,
+    Location, MarkupContent, MarkupKind, NumberOrString, OneOf, PublishDiagnosticsParams,
+    ServerCapabilities, TextDocumentSyncCapability, TextDocumentSyncKind, Url,
+    notification::{
+        DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, Notification as _,
+        PublishDiagnostics,
+    },
+    request::{Completion, DocumentSymbolRequest, GotoDefinition, HoverRequest, Request as _},
+};
+use zngur_parser::{Diagnostic, LabelKind, Severity};
+
+use crate::analysis::{Analysis, FileSpan, offset, range};
+
+mod analysis;
+
+type Result<T> = std::result::Result<T, Box<dyn Error + Send + Sync>>;
+
+fn main() -> Result<()> {
+    let (connection, io_threads) = Connection::stdio();
+    let capabilities = ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        definition_provider: Some(OneOf::Left(true)),
+        document_symbol_provider: Some(OneOf::Left(true)),
+        completion_provider: Some(CompletionOptions {
+            trigger_characters: Some(vec!["#".to_owned(), "(".to_owned(), ",".to_owned()]),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    connection.initialize(serde_json::to_value(capabilities)?)?;
+    Server {
+        connection: &connection,
+        documents: HashMap::new(),
+    }
+    .run()?;
+    drop(connection);
+    io_threads.join()?;
+    Ok(())
+}
+
+struct Document {
+    analysis: Analysis,
+    /// Files that diagnostics of this document were published for, so that they can be cleared.
+    published: Vec<Url>,
+}
+
+struct Server<'a> {
+    connection: &'a Connection,
+    documents: HashMap<Url, Document>,
+}
+
+impl Server<'_> {
+    fn run(&mut self) -> Result<()> {
+        for message in &self.connection.receiver {
+            match message {
+                Message::Request(req) => {
+                    if self.connection.handle_shutdown(&req)? {
+                        return Ok(());
+                    }
+                    let response = self.handle_request(req);
+                    self.connection.sender.send(response.into())?;
+                }
+                Message::Notification(not) => self.handle_notification(not)?,
+                Message::Response(_) => (),
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_request(&self, req: Request) -> Response {
+        match req.method.as_str() {
+            HoverRequest::METHOD => self.dispatch::<HoverRequest>(req, |this, params| {
+                let position = params.text_document_position_params;
+                let analysis = this.analysis(&position.text_document.uri)?;
+                let (text, span) = analysis.hover(offset(analysis.text(), position.position))?;
+                Some(Hover {
+                    contents: HoverContents::Markup(MarkupContent {
+                        kind: MarkupKind::Markdown,
+                        value: text,
+                    }),
+                    range: Some(range(analysis.text(), span.start, span.end)),
+                })
+            }),
+            GotoDefinition::METHOD => self.dispatch::<GotoDefinition>(req, |this, params| {
+                let position = params.text_document_position_params;
+                let analysis = this.analysis(&position.text_document.uri)?;
+                let target = analysis.definition(offset(analysis.text(), position.position))?;
+                Some(GotoDefinitionResponse::Scalar(this.location(&target)?))
+            }),
+            DocumentSymbolRequest::METHOD => {
+                self.dispatch::<DocumentSymbolRequest>(req, |this, params| {
+                    let analysis = this.analysis(&params.text_document.uri)?;
+                    Some(DocumentSymbolResponse::Nested(analysis.document_symbols()))
+                })
+            }
+            Completion::METHOD => self.dispatch::<Completion>(req, |this, params| {
+                let position = params.text_document_position;
+                let analysis = this.analysis(&position.text_document.uri)?;
+                Some(CompletionResponse::Array(
+                    analysis.completions(offset(analysis.text(), position.position)),
+                ))
+            }),
+            _ => Response::new_err(
+                req.id,
+                ErrorCode::MethodNotFound as i32,
+                format!("Unsupported request `{}`", req.method),
+            ),
+        }
+    }
+
+    fn dispatch<R: lsp_types::request::Request>(
+        &self,
+        req: Request,
+        f: impl FnOnce(&Self, R::Params) -> R::Result,
+    ) -> Response {
+        match serde_json::from_value::<R::Params>(req.params) {
+            Ok(params) => Response::new_ok(req.id, f(self, params)),
+            Err(e) => Response::new_err(req.id, ErrorCode::InvalidParams as i32, e.to_string()),
+        }
+    }
+
+    fn handle_notification(&mut self, not: Notification) -> Result<()> {
+        match not.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let params = not
+                    .extract::<lsp_types::DidOpenTextDocumentParams>(DidOpenTextDocument::METHOD)?;
+                let document = params.text_document;
+                self.update(document.uri, &document.text)?;
+            }
+            DidChangeTextDocument::METHOD => {
+                let params = not.extract::<lsp_types::DidChangeTextDocumentParams>(
+                    DidChangeTextDocument::METHOD,
+                )?;
+                // Only full syncs are requested, so the last change has the whole text.
+                if let Some(change) = params.content_changes.into_iter().last() {
+                    self.update(params.text_document.uri, &change.text)?;
+                }
+            }
+            DidCloseTextDocument::METHOD => {
+                let params = not.extract::<lsp_types::DidCloseTextDocumentParams>(
+                    DidCloseTextDocument::METHOD,
+                )?;
+                if let Some(document) = self.documents.remove(&params.text_document.uri) {
+                    for uri in document.published {
+                        self.publish(uri, vec![])?;
+                    }
+                }
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+
+    /// Analyzes the new text of a document and publishes its diagnostics.
+    fn update(&mut self, uri: Url, text: &str) -> Result<()> {
+        let file = match uri.to_file_path() {
+            Ok(path) => path.to_string_lossy().into_owned(),
+            Err(()) => uri.to_string(),
+        };
+        let previous = self.documents.insert(
+            uri.clone(),
+            Document {
+                analysis: Analysis::new(&file, text),
+                published: vec![],
+            },
+        );
+        let mut groups: Vec<(Url, Vec<lsp_types::Diagnostic>)> = vec![(uri.clone(), vec![])];
+        for d in self.documents[&uri].analysis.diagnostics() {
+            let Some(location) = d.location() else {
+                continue;
+            };
+            let Some(span) = self.location(&FileSpan {
+                file: location.file.clone(),
+                start: location.span.start,
+                end: location.span.end,
+            }) else {
+                continue;
+            };
+            let diagnostic = self.diagnostic(d, span.range);
+            match groups.iter_mut().find(|(x, _)| *x == span.uri) {
+                Some((_, group)) => group.push(diagnostic),
+                None => groups.push((span.uri, vec![diagnostic])),
+            }
+        }
+        for stale in previous.into_iter().flat_map(|x| x.published) {
+            if !groups.iter().any(|(x, _)| *x == stale) {
+                self.publish(stale, vec![])?;
+            }
+        }
+        let published = groups.iter().map(|(x, _)| x.clone()).collect();
+        self.documents.get_mut(&uri).unwrap().published = published;
+        for (uri, diagnostics) in groups {
+            self.publish(uri, diagnostics)?;
+        }
+        Ok(())
+    }
+
+    fn diagnostic(&self, d: &Diagnostic, range: lsp_types::Range) -> lsp_types::Diagnostic {
+        let related = d
+            .labels
+            .iter()
+            .filter(|l| l.kind != LabelKind::Primary)
+            .filter_map(|l| {
+                Some(DiagnosticRelatedInformation {
+                    location: self.location(&FileSpan {
+                        file: l.file.clone(),
+                        start: l.span.start,
+                        end: l.span.end,
+                    })?,
+                    message: l.message.clone(),
+                })
+            })
+            .collect::<Vec<_>>();
+        lsp_types::Diagnostic {
+            range,
+            severity: Some(match d.severity {
+                Severity::Error => DiagnosticSeverity::ERROR,
+                Severity::Warning => DiagnosticSeverity::WARNING,
+            }),
+            code: d.lint.map(|x| NumberOrString::String(x.name().to_owned())),
+            source: Some("zngur".to_owned()),
+            message: d.message.clone(),
+            related_information: (!related.is_empty()).then_some(related),
+            ..Default::default()
+        }
+    }
+
+    fn publish(&self, uri: Url, diagnostics: Vec<lsp_types::Diagnostic>) -> Result<()> {
+        let params = PublishDiagnosticsParams {
+            uri,
+            diagnostics,
+            version: None,
+        };
+        let not = Notification::new(PublishDiagnostics::METHOD.to_owned(), params);
+        self.connection.sender.send(not.into())?;
+        Ok(())
+    }
+
+    fn analysis(&self, uri: &Url) -> Option<&Analysis> {
+        self.documents.get(uri).map(|x| &x.analysis)
+    }
+
+    /// Converts a span of any file, which might not be open in the editor, to a location.
+    fn location(&self, span: &FileSpan) -> Option<Location> {
+        let open = self
+            .documents
+            .iter()
+            .find(|(_, x)| x.analysis.file() == span.file);
+        if let Some((uri, document)) = open {
+            return Some(Location {
+                uri: uri.clone(),
+                range: range(document.analysis.text(), span.start, span.end),
+            });
+        }
+        let path = Path::new(&span.file);
+        let text = std::fs::read_to_string(path).ok()?;
+        let uri = Url::from_file_path(path.canonicalize().ok()?).ok()?;
+        Some(Location {
+            uri,
+            range: range(&text, span.start, span.end),
+        })
+    }
+}
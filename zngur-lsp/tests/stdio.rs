@@ -0,0 +1,553 @@
+use std::{
+    collections::VecDeque,
+    io::{BufRead, BufReader, Read, Write},
+    path::{Path, PathBuf},
+    process::{Child, ChildStdin, ChildStdout, Command, Stdio},
+};
+
+use expect_test::{Expect, expect};
+use serde_json::{Value, json};
+
+/// A minimal language client that talks to the `zngur-lsp` binary over its stdio.
+struct Client {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    next_id: i64,
+    /// Notifications received while waiting for a response.
+    notifications: VecDeque<Value>,
+    /// Replaced with `$DIR` in every message, so that snapshots don't depend on the temp dir.
+    dir: String,
+}
+
+impl Client {
+    fn start(dir: &Path) -> Self {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_zngur-lsp"))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut client = Client {
+            stdin: child.stdin.take().unwrap(),
+            stdout: BufReader::new(child.stdout.take().unwrap()),
+            child,
+            next_id: 0,
+            notifications: VecDeque::new(),
+            dir: url(dir),
+        };
+        client.request("initialize", json!({ "capabilities": {} }));
+        client.notify("initialized", json!({}));
+        client
+    }
+
+    fn send(&mut self, message: Value) {
+        let body = message.to_string();
+        write!(self.stdin, "Content-Length: {}\r\n\r\n{body}", body.len()).unwrap();
+        self.stdin.flush().unwrap();
+    }
+
+    fn receive(&mut self) -> Value {
+        let mut length = None;
+        loop {
+            let mut line = String::new();
+            self.stdout.read_line(&mut line).unwrap();
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            if let Some(x) = line.strip_prefix("Content-Length: ") {
+                length = Some(x.parse().unwrap());
+            }
+        }
+        let mut body = vec![0; length.expect("message without Content-Length")];
+        self.stdout.read_exact(&mut body).unwrap();
+        let body = String::from_utf8(body).unwrap().replace(&self.dir, "$DIR");
+        serde_json::from_str(&body).unwrap()
+    }
+
+    fn request(&mut self, method: &str, params: Value) -> Value {
+        self.next_id += 1;
+        let id = self.next_id;
+        self.send(json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }));
+        loop {
+            let message = self.receive();
+            if message.get("id") == Some(&json!(id)) {
+                return message["result"].clone();
+            }
+            self.notifications.push_back(message);
+        }
+    }
+
+    fn notify(&mut self, method: &str, params: Value) {
+        self.send(json!({ "jsonrpc": "2.0", "method": method, "params": params }));
+    }
+
+    /// Waits for the next `textDocument/publishDiagnostics` notification.
+    fn diagnostics(&mut self) -> Value {
+        loop {
+            let message = match self.notifications.pop_front() {
+                Some(x) => x,
+                None => self.receive(),
+            };
+            if message["method"] == "textDocument/publishDiagnostics" {
+                return message["params"].clone();
+            }
+        }
+    }
+
+    fn shutdown(mut self) {
+        self.request("shutdown", Value::Null);
+        self.notify("exit", Value::Null);
+        assert!(self.child.wait().unwrap().success());
+    }
+}
+
+fn url(path: &Path) -> String {
+    format!("file://{}", path.display())
+}
+
+fn write_files(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("zngur-lsp-{name}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let dir = dir.canonicalize().unwrap();
+    for (path, content) in files {
+        std::fs::write(dir.join(path), content).unwrap();
+    }
+    dir
+}
+
+/// Position of the first occurrence of `marker` in `text`, plus `delta` characters.
+fn position(text: &str, marker: &str, delta: usize) -> Value {
+    let offset = text.find(marker).unwrap() + delta;
+    let line = text[..offset].matches('\n').count();
+    let character = offset - text[..offset].rfind('\n').map_or(0, |x| x + 1);
+    json!({ "line": line, "character": character })
+}
+
+fn check(value: Value, expect: Expect) {
+    expect.assert_eq(&serde_json::to_string_pretty(&value).unwrap());
+}
+
+const MAIN: &str = r#"use crate::Point as P;
+use ::std::string::String as S;
+import "point.zng";
+mod crate {
+    type Line {
+        #layout(size = 16, align = 4);
+        wellknown_traits(Copy);
+        constructor { a: P, b: Point };
+        fn start(&self) -> P;
+    }
+    trait Shape {
+        fn area(&self) -> f64;
+    }
+}
+"#;
+
+const POINT: &str = r#"type crate::Point {
+    #layout(size = 8, align = 4);
+    wellknown_traits(Copy);
+}
+"#;
+
+fn open(name: &str) -> (Client, String) {
+    let dir = write_files(name, &[("main.zng", MAIN), ("point.zng", POINT)]);
+    let mut client = Client::start(&dir);
+    let uri = url(&dir.join("main.zng"));
+    client.notify(
+        "textDocument/didOpen",
+        json!({
+            "textDocument": { "uri": uri, "languageId": "zng", "version": 1, "text": MAIN }
+        }),
+    );
+    (client, uri)
+}
+
+fn at(uri: &str, marker: &str, delta: usize) -> Value {
+    json!({ "textDocument": { "uri": uri }, "position": position(MAIN, marker, delta) })
+}
+
+#[test]
+fn diagnostics_as_you_type() {
+    let (mut client, uri) = open("diagnostics");
+    check(
+        client.diagnostics(),
+        expect![[r#"
+            {
+              "diagnostics": [
+                {
+                  "code": "unused_alias",
+                  "message": "Alias `S` is never used",
+                  "range": {
+                    "end": {
+                      "character": 31,
+                      "line": 1
+                    },
+                    "start": {
+                      "character": 0,
+                      "line": 1
+                    }
+                  },
+                  "severity": 2,
+                  "source": "zngur"
+                }
+              ],
+              "uri": "$DIR/main.zng"
+            }"#]],
+    );
+    let broken = MAIN.replace("fn area(&self) -> f64;", "fn area(&self) -> ;");
+    client.notify(
+        "textDocument/didChange",
+        json!({
+            "textDocument": { "uri": uri, "version": 2 },
+            "contentChanges": [{ "text": broken }]
+        }),
+    );
+    check(
+        client.diagnostics(),
+        expect![[r#"
+        {
+          "diagnostics": [
+            {
+              "message": "found ';' expected something else, 'Box', '(', '[', '::', 'crate', '&', '*', 'dyn', 'fn', or '!'",
+              "range": {
+                "end": {
+                  "character": 27,
+                  "line": 11
+                },
+                "start": {
+                  "character": 26,
+                  "line": 11
+                }
+              },
+              "severity": 1,
+              "source": "zngur"
+            },
+            {
+              "code": "unused_alias",
+              "message": "Alias `S` is never used",
+              "range": {
+                "end": {
+                  "character": 31,
+                  "line": 1
+                },
+                "start": {
+                  "character": 0,
+                  "line": 1
+                }
+              },
+              "severity": 2,
+              "source": "zngur"
+            }
+          ],
+          "uri": "$DIR/main.zng"
+        }"#]],
+    );
+    client.notify(
+        "textDocument/didClose",
+        json!({ "textDocument": { "uri": uri } }),
+    );
+    check(
+        client.diagnostics(),
+        expect![[r#"
+            {
+              "diagnostics": [],
+              "uri": "$DIR/main.zng"
+            }"#]],
+    );
+    client.shutdown();
+}
+
+#[test]
+fn diagnostics_for_invalid_types() {
+    let (mut client, uri) = open("invalid_types");
+    client.diagnostics();
+    client.notify(
+        "textDocument/didChange",
+        json!({
+            "textDocument": { "uri": uri, "version": 2 },
+            "contentChanges": [{ "text": "type [u8; 99999999999999999999] { #heap_allocated; }\ntype Box<i32, i32> { #heap_allocated; }\n" }]
+        }),
+    );
+    check(
+        client.diagnostics(),
+        expect![[r#"
+        {
+          "diagnostics": [
+            {
+              "message": "Number `99999999999999999999` is too large",
+              "range": {
+                "end": {
+                  "character": 30,
+                  "line": 0
+                },
+                "start": {
+                  "character": 10,
+                  "line": 0
+                }
+              },
+              "severity": 1,
+              "source": "zngur"
+            },
+            {
+              "message": "`Box` takes exactly one type argument, like `Box<T>`",
+              "range": {
+                "end": {
+                  "character": 18,
+                  "line": 1
+                },
+                "start": {
+                  "character": 5,
+                  "line": 1
+                }
+              },
+              "severity": 1,
+              "source": "zngur"
+            }
+          ],
+          "uri": "$DIR/main.zng"
+        }"#]],
+    );
+    client.shutdown();
+}
+
+#[test]
+fn hover_and_definition() {
+    let (mut client, uri) = open("navigation");
+    check(
+        client.request("textDocument/hover", at(&uri, "-> P", 3)),
+        expect![[r#"
+            {
+              "contents": {
+                "kind": "markdown",
+                "value": "```rust\ncrate::Point\n```\n```cpp\n::rust::crate::Point\n```"
+              },
+              "range": {
+                "end": {
+                  "character": 28,
+                  "line": 8
+                },
+                "start": {
+                  "character": 27,
+                  "line": 8
+                }
+              }
+            }"#]],
+    );
+    check(
+        client.request("textDocument/hover", at(&uri, "f64", 1)),
+        expect!["null"],
+    );
+    check(
+        client.request("textDocument/definition", at(&uri, "-> P", 3)),
+        expect![[r#"
+            {
+              "range": {
+                "end": {
+                  "character": 22,
+                  "line": 0
+                },
+                "start": {
+                  "character": 0,
+                  "line": 0
+                }
+              },
+              "uri": "$DIR/main.zng"
+            }"#]],
+    );
+    check(
+        client.request("textDocument/definition", at(&uri, "b: Point", 4)),
+        expect![[r#"
+            {
+              "range": {
+                "end": {
+                  "character": 17,
+                  "line": 0
+                },
+                "start": {
+                  "character": 5,
+                  "line": 0
+                }
+              },
+              "uri": "$DIR/point.zng"
+            }"#]],
+    );
+    check(
+        client.request("textDocument/definition", at(&uri, "mod crate", 0)),
+        expect!["null"],
+    );
+    client.shutdown();
+}
+
+#[test]
+fn document_symbols() {
+    let (mut client, uri) = open("symbols");
+    check(
+        client.request(
+            "textDocument/documentSymbol",
+            json!({ "textDocument": { "uri": uri } }),
+        ),
+        expect![[r#"
+            [
+              {
+                "children": [
+                  {
+                    "children": [],
+                    "kind": 23,
+                    "name": "Line",
+                    "range": {
+                      "end": {
+                        "character": 5,
+                        "line": 9
+                      },
+                      "start": {
+                        "character": 4,
+                        "line": 4
+                      }
+                    },
+                    "selectionRange": {
+                      "end": {
+                        "character": 13,
+                        "line": 4
+                      },
+                      "start": {
+                        "character": 9,
+                        "line": 4
+                      }
+                    }
+                  },
+                  {
+                    "children": [],
+                    "kind": 11,
+                    "name": "Shape",
+                    "range": {
+                      "end": {
+                        "character": 5,
+                        "line": 12
+                      },
+                      "start": {
+                        "character": 4,
+                        "line": 10
+                      }
+                    },
+                    "selectionRange": {
+                      "end": {
+                        "character": 15,
+                        "line": 10
+                      },
+                      "start": {
+                        "character": 10,
+                        "line": 10
+                      }
+                    }
+                  }
+                ],
+                "kind": 2,
+                "name": "crate",
+                "range": {
+                  "end": {
+                    "character": 1,
+                    "line": 13
+                  },
+                  "start": {
+                    "character": 0,
+                    "line": 3
+                  }
+                },
+                "selectionRange": {
+                  "end": {
+                    "character": 9,
+                    "line": 3
+                  },
+                  "start": {
+                    "character": 4,
+                    "line": 3
+                  }
+                }
+              }
+            ]"#]],
+    );
+    client.shutdown();
+}
+
+#[test]
+fn completion() {
+    let (mut client, uri) = open("completion");
+    let labels = |client: &mut Client, marker, delta| {
+        let items = client.request("textDocument/completion", at(&uri, marker, delta));
+        items
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|x| x["label"].as_str().unwrap().to_owned())
+            .collect::<Vec<_>>()
+    };
+    let traits = labels(&mut client, "wellknown_traits(", 17);
+    assert_eq!(
+        traits[..11],
+        [
+            "Debug",
+            "Display",
+            "Copy",
+            "Clone",
+            "Default",
+            "?Sized",
+            "PartialEq",
+            "Eq",
+            "PartialOrd",
+            "Ord",
+            "Hash"
+        ]
+    );
+    assert!(traits.iter().any(|x| x == "ShlAssign"));
+    assert_eq!(
+        labels(&mut client, "#layout", 1),
+        [
+            "layout",
+            "layout_conservative",
+            "heap_allocated",
+            "only_by_ref",
+            "cpp_value",
+            "cpp_ref"
+        ]
+    );
+    assert_eq!(labels(&mut client, "mod crate", 0), Vec::<String>::new());
+    check(
+        client.request("textDocument/completion", at(&uri, "#layout", 4)),
+        expect![[r##"
+            [
+              {
+                "detail": "#layout(size = X, align = Y);",
+                "kind": 14,
+                "label": "layout"
+              },
+              {
+                "detail": "#layout_conservative(size = X, align = Y);",
+                "kind": 14,
+                "label": "layout_conservative"
+              },
+              {
+                "detail": "#heap_allocated;",
+                "kind": 14,
+                "label": "heap_allocated"
+              },
+              {
+                "detail": "#only_by_ref;",
+                "kind": 14,
+                "label": "only_by_ref"
+              },
+              {
+                "detail": "#cpp_value \"field\" \"::cpp::Type\";",
+                "kind": 14,
+                "label": "cpp_value"
+              },
+              {
+                "detail": "#cpp_ref \"::cpp::Type\";",
+                "kind": 14,
+                "label": "cpp_ref"
+              }
+            ]"##]],
+    );
+    client.shutdown();
+}
@@ -0,0 +1,374 @@
+use zngur_def::{
+    LayoutPolicy, Mutability, RustType, ZngurConstructor, ZngurField, ZngurFile,
+    ZngurMethodDetails, ZngurOperator, ZngurOperatorTrait, ZngurType, ZngurWellknownTrait,
+};
+
+use crate::{Diagnostic, parse_fn, parse_method, parse_rust_type};
+
+/// Adds [`ZngurFile::builder`](ZngurFileExt::builder).
+pub trait ZngurFileExt {
+    /// A builder for a file with no items.
+    fn builder() -> ZngurFileBuilder;
+}
+
+impl ZngurFileExt for ZngurFile {
+    fn builder() -> ZngurFileBuilder {
+        ZngurFileBuilder::default()
+    }
+}
+
+/// Builder for a [`ZngurFile`], for build scripts that generate a part of the bindings from data
+/// instead of writing it in a zng file.
+///
+/// Types and signatures are written in the syntax of zng files, and paths in them are relative
+/// to the root, like in items outside of `mod` blocks. Problems in them are not reported until
+/// [`build`](ZngurFileBuilder::build), so the calls can be chained:
+///
+/// ```
+/// use zngur_def::{ZngurFile, ZngurWellknownTrait};
+/// use zngur_parser::ZngurFileExt;
+///
+/// let file = ZngurFile::builder()
+///     .ty("::std::vec::Vec<i32>")
+///     .layout(24, 8)
+///     .wellknown_traits(&[ZngurWellknownTrait::Debug])
+///     .method("fn new() -> ::std::vec::Vec<i32>")
+///     .method("fn push(&mut self, i32)")
+///     .func("fn crate::sum(&[i32]) -> i32")
+///     .build()
+///     .unwrap();
+/// assert_eq!(file.types[0].methods.len(), 2);
+/// ```
+#[derive(Debug, Default)]
+#[must_use]
+pub struct ZngurFileBuilder {
+    file: ZngurFile,
+    errors: Vec<Diagnostic>,
+}
+
+impl ZngurFileBuilder {
+    /// Starts a type item, like `type Vec<i32> { ... }` in a zng file. The type is added to the
+    /// file when the next item starts, or when the file is built.
+    pub fn ty(self, ty: &str) -> ZngurTypeBuilder {
+        ZngurTypeBuilder::new(self, ty)
+    }
+
+    /// Adds a free function, given by its signature and full path, like
+    /// `fn crate::add(i32, i32) -> i32`.
+    pub fn func(mut self, signature: &str) -> Self {
+        match parse_fn(signature) {
+            Ok(f) => self.file.funcs.push(f),
+            Err(e) => self.error(format!("Invalid function signature `{signature}`: {e}")),
+        }
+        self
+    }
+
+    /// Appends to the content of `#cpp_additional_includes`.
+    pub fn additional_includes(mut self, includes: &str) -> Self {
+        self.file.additional_includes += includes;
+        self
+    }
+
+    /// Same as `#convert_panic_to_exception` in a zng file.
+    pub fn convert_panic_to_exception(mut self) -> Self {
+        self.file.convert_panic_to_exception = true;
+        self
+    }
+
+    /// The built file, or every problem found while building it.
+    pub fn build(self) -> Result<ZngurFile, Vec<Diagnostic>> {
+        if !self.errors.is_empty() {
+            return Err(self.errors);
+        }
+        Ok(self.file)
+    }
+
+    fn error(&mut self, message: String) {
+        self.errors.push(Diagnostic::error(message));
+    }
+}
+
+/// Builder for a type of a [`ZngurFileBuilder`]. See [`ZngurFileBuilder::ty`].
+#[derive(Debug)]
+#[must_use]
+pub struct ZngurTypeBuilder {
+    file: ZngurFileBuilder,
+    /// The text of the type, used in diagnostics.
+    name: String,
+    /// `None` if the type is invalid, which is already reported.
+    ty: Option<RustType>,
+    layout: Option<LayoutPolicy>,
+    wellknown_traits: Vec<ZngurWellknownTrait>,
+    methods: Vec<ZngurMethodDetails>,
+    constructors: Vec<ZngurConstructor>,
+    fields: Vec<ZngurField>,
+    cpp_value: Option<(String, String)>,
+    cpp_ref: Option<String>,
+    doc: Option<String>,
+}
+
+impl ZngurTypeBuilder {
+    fn new(mut file: ZngurFileBuilder, name: &str) -> Self {
+        let ty = match parse_rust_type(name) {
+            Ok(ty) => Some(ty),
+            Err(e) => {
+                file.error(format!("Invalid type `{name}`: {e}"));
+                None
+            }
+        };
+        ZngurTypeBuilder {
+            file,
+            name: name.to_owned(),
+            ty,
+            layout: None,
+            wellknown_traits: vec![],
+            methods: vec![],
+            constructors: vec![],
+            fields: vec![],
+            cpp_value: None,
+            cpp_ref: None,
+            doc: None,
+        }
+    }
+
+    /// Same as `#layout(size = size, align = align)`.
+    pub fn layout(self, size: usize, align: usize) -> Self {
+        self.layout_policy(LayoutPolicy::StackAllocated { size, align })
+    }
+
+    /// Same as `#layout(auto)`.
+    pub fn auto_layout(self) -> Self {
+        self.layout_policy(LayoutPolicy::Auto)
+    }
+
+    /// Same as `#layout_conservative(size = size, align = align)`.
+    pub fn conservative_layout(self, size: usize, align: usize) -> Self {
+        self.layout_policy(LayoutPolicy::Conservative { size, align })
+    }
+
+    /// Same as `#heap_allocated`.
+    pub fn heap_allocated(self) -> Self {
+        self.layout_policy(LayoutPolicy::HeapAllocated)
+    }
+
+    /// Same as `#only_by_ref`.
+    pub fn only_by_ref(self) -> Self {
+        self.layout_policy(LayoutPolicy::OnlyByRef)
+    }
+
+    /// Same as `wellknown_traits(...)`. Can be used multiple times.
+    pub fn wellknown_traits(mut self, traits: &[ZngurWellknownTrait]) -> Self {
+        self.wellknown_traits.extend_from_slice(traits);
+        self
+    }
+
+    /// Same as `Op<rhs, Output = output>` in `wellknown_traits(...)`, where `op` is an operator
+    /// trait like `Add`. `rhs` is `Self` if not given.
+    pub fn operator(
+        mut self,
+        op: ZngurOperatorTrait,
+        rhs: Option<&str>,
+        output: Option<&str>,
+    ) -> Self {
+        let mut parse = |ty: Option<&str>| {
+            let ty = ty?;
+            parse_rust_type(ty)
+                .map_err(|e| self.error(format!("Invalid type `{ty}`: {e}")))
+                .ok()
+        };
+        let operator = ZngurOperator {
+            op,
+            rhs: parse(rhs),
+            output: parse(output),
+        };
+        match operator.check() {
+            Ok(()) => self
+                .wellknown_traits
+                .push(ZngurWellknownTrait::Operator(operator)),
+            Err(e) => self.error(format!("{e}, in type `{}`", self.name)),
+        }
+        self
+    }
+
+    /// Same as `IntoIterator<Item = item>` in `wellknown_traits(...)`, or `&IntoIterator<...>` and
+    /// `&mut IntoIterator<...>` if `by_ref` is given.
+    pub fn into_iterator(mut self, by_ref: Option<Mutability>, item: &str) -> Self {
+        match parse_rust_type(item) {
+            Ok(item) => self
+                .wellknown_traits
+                .push(ZngurWellknownTrait::IntoIterator { by_ref, item }),
+            Err(e) => self.error(format!("Invalid type `{item}`: {e}")),
+        }
+        self
+    }
+
+    /// Same as `Hash<Hasher = hasher>` in `wellknown_traits(...)`, or `Hash` if `hasher` is `None`.
+    pub fn hash(mut self, hasher: Option<&str>) -> Self {
+        match hasher.map(parse_rust_type).transpose() {
+            Ok(hasher) => self
+                .wellknown_traits
+                .push(ZngurWellknownTrait::Hash { hasher }),
+            Err(e) => self.error(format!(
+                "Invalid type `{}`: {e}",
+                hasher.unwrap_or_default()
+            )),
+        }
+        self
+    }
+
+    /// Adds a method, given by its signature, like `fn push(&mut self, i32)`.
+    pub fn method(mut self, signature: &str) -> Self {
+        match parse_method(signature) {
+            Ok(data) => self.methods.push(ZngurMethodDetails {
+                data,
+                use_path: None,
+                deref: None,
+            }),
+            Err(e) => self.error(format!("Invalid method signature `{signature}`: {e}")),
+        }
+        self
+    }
+
+    /// Adds a constructor of the struct or enum variant `name`, or of the type itself if `name`
+    /// is `None`. `inputs` are the names and types of the fields, where the names of tuple fields
+    /// are their indices.
+    pub fn constructor(mut self, name: Option<&str>, inputs: &[(&str, &str)]) -> Self {
+        let mut converted = vec![];
+        for (field, ty) in inputs {
+            match parse_rust_type(ty) {
+                Ok(ty) => converted.push((field.to_string(), ty)),
+                Err(e) => self.error(format!("Invalid type `{ty}`: {e}")),
+            }
+        }
+        self.constructors.push(ZngurConstructor {
+            name: name.map(|x| x.to_owned()),
+            inputs: converted,
+            doc: None,
+            span: None,
+        });
+        self
+    }
+
+    /// Same as `field name (offset = offset, type = ty);`.
+    pub fn field(mut self, name: &str, offset: usize, ty: &str) -> Self {
+        match parse_rust_type(ty) {
+            Ok(ty) => self.fields.push(ZngurField {
+                name: name.to_owned(),
+                ty,
+                offset,
+                doc: None,
+                span: None,
+            }),
+            Err(e) => self.error(format!("Invalid type `{ty}`: {e}")),
+        }
+        self
+    }
+
+    /// Same as `#cpp_value "field" "cpp_type"`.
+    pub fn cpp_value(mut self, field: &str, cpp_type: &str) -> Self {
+        self.cpp_value = Some((field.to_owned(), cpp_type.to_owned()));
+        self
+    }
+
+    /// Same as `#cpp_ref "cpp_type"`, which also sets the layout.
+    pub fn cpp_ref(mut self, cpp_type: &str) -> Self {
+        self.cpp_ref = Some(cpp_type.to_owned());
+        self.layout_policy(LayoutPolicy::StackAllocated { size: 0, align: 1 })
+    }
+
+    /// Documentation of the type, which is copied into the generated header.
+    pub fn doc(mut self, doc: &str) -> Self {
+        self.doc = Some(doc.to_owned());
+        self
+    }
+
+    /// Adds the type to the file, and starts another one.
+    pub fn ty(self, ty: &str) -> ZngurTypeBuilder {
+        self.finish().ty(ty)
+    }
+
+    /// Adds the type to the file, and then the function. See [`ZngurFileBuilder::func`].
+    pub fn func(self, signature: &str) -> ZngurFileBuilder {
+        self.finish().func(signature)
+    }
+
+    /// Adds the type to the file, and builds it. See [`ZngurFileBuilder::build`].
+    pub fn build(self) -> Result<ZngurFile, Vec<Diagnostic>> {
+        self.finish().build()
+    }
+
+    /// Adds the type to the file, and returns the builder of the file.
+    pub fn finish(self) -> ZngurFileBuilder {
+        let ZngurTypeBuilder {
+            mut file,
+            name,
+            ty,
+            mut layout,
+            mut wellknown_traits,
+            methods,
+            constructors,
+            fields,
+            cpp_value,
+            cpp_ref,
+            doc,
+        } = self;
+        for tr in &wellknown_traits {
+            for required in tr.required_traits() {
+                if !wellknown_traits.contains(required) {
+                    file.error(format!("`{tr}` of type `{name}` requires `{required}`"));
+                }
+            }
+        }
+        let is_unsized = wellknown_traits.contains(&ZngurWellknownTrait::Unsized);
+        let is_copy = wellknown_traits.contains(&ZngurWellknownTrait::Copy);
+        if !is_copy && !is_unsized {
+            wellknown_traits.push(ZngurWellknownTrait::Drop);
+        }
+        if is_unsized {
+            if layout.is_some() {
+                file.error(format!(
+                    "Duplicate layout policy found for unsized type `{name}`"
+                ));
+            }
+            layout = Some(LayoutPolicy::OnlyByRef);
+        }
+        let Some(layout) = layout else {
+            file.error(format!(
+                "No layout policy found for type `{name}`. \
+Use one of `layout`, `heap_allocated` or `only_by_ref`."
+            ));
+            return file;
+        };
+        if layout == LayoutPolicy::HeapAllocated && is_copy {
+            file.error(format!("Heap allocated type `{name}` can't be `Copy`"));
+        }
+        if let Some(ty) = ty {
+            file.file.types.push(ZngurType {
+                ty,
+                layout,
+                wellknown_traits,
+                methods,
+                constructors,
+                fields,
+                cpp_value,
+                cpp_ref,
+                doc,
+                span: None,
+            });
+        }
+        file
+    }
+
+    fn layout_policy(mut self, layout: LayoutPolicy) -> Self {
+        if self.layout.is_some() {
+            let message = format!("Duplicate layout policy found for type `{}`", self.name);
+            self.error(message);
+        }
+        self.layout = Some(layout);
+        self
+    }
+
+    fn error(&mut self, message: String) {
+        self.file.error(message);
+    }
+}
@@ -0,0 +1,97 @@
+use zngur_def::CfgPredicate;
+
+/// The configuration that `#cfg(...)` attributes of zng files are evaluated against.
+///
+/// A configuration is a set of options, each of which is either a name like `unix` or a name and
+/// value pair like `target_os = "linux"`. A name can have multiple values, like `feature`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Cfg {
+    options: Vec<(String, Option<String>)>,
+}
+
+impl Cfg {
+    /// An empty configuration, in which only `not(...)` predicates hold.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The configuration of the target that this crate is compiled for. Used when zng files are
+    /// processed outside of build scripts. `debug_assertions` is not included, since it describes
+    /// how zngur itself was built rather than the crate being bound.
+    pub fn host() -> Self {
+        let mut cfg = Cfg::new();
+        cfg.insert("target_os", Some(std::env::consts::OS));
+        cfg.insert("target_arch", Some(std::env::consts::ARCH));
+        if !std::env::consts::FAMILY.is_empty() {
+            cfg.insert("target_family", Some(std::env::consts::FAMILY));
+            cfg.insert(std::env::consts::FAMILY, None);
+        }
+        cfg.insert("target_pointer_width", Some(&usize::BITS.to_string()));
+        let endian = if cfg!(target_endian = "little") {
+            "little"
+        } else {
+            "big"
+        };
+        cfg.insert("target_endian", Some(endian));
+        cfg
+    }
+
+    /// The configuration of the crate being built, read from the `CARGO_CFG_*` and `CARGO_FEATURE_*`
+    /// environment variables that cargo sets for build scripts.
+    pub fn from_cargo_env() -> Self {
+        let mut cfg = Cfg::new();
+        for (key, value) in std::env::vars() {
+            if let Some(feature) = key.strip_prefix("CARGO_FEATURE_") {
+                cfg.insert("feature", Some(&normalize_feature(feature)));
+            } else if let Some(name) = key.strip_prefix("CARGO_CFG_") {
+                let name = name.to_lowercase();
+                if value.is_empty() {
+                    cfg.insert(&name, None);
+                }
+                for value in value.split(',').filter(|x| !x.is_empty()) {
+                    cfg.insert(&name, Some(value));
+                }
+            }
+        }
+        cfg
+    }
+
+    /// Adds `name` or `name = "value"` to the configuration.
+    pub fn insert(&mut self, name: &str, value: Option<&str>) {
+        let value = match name {
+            "feature" => value.map(normalize_feature),
+            _ => value.map(str::to_owned),
+        };
+        let option = (name.to_owned(), value);
+        if !self.options.contains(&option) {
+            self.options.push(option);
+        }
+    }
+
+    /// Removes `name` and all of its values from the configuration.
+    pub fn remove(&mut self, name: &str) {
+        self.options.retain(|x| x.0 != name);
+    }
+
+    /// Whether `predicate` holds in this configuration.
+    pub fn eval(&self, predicate: &CfgPredicate) -> bool {
+        match predicate {
+            CfgPredicate::Option { name, value } => {
+                let value = match name.as_str() {
+                    "feature" => value.as_deref().map(normalize_feature),
+                    _ => value.clone(),
+                };
+                self.options.iter().any(|x| x.0 == *name && x.1 == value)
+            }
+            CfgPredicate::Any(v) => v.iter().any(|x| self.eval(x)),
+            CfgPredicate::All(v) => v.iter().all(|x| self.eval(x)),
+            CfgPredicate::Not(p) => !self.eval(p),
+        }
+    }
+}
+
+/// Cargo reports features in uppercase with `-` replaced by `_`, so features are compared in
+/// lowercase with `_` instead of `-`.
+fn normalize_feature(feature: &str) -> String {
+    feature.to_lowercase().replace('-', "_")
+}
@@ -0,0 +1,226 @@
+use std::{collections::HashMap, fmt::Display, io};
+
+use ariadne::{Color, Config, FnCache, Report, ReportKind, Source};
+use chumsky::error::Rich;
+use zngur_def::ZngurSpan;
+
+use crate::{Lint, Span};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LabelKind {
+    /// The location that caused the diagnostic.
+    Primary,
+    /// A related location that helps explaining the diagnostic.
+    Secondary,
+    /// The construct that the parser was in the middle of when the error happened.
+    Context,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Label {
+    pub file: String,
+    pub span: Span,
+    pub message: String,
+    pub kind: LabelKind,
+}
+
+/// A problem found in a zng file, with enough location information to be rendered by the caller.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub labels: Vec<Label>,
+    /// The lint that produced this warning, if it can be silenced with `#allow(...)`.
+    pub lint: Option<Lint>,
+}
+
+impl Diagnostic {
+    pub fn error(message: impl ToString) -> Self {
+        Diagnostic {
+            severity: Severity::Error,
+            message: message.to_string(),
+            labels: vec![],
+            lint: None,
+        }
+    }
+
+    pub fn warning(message: impl ToString) -> Self {
+        Diagnostic {
+            severity: Severity::Warning,
+            message: message.to_string(),
+            labels: vec![],
+            lint: None,
+        }
+    }
+
+    /// A warning produced by `lint`.
+    pub fn lint(lint: Lint, message: impl ToString) -> Self {
+        Diagnostic {
+            lint: Some(lint),
+            ..Diagnostic::warning(message)
+        }
+    }
+
+    pub fn with_label(
+        mut self,
+        kind: LabelKind,
+        file: &str,
+        span: Span,
+        message: impl ToString,
+    ) -> Self {
+        self.labels.push(Label {
+            file: file.to_owned(),
+            span,
+            message: message.to_string(),
+            kind,
+        });
+        self
+    }
+
+    /// Like [`Diagnostic::with_label`], for a location stored in `zngur-def` items. Nothing is
+    /// added if the location is unknown.
+    pub fn with_zngur_label(
+        self,
+        kind: LabelKind,
+        span: Option<&ZngurSpan>,
+        message: impl ToString,
+    ) -> Self {
+        match span {
+            Some(span) => self.with_label(kind, &span.file, (span.start..span.end).into(), message),
+            None => self,
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    pub(crate) fn from_rich<T: Display>(file: &str, e: &Rich<'_, T, Span>) -> Self {
+        let mut d = Diagnostic::error(e).with_label(
+            LabelKind::Primary,
+            file,
+            *e.span(),
+            e.reason().to_string(),
+        );
+        for (label, span) in e.contexts() {
+            d = d.with_label(
+                LabelKind::Context,
+                file,
+                *span,
+                format!("while parsing this {label}"),
+            );
+        }
+        d
+    }
+
+    /// The location this diagnostic is reported at, which is the first primary label if any.
+    pub fn location(&self) -> Option<&Label> {
+        self.labels
+            .iter()
+            .find(|l| l.kind == LabelKind::Primary)
+            .or(self.labels.first())
+    }
+
+    fn to_ariadne(&self, color: bool) -> Report<'_, (String, std::ops::Range<usize>)> {
+        let (file, offset) = match self.location() {
+            Some(l) => (l.file.clone(), l.span.start),
+            None => (String::new(), 0),
+        };
+        let kind = match self.severity {
+            Severity::Error => ReportKind::Error,
+            Severity::Warning => ReportKind::Warning,
+        };
+        let mut report = Report::build(kind, file, offset)
+            .with_config(Config::default().with_color(color))
+            .with_message(&self.message)
+            .with_labels(self.labels.iter().map(|l| {
+                ariadne::Label::new((l.file.clone(), l.span.into_range()))
+                    .with_message(&l.message)
+                    .with_color(match l.kind {
+                        LabelKind::Primary => Color::Red,
+                        LabelKind::Secondary => Color::Blue,
+                        LabelKind::Context => Color::Yellow,
+                    })
+            }));
+        if let Some(lint) = self.lint {
+            report = report.with_note(format!("use `#allow({lint});` to silence this warning"));
+        }
+        report.finish()
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.severity, self.message)
+    }
+}
+
+/// Renders diagnostics in the human readable format of `ariadne`.
+///
+/// Source of files that are not provided via `with_source` is read from the disk on demand.
+pub struct AriadneRenderer {
+    sources: HashMap<String, Source>,
+    color: bool,
+}
+
+impl Default for AriadneRenderer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AriadneRenderer {
+    pub fn new() -> Self {
+        AriadneRenderer {
+            sources: HashMap::new(),
+            color: true,
+        }
+    }
+
+    pub fn with_source(mut self, file: &str, text: &str) -> Self {
+        self.sources.insert(file.to_owned(), Source::from(text));
+        self
+    }
+
+    pub fn with_color(mut self, color: bool) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn write<'a>(
+        &self,
+        diagnostics: impl IntoIterator<Item = &'a Diagnostic>,
+        mut w: impl io::Write,
+    ) -> io::Result<()> {
+        let mut cache = FnCache::new(|file: &String| {
+            std::fs::read_to_string(file).map_err(|e| Box::new(e) as _)
+        })
+        .with_sources(self.sources.clone());
+        for d in diagnostics {
+            d.to_ariadne(self.color).write(&mut cache, &mut w)?;
+        }
+        Ok(())
+    }
+
+    pub fn eprint<'a>(
+        &self,
+        diagnostics: impl IntoIterator<Item = &'a Diagnostic>,
+    ) -> io::Result<()> {
+        self.write(diagnostics, io::stderr())
+    }
+}
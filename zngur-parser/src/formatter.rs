@@ -0,0 +1,445 @@
+use chumsky::Parser;
+
+use crate::{Diagnostic, Span, Token, ZngParser, lexer_with_comments};
+
+/// Lines longer than this are broken before the return type.
+const MAX_WIDTH: usize = 100;
+const INDENT: &str = "    ";
+
+/// Formats a zng file in the canonical style, keeping the comments.
+///
+/// Formatting only changes whitespace and the order of layout and wellknown traits items inside
+/// type blocks, and the result is checked to parse into the same [`ZngurFile`](zngur_def::ZngurFile)
+/// as the input. Files that don't parse are not formatted, and their diagnostics are returned
+/// instead.
+pub fn format_zng(filename: &str, text: &str) -> Result<String, Vec<Diagnostic>> {
+    let original = ZngParser::new().parse(filename, text)?;
+    let tokens = lexer_with_comments()
+        .parse(text)
+        .into_output()
+        .expect("text is already lexed successfully");
+    let mut parser = StmtParser {
+        text,
+        tokens,
+        pos: 0,
+    };
+    let block = parser.block(false);
+    let mut out = String::new();
+    emit_block(&mut out, &block, 0);
+    match ZngParser::new().parse(filename, &out) {
+        Ok(formatted) if formatted.eq_ignoring_spans(&original) => Ok(out),
+        _ => Err(vec![Diagnostic::error(format!(
+            "Formatting `{filename}` changes its meaning. This is a bug in the zng formatter."
+        ))]),
+    }
+}
+
+struct Comment<'a> {
+    blank_before: bool,
+    text: &'a str,
+}
+
+/// An item ending with `;` or with a block of items, like `type X { ... }`.
+struct Stmt<'a> {
+    blank_before: bool,
+    comments: Vec<Comment<'a>>,
+    /// Whether there is a blank line between the comments and the item itself.
+    blank_after_comments: bool,
+    /// `#cfg(...)` attributes, each emitted on its own line.
+    attributes: Vec<Vec<Token<'a>>>,
+    head: Vec<Token<'a>>,
+    body: Option<Block<'a>>,
+    trailing: Option<&'a str>,
+}
+
+struct Block<'a> {
+    /// Comment on the same line as the opening `{`.
+    open_comment: Option<&'a str>,
+    stmts: Vec<Stmt<'a>>,
+    /// Comments after the last item of the block.
+    dangling: Vec<Comment<'a>>,
+}
+
+struct StmtParser<'a> {
+    text: &'a str,
+    tokens: Vec<(Token<'a>, Span)>,
+    pos: usize,
+}
+
+impl<'a> StmtParser<'a> {
+    fn peek(&self) -> Option<Token<'a>> {
+        self.tokens.get(self.pos).map(|x| x.0.clone())
+    }
+
+    fn bump(&mut self) -> Token<'a> {
+        self.pos += 1;
+        self.tokens[self.pos - 1].0.clone()
+    }
+
+    /// Number of line breaks between the previous token and the next one.
+    fn newlines_before(&self) -> usize {
+        let start = match self.pos {
+            0 => return 0,
+            pos => self.tokens[pos - 1].1.end,
+        };
+        let end = self
+            .tokens
+            .get(self.pos)
+            .map_or(self.text.len(), |x| x.1.start);
+        self.text[start..end].matches('\n').count()
+    }
+
+    /// Consumes a comment that is on the same line as the previous token.
+    fn trailing_comment(&mut self) -> Option<&'a str> {
+        match self.peek() {
+            Some(Token::Comment(c)) if self.newlines_before() == 0 => {
+                self.pos += 1;
+                Some(c)
+            }
+            _ => None,
+        }
+    }
+
+    /// Consumes `#name(...)` up to the matching `)`.
+    fn balanced_attribute(&mut self) -> Vec<Token<'a>> {
+        let mut tokens = vec![self.bump(), self.bump()];
+        let mut depth = 0usize;
+        while let Some(token) = self.peek() {
+            tokens.push(self.bump());
+            match token {
+                Token::ParenOpen => depth += 1,
+                Token::ParenClose => depth = depth.saturating_sub(1),
+                _ => (),
+            }
+            if depth == 0 {
+                break;
+            }
+        }
+        tokens
+    }
+
+    fn block(&mut self, in_braces: bool) -> Block<'a> {
+        let open_comment = if in_braces {
+            self.trailing_comment()
+        } else {
+            None
+        };
+        let mut stmts = vec![];
+        loop {
+            let mut comments = vec![];
+            let blank_before = self.newlines_before() > 1;
+            while let Some(Token::Comment(text)) = self.peek() {
+                comments.push(Comment {
+                    blank_before: self.newlines_before() > 1,
+                    text,
+                });
+                self.pos += 1;
+            }
+            match self.peek() {
+                None | Some(Token::BraceClose) => {
+                    if in_braces {
+                        self.bump();
+                    }
+                    return Block {
+                        open_comment,
+                        stmts,
+                        dangling: comments,
+                    };
+                }
+                Some(_) => stmts.push(self.stmt(blank_before, comments)),
+            }
+        }
+    }
+
+    fn stmt(&mut self, blank_before: bool, comments: Vec<Comment<'a>>) -> Stmt<'a> {
+        let blank_after_comments = !comments.is_empty() && self.newlines_before() > 1;
+        let mut attributes = vec![];
+        while self.peek() == Some(Token::Sharp)
+            && matches!(
+                self.tokens.get(self.pos + 1),
+                Some((Token::Ident("cfg"), _))
+            )
+        {
+            attributes.push(self.balanced_attribute());
+        }
+        let has_body = matches!(
+            self.peek(),
+            Some(Token::KwType | Token::KwTrait | Token::KwMod | Token::KwExtern | Token::KwImpl)
+        ) || matches!(self.tokens.get(self.pos + 1), Some((Token::KwFor, _)));
+        let mut head = vec![];
+        let mut depth = 0usize;
+        let mut body = None;
+        // File attributes are the only items without a trailing `;`.
+        let attribute_len = match self.tokens.get(self.pos + 1) {
+            Some((Token::Ident("convert_panic_to_exception"), _)) => 2,
+            Some((Token::Ident("cpp_additional_includes"), _)) => 3,
+            _ => 0,
+        };
+        if self.peek() == Some(Token::Sharp) && attribute_len != 0 {
+            for _ in 0..attribute_len {
+                head.push(self.bump());
+            }
+        }
+        while let Some(token) = self
+            .peek()
+            .filter(|_| head.is_empty() || attribute_len == 0)
+        {
+            match token {
+                Token::BraceOpen if has_body && depth == 0 => {
+                    self.bump();
+                    body = Some(self.block(true));
+                    break;
+                }
+                // The `;` of array types like `[u8; 32]` is inside brackets.
+                Token::Semicolon if depth == 0 => {
+                    head.push(self.bump());
+                    break;
+                }
+                Token::ParenOpen | Token::BracketOpen | Token::AngleOpen | Token::BraceOpen => {
+                    depth += 1;
+                }
+                Token::ParenClose | Token::BracketClose | Token::AngleClose | Token::BraceClose => {
+                    depth = depth.saturating_sub(1);
+                }
+                _ => (),
+            }
+            head.push(self.bump());
+        }
+        Stmt {
+            blank_before,
+            comments,
+            blank_after_comments,
+            attributes,
+            head,
+            body,
+            trailing: self.trailing_comment(),
+        }
+    }
+}
+
+/// The position of an item inside a type block. Layout policy comes first, then wellknown traits,
+/// then everything else in the original order.
+fn type_item_rank(stmt: &Stmt<'_>) -> usize {
+    match stmt.head.as_slice() {
+        [
+            Token::Sharp,
+            Token::Ident("layout" | "layout_conservative" | "heap_allocated" | "only_by_ref"),
+            ..,
+        ] => 0,
+        [Token::Ident("wellknown_traits"), ..] => 1,
+        _ => 2,
+    }
+}
+
+/// Emits comments on their own lines. The blank line before the first one is left to the caller.
+fn emit_comments(out: &mut String, comments: &[Comment<'_>], indent: &str) {
+    for (i, c) in comments.iter().enumerate() {
+        if c.blank_before && i != 0 {
+            out.push('\n');
+        }
+        out.push_str(indent);
+        out.push_str(c.text);
+        out.push('\n');
+    }
+}
+
+fn emit_block(out: &mut String, block: &Block<'_>, level: usize) {
+    let items = block.stmts.iter().map(|x| (x, x.blank_before)).collect();
+    emit_stmts(out, items, &block.dangling, level);
+}
+
+/// Emits the items of a type block, grouping the layout policy and wellknown traits at the top.
+fn emit_type_block(out: &mut String, block: &Block<'_>, level: usize) {
+    let mut items = block.stmts.iter().collect::<Vec<_>>();
+    items.sort_by_key(|x| type_item_rank(x));
+    let mut after_header = false;
+    let items = items
+        .into_iter()
+        .map(|x| {
+            let blank_before = match type_item_rank(x) {
+                0 | 1 => {
+                    after_header = true;
+                    false
+                }
+                _ => std::mem::take(&mut after_header) || x.blank_before,
+            };
+            (x, blank_before)
+        })
+        .collect();
+    emit_stmts(out, items, &block.dangling, level);
+}
+
+fn emit_stmts(
+    out: &mut String,
+    items: Vec<(&Stmt<'_>, bool)>,
+    dangling: &[Comment<'_>],
+    level: usize,
+) {
+    let indent = INDENT.repeat(level);
+    let mut first = true;
+    for (stmt, blank_before) in items {
+        if blank_before && !first {
+            out.push('\n');
+        }
+        first = false;
+        emit_comments(out, &stmt.comments, &indent);
+        if stmt.blank_after_comments {
+            out.push('\n');
+        }
+        for attribute in &stmt.attributes {
+            out.push_str(&indent);
+            out.push_str(&render_tokens(attribute, &indent));
+            out.push('\n');
+        }
+        out.push_str(&indent);
+        out.push_str(&render_head(&stmt.head, &indent));
+        if let Some(body) = &stmt.body {
+            if body.stmts.is_empty() && body.dangling.is_empty() && body.open_comment.is_none() {
+                out.push_str(" {}");
+            } else {
+                out.push_str(" {");
+                if let Some(c) = body.open_comment {
+                    out.push(' ');
+                    out.push_str(c);
+                }
+                out.push('\n');
+                if stmt.head.first() == Some(&Token::KwType) {
+                    emit_type_block(out, body, level + 1);
+                } else {
+                    emit_block(out, body, level + 1);
+                }
+                out.push_str(&indent);
+                out.push('}');
+            }
+        }
+        if let Some(c) = stmt.trailing {
+            out.push(' ');
+            out.push_str(c);
+        }
+        out.push('\n');
+    }
+    if dangling.first().is_some_and(|x| x.blank_before) && !first {
+        out.push('\n');
+    }
+    emit_comments(out, dangling, &indent);
+}
+
+/// Renders the tokens of an item in a single line if it fits. Otherwise, the return type and the
+/// `use` and `deref` parts of function signatures are moved to separate lines as needed, and the
+/// arguments are put one per line if the signature itself is still too long.
+fn render_head(head: &[Token<'_>], indent: &str) -> String {
+    let fits = |indent: &str, line: &str| indent.len() + line.len() <= MAX_WIDTH;
+    let line = render_tokens(head, indent);
+    if fits(indent, &line) {
+        return line;
+    }
+    let continuation = format!("{indent}{INDENT}");
+    let mut segments = split_top_level(head, |t| {
+        matches!(t, Token::Arrow | Token::KwUse | Token::Ident("deref"))
+    })
+    .into_iter();
+    let signature = segments.next().unwrap_or_default();
+    let mut lines = vec![];
+    let mut current = render_tokens(signature, indent);
+    let mut current_indent = indent;
+    if !fits(indent, &current) {
+        let open = split_top_level(signature, |t| *t == Token::ParenOpen)[0].len();
+        let close = signature.len() - 1;
+        if open + 1 < close && signature[close] == Token::ParenClose {
+            lines.push(render_tokens(&signature[..=open], indent));
+            let args = split_top_level(&signature[open + 1..close], |t| *t == Token::Comma);
+            let count = args.len();
+            for (i, arg) in args.into_iter().enumerate() {
+                // Every argument except the first one starts with its separating comma.
+                let arg = if i == 0 { arg } else { &arg[1..] };
+                let comma = if i + 1 == count { "" } else { "," };
+                lines.push(format!(
+                    "{continuation}{}{comma}",
+                    render_tokens(arg, &continuation)
+                ));
+            }
+            current = format!("{indent})");
+            current_indent = "";
+        }
+    }
+    for segment in segments {
+        let segment = render_tokens(segment, &continuation);
+        if fits(current_indent, &format!("{current} {segment}")) {
+            current = format!("{current} {segment}");
+        } else {
+            lines.push(current);
+            current = format!("{continuation}{segment}");
+            current_indent = "";
+        }
+    }
+    lines.push(current);
+    lines.join("\n")
+}
+
+/// Splits the tokens before every token that matches `is_split` and is not nested inside any
+/// kind of brackets.
+fn split_top_level<'a, 'b>(
+    tokens: &'b [Token<'a>],
+    is_split: impl Fn(&Token<'a>) -> bool,
+) -> Vec<&'b [Token<'a>]> {
+    let mut result = vec![];
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (i, t) in tokens.iter().enumerate() {
+        if depth == 0 && i != 0 && is_split(t) {
+            result.push(&tokens[start..i]);
+            start = i;
+        }
+        match t {
+            Token::ParenOpen | Token::BracketOpen | Token::AngleOpen | Token::BraceOpen => {
+                depth += 1
+            }
+            Token::ParenClose | Token::BracketClose | Token::AngleClose | Token::BraceClose => {
+                depth = depth.saturating_sub(1)
+            }
+            _ => (),
+        }
+    }
+    result.push(&tokens[start..]);
+    result
+}
+
+/// Whether a space should be placed between two consecutive tokens of an item.
+fn needs_space(prev2: Option<&Token<'_>>, prev: &Token<'_>, next: &Token<'_>) -> bool {
+    use Token::*;
+    match (prev, next) {
+        (BraceOpen, BraceClose) => false,
+        (BraceOpen, _) | (_, BraceClose) => true,
+        (ParenOpen | BracketOpen | AngleOpen | And | Star | ColonColon | Sharp | Question, _) => {
+            false
+        }
+        (_, ParenClose | BracketClose | AngleClose | Comma | Semicolon | Colon | AngleOpen) => {
+            false
+        }
+        (Ident(_) | AngleClose | KwCrate, ColonColon) => false,
+        // `field name (offset = 0, type = T)`
+        (Ident(_), ParenOpen) => prev2 == Some(&Ident("field")),
+        (AngleClose, ParenOpen) => false,
+        // `fn(u32) -> u32` function pointer types
+        (KwFn, ParenOpen) => false,
+        _ => true,
+    }
+}
+
+fn render_tokens(tokens: &[Token<'_>], indent: &str) -> String {
+    let mut line = String::new();
+    for (i, t) in tokens.iter().enumerate() {
+        if i != 0 {
+            let prev = &tokens[i - 1];
+            if let Token::Comment(_) = prev {
+                line.push('\n');
+                line.push_str(indent);
+                line.push_str(INDENT);
+            } else if needs_space(i.checked_sub(2).map(|x| &tokens[x]), prev, t) {
+                line.push(' ');
+            }
+        }
+        line.push_str(&t.to_string());
+    }
+    line
+}
@@ -0,0 +1,84 @@
+use zngur_def::{RustType, ZngurSpan};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    Mod,
+    Type,
+    Trait,
+}
+
+/// A `mod`, `type` or `trait` block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    /// The path of the module, or the type or trait, as written in the file.
+    pub name: String,
+    pub kind: SymbolKind,
+    /// The whole block, including the keyword.
+    pub span: ZngurSpan,
+    /// The path of the module, or the type or trait.
+    pub name_span: ZngurSpan,
+    /// Blocks inside this one.
+    pub children: Vec<Symbol>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReferenceTarget {
+    /// A type, with aliases and relative paths already resolved.
+    Type(RustType),
+    /// The `use ... as ...` item that the path is expanded with.
+    Alias(ZngurSpan),
+}
+
+/// A path in a zng file and what it refers to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reference {
+    pub span: ZngurSpan,
+    pub target: ReferenceTarget,
+}
+
+/// Locations of the items of zng files and what their paths refer to, for editor tooling.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SymbolIndex {
+    /// Top level blocks of every parsed file.
+    pub symbols: Vec<Symbol>,
+    pub references: Vec<Reference>,
+    /// Every `type` block, including the ones with errors in them.
+    pub types: Vec<(RustType, ZngurSpan)>,
+}
+
+impl SymbolIndex {
+    /// References that contain the byte `offset` of `file`.
+    pub fn references_at<'a>(
+        &'a self,
+        file: &'a str,
+        offset: usize,
+    ) -> impl Iterator<Item = &'a Reference> + 'a {
+        self.references
+            .iter()
+            .filter(move |r| r.span.file == file && r.span.start <= offset && offset <= r.span.end)
+    }
+
+    /// The `type` block of `ty`. If there is none, a `type` block of the same path with different
+    /// generic arguments is returned.
+    pub fn type_declaration(&self, ty: &RustType) -> Option<&ZngurSpan> {
+        let path = |ty: &RustType| match ty {
+            RustType::Adt(pg) => Some(pg.path.clone()),
+            _ => None,
+        };
+        self.types
+            .iter()
+            .find(|(x, _)| x == ty)
+            .or_else(|| {
+                let p = path(ty)?;
+                self.types
+                    .iter()
+                    .find(|(x, _)| path(x).as_ref() == Some(&p))
+            })
+            .map(|(_, span)| span)
+    }
+
+    /// Top level symbols of `file`.
+    pub fn file_symbols<'a>(&'a self, file: &'a str) -> impl Iterator<Item = &'a Symbol> + 'a {
+        self.symbols.iter().filter(move |x| x.span.file == file)
+    }
+}
@@ -1,18 +1,35 @@
-use std::{fmt::Display, process::exit, sync::Mutex};
+use std::{
+    cell::{Cell, OnceCell, RefCell},
+    fmt::Display,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
 
-use ariadne::{Color, Label, Report, ReportKind, sources};
 use chumsky::prelude::*;
 use itertools::{Either, Itertools};
 
 use zngur_def::{
-    LayoutPolicy, Mutability, PrimitiveRustType, RustPathAndGenerics, RustTrait, RustType,
-    ZngurConstructor, ZngurExternCppFn, ZngurExternCppImpl, ZngurField, ZngurFile, ZngurFn,
-    ZngurMethod, ZngurMethodDetails, ZngurMethodReceiver, ZngurTrait, ZngurType,
-    ZngurWellknownTrait,
+    CfgPredicate, LayoutPolicy, Mutability, PrimitiveRustType, RustPathAndGenerics, RustTrait,
+    RustType, ZngurConstructor, ZngurExternCppFn, ZngurExternCppImpl, ZngurField, ZngurFile,
+    ZngurFn, ZngurMethod, ZngurMethodDetails, ZngurMethodReceiver, ZngurOperator,
+    ZngurOperatorTrait, ZngurSpan, ZngurTrait, ZngurType, ZngurWellknownTrait,
 };
 
+pub use builder::{ZngurFileBuilder, ZngurFileExt, ZngurTypeBuilder};
+pub use cfg::Cfg;
+pub use diagnostic::{AriadneRenderer, Diagnostic, Label, LabelKind, Severity};
+pub use formatter::format_zng;
+pub use index::{Reference, ReferenceTarget, Symbol, SymbolIndex, SymbolKind};
+pub use lint::{Lint, lint};
+
 pub type Span = SimpleSpan<usize>;
 
+mod builder;
+mod cfg;
+mod diagnostic;
+mod formatter;
+mod index;
+mod lint;
 #[cfg(test)]
 mod tests;
 
@@ -57,6 +74,7 @@ struct ParsedPath<'a> {
 }
 
 impl ParsedPath<'_> {
+    #[allow(clippy::wrong_self_convention)]
     fn to_zngur(self, base: &[String]) -> Vec<String> {
         match self.start {
             ParsedPathStart::Absolute => self.segments.into_iter().map(|x| x.to_owned()).collect(),
@@ -90,11 +108,21 @@ pub struct ParsedAlias<'a> {
     name: &'a str,
     path: ParsedPath<'a>,
     span: Span,
+    state: Rc<AliasState>,
+}
+
+/// State of an alias, shared between its clones that are visible in nested modules and imports.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct AliasState {
+    used: Cell<bool>,
+    /// Location of the alias, which is known once its file is being processed.
+    definition: OnceCell<ZngurSpan>,
 }
 
 impl ParsedAlias<'_> {
     fn expand(&self, path: &ParsedPath<'_>, base: &[String]) -> Option<Vec<String>> {
         if path.matches_alias(self) {
+            self.state.used.set(true);
             match self.path.start {
                 ParsedPathStart::Absolute => Some(
                     self.path
@@ -134,18 +162,36 @@ enum ParsedItem<'a> {
     Mod {
         path: ParsedPath<'a>,
         items: Vec<ParsedItem<'a>>,
+        span: Span,
     },
     Type {
         ty: Spanned<ParsedRustType<'a>>,
         items: Vec<Spanned<ParsedTypeItem<'a>>>,
+        span: Span,
     },
     Trait {
-        tr: ParsedRustTrait<'a>,
+        tr: Spanned<ParsedRustTrait<'a>>,
         methods: Vec<ParsedMethod<'a>>,
+        span: Span,
     },
     Fn(ParsedMethod<'a>),
     ExternCpp(Vec<ParsedExternCppItem<'a>>),
     Alias(ParsedAlias<'a>),
+    Import(Spanned<&'a str>),
+    Allow(Vec<Spanned<&'a str>>),
+    For {
+        params: Vec<Spanned<&'a str>>,
+        rows: Vec<Spanned<Vec<ParsedTemplateArg<'a>>>>,
+        items: Vec<ParsedItem<'a>>,
+        span: Span,
+    },
+    /// An item with a `#cfg(...)` attribute.
+    Cfg {
+        predicate: CfgPredicate,
+        item: Box<ParsedItem<'a>>,
+    },
+    /// An item with a syntax error, which is already reported.
+    Error,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -156,17 +202,50 @@ enum ProcessedItem<'a> {
         path: ParsedPath<'a>,
         items: Vec<ProcessedItem<'a>>,
         aliases: Vec<ParsedAlias<'a>>,
+        span: Span,
     },
     Type {
         ty: Spanned<ParsedRustType<'a>>,
         items: Vec<Spanned<ParsedTypeItem<'a>>>,
+        span: Span,
     },
     Trait {
-        tr: ParsedRustTrait<'a>,
+        tr: Spanned<ParsedRustTrait<'a>>,
         methods: Vec<ParsedMethod<'a>>,
+        span: Span,
     },
     Fn(ParsedMethod<'a>),
     ExternCpp(Vec<ParsedExternCppItem<'a>>),
+    Import(Spanned<&'a str>),
+    Allow(Vec<Spanned<&'a str>>),
+    For {
+        params: Vec<Spanned<&'a str>>,
+        rows: Vec<Spanned<Vec<ParsedTemplateArg<'a>>>>,
+        items: Vec<ProcessedItem<'a>>,
+        aliases: Vec<ParsedAlias<'a>>,
+    },
+    Error,
+}
+
+/// A value that a `#for` parameter takes in one instantiation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ParsedTemplateArg<'a> {
+    Number(usize),
+    Type(ParsedRustType<'a>),
+}
+
+/// A value of a `#for` parameter, while the items of the `#for` block are being instantiated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum TemplateArg {
+    Number(usize),
+    Type(RustType),
+}
+
+/// A number, or a `#for` parameter that is bound to one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ParsedNumber<'a> {
+    Literal(usize),
+    Param(&'a str),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -174,7 +253,7 @@ enum ParsedExternCppItem<'a> {
     Function(ParsedMethod<'a>),
     Impl {
         tr: Option<ParsedRustTrait<'a>>,
-        ty: ParsedRustType<'a>,
+        ty: Spanned<ParsedRustType<'a>>,
         methods: Vec<ParsedMethod<'a>>,
     },
 }
@@ -188,15 +267,35 @@ enum ParsedConstructorArgs<'a> {
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 enum ParsedLayoutPolicy<'a> {
-    StackAllocated(Vec<(Spanned<&'a str>, usize)>),
+    StackAllocated(Vec<(Spanned<&'a str>, Spanned<ParsedNumber<'a>>)>),
+    Conservative(Vec<(Spanned<&'a str>, Spanned<ParsedNumber<'a>>)>),
+    Auto,
     HeapAllocated,
     OnlyByRef,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+enum ParsedWellknownTrait<'a> {
+    Simple(ZngurWellknownTrait),
+    Operator {
+        op: ZngurOperatorTrait,
+        rhs: Option<ParsedRustType<'a>>,
+        output: Option<ParsedRustType<'a>>,
+    },
+    IntoIterator {
+        by_ref: Option<Mutability>,
+        item: ParsedRustType<'a>,
+    },
+    Hash {
+        hasher: Option<ParsedRustType<'a>>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[allow(clippy::large_enum_variant)]
 enum ParsedTypeItem<'a> {
     Layout(Span, ParsedLayoutPolicy<'a>),
-    Traits(Vec<Spanned<ZngurWellknownTrait>>),
+    Traits(Vec<Spanned<ParsedWellknownTrait<'a>>>),
     Constructor {
         name: Option<&'a str>,
         args: ParsedConstructorArgs<'a>,
@@ -218,6 +317,10 @@ enum ParsedTypeItem<'a> {
     CppRef {
         cpp_type: &'a str,
     },
+    /// An item with a `#cfg(...)` attribute.
+    Cfg(CfgPredicate, Box<ParsedTypeItem<'a>>),
+    /// An item with a syntax error, which is already reported.
+    Error,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -227,49 +330,78 @@ struct ParsedMethod<'a> {
     generics: Vec<ParsedRustType<'a>>,
     inputs: Vec<ParsedRustType<'a>>,
     output: ParsedRustType<'a>,
+    span: Span,
 }
 
 impl ParsedMethod<'_> {
-    fn to_zngur(self, aliases: &[ParsedAlias<'_>], base: &[String]) -> ZngurMethod {
+    #[allow(clippy::wrong_self_convention)]
+    fn to_zngur(
+        self,
+        aliases: &[ParsedAlias<'_>],
+        base: &[String],
+        ctx: &ParseContext,
+    ) -> ZngurMethod {
         ZngurMethod {
             name: self.name.to_owned(),
             generics: self
                 .generics
                 .into_iter()
-                .map(|x| x.to_zngur(aliases, base))
+                .map(|x| x.to_zngur(aliases, base, ctx))
                 .collect(),
             receiver: self.receiver,
             inputs: self
                 .inputs
                 .into_iter()
-                .map(|x| x.to_zngur(aliases, base))
+                .map(|x| x.to_zngur(aliases, base, ctx))
                 .collect(),
-            output: self.output.to_zngur(aliases, base),
+            output: self.output.to_zngur(aliases, base, ctx),
+            doc: ctx.doc(self.span),
+            span: ctx.span(self.span),
         }
     }
 }
 
 impl ProcessedItem<'_> {
-    fn add_to_zngur_file(self, r: &mut ZngurFile, aliases: &[ParsedAlias], base: &[String]) {
+    fn add_to_zngur_file(
+        self,
+        r: &mut ZngurFile,
+        aliases: &[ParsedAlias],
+        base: &[String],
+        ctx: &mut ParseContext,
+    ) {
         match self {
             ProcessedItem::Mod {
                 path,
                 items,
                 aliases: mut mod_aliases,
+                span,
             } => {
+                let path_span = path.span;
                 let base = path.to_zngur(base);
+                ctx.declare_aliases(&mod_aliases);
+                let own_aliases = mod_aliases.len();
                 mod_aliases.extend_from_slice(aliases);
+                let outer_symbols = std::mem::take(&mut ctx.symbols);
                 for item in items {
-                    item.add_to_zngur_file(r, &mod_aliases, &base);
+                    item.add_to_zngur_file(r, &mod_aliases, &base, ctx);
                 }
+                ctx.check_unused_aliases(&mod_aliases[..own_aliases]);
+                let children = std::mem::replace(&mut ctx.symbols, outer_symbols);
+                ctx.add_symbol(SymbolKind::Mod, span, path_span, children);
             }
-            ProcessedItem::Type { ty, items } => {
+            ProcessedItem::Type { ty, items, span } => {
+                ctx.add_symbol(SymbolKind::Type, span, ty.span, vec![]);
                 if ty.inner == ParsedRustType::Tuple(vec![]) {
                     // We add unit type implicitly.
-                    create_and_emit_error(
+                    ctx.add_error(
                         "Unit type is declared implicitly. Remove this entirely.",
                         ty.span,
                     );
+                    return;
+                }
+                let rust_ty = ty.inner.to_zngur(aliases, base, ctx);
+                if let Some(span) = ctx.span(ty.span) {
+                    ctx.index.get_mut().types.push((rust_ty.clone(), span));
                 }
 
                 let mut methods = vec![];
@@ -278,52 +410,109 @@ impl ProcessedItem<'_> {
                 let mut wellknown_traits = vec![];
                 let mut layout = None;
                 let mut layout_span = None;
+                let mut layout_is_valid = true;
                 let mut cpp_value = None;
                 let mut cpp_ref = None;
+                let items = items
+                    .into_iter()
+                    .filter_map(|x| ctx.cfg_type_item(x))
+                    .collect_vec();
                 for item in items {
                     let item_span = item.span;
                     let item = item.inner;
                     match item {
                         ParsedTypeItem::Layout(span, p) => {
-                            layout = Some(match p {
-                                ParsedLayoutPolicy::StackAllocated(p) => {
+                            let conservative = matches!(p, ParsedLayoutPolicy::Conservative(_));
+                            layout = match p {
+                                ParsedLayoutPolicy::StackAllocated(p)
+                                | ParsedLayoutPolicy::Conservative(p) => {
                                     let mut size = None;
                                     let mut align = None;
+                                    let mut unresolved = false;
                                     for (key, value) in p {
+                                        let Some(value) = ctx.resolve_number(value) else {
+                                            unresolved = true;
+                                            continue;
+                                        };
                                         match key.inner {
                                             "size" => size = Some(value),
                                             "align" => align = Some(value),
-                                            _ => {
-                                                create_and_emit_error("Unknown property", key.span)
-                                            }
+                                            _ => ctx.add_error("Unknown property", key.span),
                                         }
                                     }
-                                    let Some(size) = size else {
-                                        create_and_emit_error(
+                                    if unresolved {
+                                        layout_is_valid = false;
+                                    } else if size.is_none() {
+                                        ctx.add_error(
                                             "Size is not declared for this type",
                                             ty.span,
                                         );
-                                    };
-                                    let Some(align) = align else {
-                                        create_and_emit_error(
+                                    }
+                                    if !unresolved && align.is_none() {
+                                        ctx.add_error(
                                             "Align is not declared for this type",
                                             ty.span,
                                         );
-                                    };
-                                    LayoutPolicy::StackAllocated { size, align }
+                                    }
+                                    match (size, align) {
+                                        (Some(size), Some(align)) if conservative => {
+                                            Some(LayoutPolicy::Conservative { size, align })
+                                        }
+                                        (Some(size), Some(align)) => {
+                                            Some(LayoutPolicy::StackAllocated { size, align })
+                                        }
+                                        _ => {
+                                            layout_is_valid = false;
+                                            None
+                                        }
+                                    }
                                 }
-                                ParsedLayoutPolicy::HeapAllocated => LayoutPolicy::HeapAllocated,
-                                ParsedLayoutPolicy::OnlyByRef => LayoutPolicy::OnlyByRef,
-                            });
+                                ParsedLayoutPolicy::Auto => Some(LayoutPolicy::Auto),
+                                ParsedLayoutPolicy::HeapAllocated => {
+                                    Some(LayoutPolicy::HeapAllocated)
+                                }
+                                ParsedLayoutPolicy::OnlyByRef => Some(LayoutPolicy::OnlyByRef),
+                            };
                             match layout_span {
                                 Some(_) => {
-                                    create_and_emit_error("Duplicate layout policy found", span);
+                                    ctx.add_error("Duplicate layout policy found", span);
                                 }
                                 None => layout_span = Some(span),
                             }
                         }
                         ParsedTypeItem::Traits(tr) => {
-                            wellknown_traits.extend(tr);
+                            for tr in tr {
+                                let inner = match tr.inner {
+                                    ParsedWellknownTrait::Simple(x) => x,
+                                    ParsedWellknownTrait::Operator { op, rhs, output } => {
+                                        let operator = ZngurOperator {
+                                            op,
+                                            rhs: rhs.map(|x| x.to_zngur(aliases, base, ctx)),
+                                            output: output.map(|x| x.to_zngur(aliases, base, ctx)),
+                                        };
+                                        if let Err(e) = operator.check() {
+                                            ctx.add_error(&e, tr.span);
+                                            continue;
+                                        }
+                                        ZngurWellknownTrait::Operator(operator)
+                                    }
+                                    ParsedWellknownTrait::IntoIterator { by_ref, item } => {
+                                        ZngurWellknownTrait::IntoIterator {
+                                            by_ref,
+                                            item: item.to_zngur(aliases, base, ctx),
+                                        }
+                                    }
+                                    ParsedWellknownTrait::Hash { hasher } => {
+                                        ZngurWellknownTrait::Hash {
+                                            hasher: hasher.map(|x| x.to_zngur(aliases, base, ctx)),
+                                        }
+                                    }
+                                };
+                                wellknown_traits.push(Spanned {
+                                    inner,
+                                    span: tr.span,
+                                });
+                            }
                         }
                         ParsedTypeItem::Constructor { name, args } => {
                             constructors.push(ZngurConstructor {
@@ -333,20 +522,28 @@ impl ProcessedItem<'_> {
                                     ParsedConstructorArgs::Tuple(t) => t
                                         .into_iter()
                                         .enumerate()
-                                        .map(|(i, t)| (i.to_string(), t.to_zngur(aliases, base)))
+                                        .map(|(i, t)| {
+                                            (i.to_string(), t.to_zngur(aliases, base, ctx))
+                                        })
                                         .collect(),
                                     ParsedConstructorArgs::Named(t) => t
                                         .into_iter()
-                                        .map(|(i, t)| (i.to_owned(), t.to_zngur(aliases, base)))
+                                        .map(|(i, t)| {
+                                            (i.to_owned(), t.to_zngur(aliases, base, ctx))
+                                        })
                                         .collect(),
                                 },
+                                doc: ctx.doc(item_span),
+                                span: ctx.span(item_span),
                             })
                         }
                         ParsedTypeItem::Field { name, ty, offset } => {
                             fields.push(ZngurField {
                                 name: name.to_owned(),
-                                ty: ty.to_zngur(aliases, base),
+                                ty: ty.to_zngur(aliases, base, ctx),
                                 offset,
+                                doc: ctx.doc(item_span),
+                                span: ctx.span(item_span),
                             });
                         }
                         ParsedTypeItem::Method {
@@ -355,17 +552,9 @@ impl ProcessedItem<'_> {
                             deref,
                         } => {
                             methods.push(ZngurMethodDetails {
-                                data: data.to_zngur(aliases, base),
-                                use_path: use_path.map(|x| {
-                                    aliases
-                                        .iter()
-                                        .filter_map(|alias| alias.expand(&x, base))
-                                        .collect::<Vec<_>>()
-                                        .first()
-                                        .cloned()
-                                        .unwrap_or_else(|| x.to_zngur(base))
-                                }),
-                                deref: deref.map(|x| x.to_zngur(aliases, base)),
+                                data: data.to_zngur(aliases, base, ctx),
+                                use_path: use_path.map(|x| ctx.resolve_path(x, aliases, base)),
+                                deref: deref.map(|x| x.to_zngur(aliases, base, ctx)),
                             });
                         }
                         ParsedTypeItem::CppValue { field, cpp_type } => {
@@ -374,7 +563,7 @@ impl ProcessedItem<'_> {
                         ParsedTypeItem::CppRef { cpp_type } => {
                             match layout_span {
                                 Some(span) => {
-                                    create_and_emit_error("Duplicate layout policy found", span);
+                                    ctx.add_error("Duplicate layout policy found", span);
                                 }
                                 None => {
                                     layout =
@@ -384,6 +573,14 @@ impl ProcessedItem<'_> {
                             }
                             cpp_ref = Some(cpp_type.to_owned());
                         }
+                        ParsedTypeItem::Cfg(..) => {
+                            unreachable!("removed by `ParseContext::cfg_type_item`")
+                        }
+                        ParsedTypeItem::Error => {
+                            // The broken item might be the layout policy, so we don't complain
+                            // about a missing one.
+                            layout_is_valid = false;
+                        }
                     }
                 }
                 let is_unsized = wellknown_traits
@@ -394,6 +591,16 @@ impl ProcessedItem<'_> {
                     .iter()
                     .find(|x| x.inner == ZngurWellknownTrait::Copy)
                     .cloned();
+                for tr in &wellknown_traits {
+                    for required in tr.inner.required_traits() {
+                        if !wellknown_traits.iter().any(|x| x.inner == *required) {
+                            ctx.add_error(
+                                &format!("`{}` requires `{required}`", tr.inner),
+                                tr.span,
+                            );
+                        }
+                    }
+                }
                 let mut wt = wellknown_traits
                     .into_iter()
                     .map(|x| x.inner)
@@ -403,40 +610,55 @@ impl ProcessedItem<'_> {
                 }
                 if let Some(is_unsized) = is_unsized {
                     if let Some(span) = layout_span {
-                        let file_name = LATEST_FILENAME.lock().unwrap().to_owned();
-                        emit_ariadne_error(
-                            Report::build(
-                                ReportKind::Error,
-                                file_name.clone(),
-                                span.start,
-                            )
-                            .with_message("Duplicate layout policy found for unsized type.")
-                            .with_label(
-                                Label::new((file_name.clone(), span.start..span.end))
-                                    .with_message(
-                                        "Unsized types have implicit layout policy, remove this.",
-                                    )
-                                    .with_color(Color::Red),
-                            )
-                            .with_label(
-                                Label::new((file_name.clone(), is_unsized.span.start..is_unsized.span.end))
-                                    .with_message("Type declared as unsized here.")
-                                    .with_color(Color::Blue),
-                            )
-                            .finish(),
-                        )
+                        ctx.add_diagnostic(
+                            Diagnostic::error("Duplicate layout policy found for unsized type.")
+                                .with_label(
+                                    LabelKind::Primary,
+                                    &ctx.file,
+                                    span,
+                                    "Unsized types have implicit layout policy, remove this.",
+                                )
+                                .with_label(
+                                    LabelKind::Secondary,
+                                    &ctx.file,
+                                    is_unsized.span,
+                                    "Type declared as unsized here.",
+                                ),
+                        );
                     }
                     layout = Some(LayoutPolicy::OnlyByRef);
                 }
                 let Some(layout) = layout else {
-                    create_and_emit_error(
-                        "No layout policy found for this type. \
+                    if layout_is_valid {
+                        ctx.add_error(
+                            "No layout policy found for this type. \
 Use one of `#layout(size = X, align = Y)`, `#heap_allocated` or `#only_by_ref`.",
-                        ty.span,
-                    );
+                            ty.span,
+                        );
+                    }
+                    return;
                 };
+                if let (LayoutPolicy::HeapAllocated, Some(is_copy), Some(span)) =
+                    (&layout, &is_copy, layout_span)
+                {
+                    ctx.add_diagnostic(
+                        Diagnostic::error("Heap allocated types can't be `Copy`.")
+                            .with_label(
+                                LabelKind::Primary,
+                                &ctx.file,
+                                is_copy.span,
+                                "Copying the type would copy the pointer to the heap allocation.",
+                            )
+                            .with_label(
+                                LabelKind::Secondary,
+                                &ctx.file,
+                                span,
+                                "Type declared as heap allocated here.",
+                            ),
+                    );
+                }
                 r.types.push(ZngurType {
-                    ty: ty.inner.to_zngur(aliases, base),
+                    ty: rust_ty,
                     layout,
                     methods,
                     wellknown_traits: wt,
@@ -444,23 +666,30 @@ Use one of `#layout(size = X, align = Y)`, `#heap_allocated` or `#only_by_ref`."
                     fields,
                     cpp_value,
                     cpp_ref,
+                    doc: ctx.doc(span),
+                    span: ctx.span(ty.span),
                 });
             }
-            ProcessedItem::Trait { tr, methods } => {
-                let tr = tr.to_zngur(aliases, base);
+            ProcessedItem::Trait { tr, methods, span } => {
+                ctx.add_symbol(SymbolKind::Trait, span, tr.span, vec![]);
+                let doc = ctx.doc(span);
+                let span = ctx.span(tr.span);
+                let tr = tr.inner.to_zngur(aliases, base, ctx);
                 r.traits.insert(
                     tr.clone(),
                     ZngurTrait {
                         tr,
                         methods: methods
                             .into_iter()
-                            .map(|m| m.to_zngur(aliases, base))
+                            .map(|m| m.to_zngur(aliases, base, ctx))
                             .collect(),
+                        doc,
+                        span,
                     },
                 );
             }
             ProcessedItem::Fn(f) => {
-                let method = f.to_zngur(aliases, base);
+                let method = f.to_zngur(aliases, base, ctx);
                 r.funcs.push(ZngurFn {
                     path: RustPathAndGenerics {
                         path: base.iter().chain(Some(&method.name)).cloned().collect(),
@@ -469,27 +698,31 @@ Use one of `#layout(size = X, align = Y)`, `#heap_allocated` or `#only_by_ref`."
                     },
                     inputs: method.inputs,
                     output: method.output,
+                    doc: method.doc,
+                    span: method.span,
                 })
             }
             ProcessedItem::ExternCpp(items) => {
                 for item in items {
                     match item {
                         ParsedExternCppItem::Function(method) => {
-                            let method = method.to_zngur(aliases, base);
+                            let method = method.to_zngur(aliases, base, ctx);
                             r.extern_cpp_funcs.push(ZngurExternCppFn {
                                 name: method.name.to_string(),
                                 inputs: method.inputs,
                                 output: method.output,
+                                span: method.span,
                             });
                         }
                         ParsedExternCppItem::Impl { tr, ty, methods } => {
                             r.extern_cpp_impls.push(ZngurExternCppImpl {
-                                tr: tr.map(|x| x.to_zngur(aliases, base)),
-                                ty: ty.to_zngur(aliases, base),
+                                tr: tr.map(|x| x.to_zngur(aliases, base, ctx)),
+                                ty: ty.inner.to_zngur(aliases, base, ctx),
                                 methods: methods
                                     .into_iter()
-                                    .map(|x| x.to_zngur(aliases, base))
+                                    .map(|x| x.to_zngur(aliases, base, ctx))
                                     .collect(),
+                                span: ctx.span(ty.span),
                             });
                         }
                     }
@@ -501,6 +734,89 @@ Use one of `#layout(size = X, align = Y)`, `#heap_allocated` or `#only_by_ref`."
             ProcessedItem::ConvertPanicToException => {
                 r.convert_panic_to_exception = true;
             }
+            ProcessedItem::Import(path) => {
+                ctx.import(path, r, aliases, base);
+            }
+            ProcessedItem::Allow(lints) => {
+                for lint in lints {
+                    if Lint::from_name(lint.inner).is_none() {
+                        ctx.add_diagnostic(
+                            Diagnostic::warning(format!("Unknown lint `{}`", lint.inner))
+                                .with_label(
+                                    LabelKind::Primary,
+                                    &ctx.file,
+                                    lint.span,
+                                    format!(
+                                        "Expected one of {}",
+                                        Lint::ALL.iter().map(|x| format!("`{x}`")).join(", ")
+                                    ),
+                                ),
+                        );
+                    }
+                    r.allowed_lints.push(lint.inner.to_owned());
+                }
+            }
+            ProcessedItem::For {
+                params,
+                rows,
+                items,
+                aliases: for_aliases,
+            } => {
+                ctx.declare_aliases(&for_aliases);
+                let own_aliases = for_aliases.len();
+                let mut for_aliases = for_aliases;
+                for_aliases.extend_from_slice(aliases);
+                for (i, row) in rows.into_iter().enumerate() {
+                    if row.inner.len() != params.len() {
+                        ctx.add_error(
+                            &format!(
+                                "Expected {} values for the parameters of `#for`, found {}",
+                                params.len(),
+                                row.inner.len()
+                            ),
+                            row.span,
+                        );
+                        continue;
+                    }
+                    let args = params
+                        .iter()
+                        .zip(row.inner)
+                        .map(|(param, arg)| {
+                            let arg = match arg {
+                                ParsedTemplateArg::Number(n) => TemplateArg::Number(n),
+                                ParsedTemplateArg::Type(ty) => {
+                                    TemplateArg::Type(ty.to_zngur(&for_aliases, base, ctx))
+                                }
+                            };
+                            (param.inner.to_owned(), arg)
+                        })
+                        .collect_vec();
+                    let outer_args = ctx.template_args.len();
+                    ctx.template_args.extend(args);
+                    let first_diagnostic = ctx.diagnostics.len();
+                    let outer_symbols = ctx.symbols.len();
+                    for item in items.clone() {
+                        item.add_to_zngur_file(r, &for_aliases, base, ctx);
+                    }
+                    // Items of every instantiation have the same location, so only the first one
+                    // shows up in the outline.
+                    if i != 0 {
+                        ctx.symbols.truncate(outer_symbols);
+                    }
+                    let file = ctx.file.clone();
+                    for d in &mut ctx.diagnostics[first_diagnostic..] {
+                        *d = d.clone().with_label(
+                            LabelKind::Secondary,
+                            &file,
+                            row.span,
+                            "In this instantiation of `#for`",
+                        );
+                    }
+                    ctx.template_args.truncate(outer_args);
+                }
+                ctx.check_unused_aliases(&for_aliases[..own_aliases]);
+            }
+            ProcessedItem::Error => {}
         }
     }
 }
@@ -512,27 +828,58 @@ enum ParsedRustType<'a> {
     Raw(Mutability, Box<ParsedRustType<'a>>),
     Boxed(Box<ParsedRustType<'a>>),
     Slice(Box<ParsedRustType<'a>>),
+    Array(Box<ParsedRustType<'a>>, usize),
+    FnPtr {
+        inputs: Vec<ParsedRustType<'a>>,
+        output: Box<ParsedRustType<'a>>,
+    },
     Dyn(ParsedRustTrait<'a>, Vec<&'a str>),
     Tuple(Vec<ParsedRustType<'a>>),
     Adt(ParsedRustPathAndGenerics<'a>),
 }
 
 impl ParsedRustType<'_> {
-    fn to_zngur(self, aliases: &[ParsedAlias<'_>], base: &[String]) -> RustType {
+    #[allow(clippy::wrong_self_convention)]
+    fn to_zngur(
+        self,
+        aliases: &[ParsedAlias<'_>],
+        base: &[String],
+        ctx: &ParseContext,
+    ) -> RustType {
         match self {
             ParsedRustType::Primitive(s) => RustType::Primitive(s),
-            ParsedRustType::Ref(m, s) => RustType::Ref(m, Box::new(s.to_zngur(aliases, base))),
-            ParsedRustType::Raw(m, s) => RustType::Raw(m, Box::new(s.to_zngur(aliases, base))),
-            ParsedRustType::Boxed(s) => RustType::Boxed(Box::new(s.to_zngur(aliases, base))),
-            ParsedRustType::Slice(s) => RustType::Slice(Box::new(s.to_zngur(aliases, base))),
+            ParsedRustType::Ref(m, s) => RustType::Ref(m, Box::new(s.to_zngur(aliases, base, ctx))),
+            ParsedRustType::Raw(m, s) => RustType::Raw(m, Box::new(s.to_zngur(aliases, base, ctx))),
+            ParsedRustType::Boxed(s) => RustType::Boxed(Box::new(s.to_zngur(aliases, base, ctx))),
+            ParsedRustType::Slice(s) => RustType::Slice(Box::new(s.to_zngur(aliases, base, ctx))),
+            ParsedRustType::Array(s, n) => {
+                RustType::Array(Box::new(s.to_zngur(aliases, base, ctx)), n)
+            }
+            ParsedRustType::FnPtr { inputs, output } => RustType::FnPtr {
+                inputs: inputs
+                    .into_iter()
+                    .map(|s| s.to_zngur(aliases, base, ctx))
+                    .collect(),
+                output: Box::new(output.to_zngur(aliases, base, ctx)),
+            },
             ParsedRustType::Dyn(tr, bounds) => RustType::Dyn(
-                tr.to_zngur(aliases, base),
+                tr.to_zngur(aliases, base, ctx),
                 bounds.into_iter().map(|x| x.to_owned()).collect(),
             ),
-            ParsedRustType::Tuple(v) => {
-                RustType::Tuple(v.into_iter().map(|s| s.to_zngur(aliases, base)).collect())
+            ParsedRustType::Tuple(v) => RustType::Tuple(
+                v.into_iter()
+                    .map(|s| s.to_zngur(aliases, base, ctx))
+                    .collect(),
+            ),
+            ParsedRustType::Adt(s) => {
+                if let Some(ty) = ctx.template_type(&s) {
+                    return ty;
+                }
+                let span = s.path.span;
+                let ty = RustType::Adt(s.to_zngur(aliases, base, ctx));
+                ctx.add_reference(span, ReferenceTarget::Type(ty.clone()));
+                ty
             }
-            ParsedRustType::Adt(s) => RustType::Adt(s.to_zngur(aliases, base)),
         }
     }
 }
@@ -548,9 +895,15 @@ enum ParsedRustTrait<'a> {
 }
 
 impl ParsedRustTrait<'_> {
-    fn to_zngur(self, aliases: &[ParsedAlias<'_>], base: &[String]) -> RustTrait {
+    #[allow(clippy::wrong_self_convention)]
+    fn to_zngur(
+        self,
+        aliases: &[ParsedAlias<'_>],
+        base: &[String],
+        ctx: &ParseContext,
+    ) -> RustTrait {
         match self {
-            ParsedRustTrait::Normal(s) => RustTrait::Normal(s.to_zngur(aliases, base)),
+            ParsedRustTrait::Normal(s) => RustTrait::Normal(s.to_zngur(aliases, base, ctx)),
             ParsedRustTrait::Fn {
                 name,
                 inputs,
@@ -559,9 +912,9 @@ impl ParsedRustTrait<'_> {
                 name: name.to_owned(),
                 inputs: inputs
                     .into_iter()
-                    .map(|s| s.to_zngur(aliases, base))
+                    .map(|s| s.to_zngur(aliases, base, ctx))
                     .collect(),
-                output: Box::new(output.to_zngur(aliases, base)),
+                output: Box::new(output.to_zngur(aliases, base, ctx)),
             },
         }
     }
@@ -575,41 +928,265 @@ struct ParsedRustPathAndGenerics<'a> {
 }
 
 impl ParsedRustPathAndGenerics<'_> {
-    fn to_zngur(self, aliases: &[ParsedAlias<'_>], base: &[String]) -> RustPathAndGenerics {
+    #[allow(clippy::wrong_self_convention)]
+    fn to_zngur(
+        self,
+        aliases: &[ParsedAlias<'_>],
+        base: &[String],
+        ctx: &ParseContext,
+    ) -> RustPathAndGenerics {
         RustPathAndGenerics {
-            path: aliases
-                .iter()
-                .filter_map(|alias| alias.expand(&self.path, base))
-                .collect::<Vec<_>>()
-                .first()
-                .cloned()
-                .unwrap_or_else(|| self.path.to_zngur(base)),
+            path: ctx.resolve_path(self.path, aliases, base),
             generics: self
                 .generics
                 .into_iter()
-                .map(|x| x.to_zngur(aliases, base))
+                .map(|x| x.to_zngur(aliases, base, ctx))
                 .collect(),
             named_generics: self
                 .named_generics
                 .into_iter()
-                .map(|(name, x)| (name.to_owned(), x.to_zngur(aliases, base)))
+                .map(|(name, x)| (name.to_owned(), x.to_zngur(aliases, base, ctx)))
                 .collect(),
         }
     }
 }
 
-static LATEST_FILENAME: Mutex<String> = Mutex::new(String::new());
-static LATEST_TEXT: Mutex<String> = Mutex::new(String::new());
+struct ParseContext {
+    /// The file that is being processed, used for reporting diagnostics and resolving imports.
+    file: String,
+    /// Content of `file`, used for computing line and column of spans.
+    text: String,
+    /// The `///` comments of `text`, with the start of the item that each of them documents.
+    docs: Vec<(usize, String)>,
+    /// Canonical paths of the files that are being imported, used for detecting import cycles.
+    import_stack: Vec<PathBuf>,
+    imported_files: Vec<PathBuf>,
+    diagnostics: Vec<Diagnostic>,
+    /// Filled while converting items, which only have shared access to the context.
+    index: RefCell<SymbolIndex>,
+    /// Symbols of the module that is being processed.
+    symbols: Vec<Symbol>,
+    /// Values of the parameters of the `#for` blocks that are being instantiated, innermost last.
+    template_args: Vec<(String, TemplateArg)>,
+    /// The configuration that `#cfg(...)` attributes are evaluated against.
+    cfg: Cfg,
+    /// Every `#cfg(...)` predicate evaluated so far, with its result.
+    cfgs: RefCell<Vec<(CfgPredicate, bool)>>,
+}
 
-impl<'a> ParsedZngFile<'a> {
-    pub fn parse(filename: &str, text: &str) -> ZngurFile {
-        *LATEST_FILENAME.lock().unwrap() = filename.to_string();
-        *LATEST_TEXT.lock().unwrap() = text.to_string();
-        let (tokens, errs) = lexer().parse(text).into_output_errors();
-        let Some(tokens) = tokens else {
-            let errs = errs.into_iter().map(|e| e.map_token(|c| c.to_string()));
-            emit_error(errs);
+impl ParseContext {
+    fn new(file: &str, cfg: Cfg) -> Self {
+        ParseContext {
+            file: file.to_owned(),
+            text: String::new(),
+            docs: vec![],
+            import_stack: vec![],
+            imported_files: vec![],
+            diagnostics: vec![],
+            index: RefCell::default(),
+            symbols: vec![],
+            template_args: vec![],
+            cfg,
+            cfgs: RefCell::default(),
+        }
+    }
+
+    /// Whether `predicate` holds. The result is recorded, so the generated code can check it too.
+    fn eval_cfg(&self, predicate: CfgPredicate) -> bool {
+        let result = self.cfg.eval(&predicate);
+        let mut cfgs = self.cfgs.borrow_mut();
+        if !cfgs.iter().any(|x| x.0 == predicate) {
+            cfgs.push((predicate, result));
+        }
+        result
+    }
+
+    /// Removes the `#cfg(...)` attributes of `item`, or the item itself if they don't hold.
+    fn cfg_item<'a>(&self, mut item: ParsedItem<'a>) -> Option<ParsedItem<'a>> {
+        while let ParsedItem::Cfg {
+            predicate,
+            item: inner,
+        } = item
+        {
+            if !self.eval_cfg(predicate) {
+                return None;
+            }
+            item = *inner;
+        }
+        Some(item)
+    }
+
+    /// Same as [`ParseContext::cfg_item`], for the items of a type block.
+    fn cfg_type_item<'a>(
+        &self,
+        mut item: Spanned<ParsedTypeItem<'a>>,
+    ) -> Option<Spanned<ParsedTypeItem<'a>>> {
+        while let ParsedTypeItem::Cfg(predicate, inner) = item.inner {
+            if !self.eval_cfg(predicate) {
+                return None;
+            }
+            item.inner = *inner;
+        }
+        Some(item)
+    }
+
+    fn template_arg(&self, name: &str) -> Option<&TemplateArg> {
+        self.template_args
+            .iter()
+            .rev()
+            .find(|x| x.0 == name)
+            .map(|x| &x.1)
+    }
+
+    /// The type that `ty` is bound to, if it is a `#for` parameter.
+    fn template_type(&self, ty: &ParsedRustPathAndGenerics<'_>) -> Option<RustType> {
+        let [name] = &*ty.path.segments else {
+            return None;
+        };
+        if ty.path.start != ParsedPathStart::Relative
+            || !ty.generics.is_empty()
+            || !ty.named_generics.is_empty()
+        {
+            return None;
+        }
+        match self.template_arg(name)? {
+            TemplateArg::Type(ty) => Some(ty.clone()),
+            TemplateArg::Number(_) => None,
+        }
+    }
+
+    fn resolve_number(&mut self, number: Spanned<ParsedNumber<'_>>) -> Option<usize> {
+        match number.inner {
+            ParsedNumber::Literal(n) => Some(n),
+            ParsedNumber::Param(name) => match self.template_arg(name) {
+                Some(TemplateArg::Number(n)) => Some(*n),
+                _ => {
+                    self.add_error(
+                        &format!("`{name}` is not a `#for` parameter with a number value"),
+                        number.span,
+                    );
+                    None
+                }
+            },
+        }
+    }
+
+    fn add_diagnostic(&mut self, diagnostic: Diagnostic) {
+        self.diagnostics.push(diagnostic);
+    }
+
+    fn add_error(&mut self, message: &str, span: Span) {
+        self.add_diagnostic(Diagnostic::error(message).with_label(
+            LabelKind::Primary,
+            &self.file,
+            span,
+            message,
+        ));
+    }
+
+    fn span(&self, span: Span) -> Option<ZngurSpan> {
+        let before = &self.text[..span.start];
+        let line_start = before.rfind('\n').map_or(0, |x| x + 1);
+        Some(ZngurSpan {
+            file: self.file.clone(),
+            start: span.start,
+            end: span.end,
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+        })
+    }
+
+    /// The `///` comments right before the item at `span`.
+    fn doc(&self, span: Span) -> Option<String> {
+        self.docs
+            .iter()
+            .find(|(start, _)| *start == span.start)
+            .map(|(_, doc)| doc.clone())
+    }
+
+    fn add_reference(&self, span: Span, target: ReferenceTarget) {
+        if let Some(span) = self.span(span) {
+            self.index
+                .borrow_mut()
+                .references
+                .push(Reference { span, target });
+        }
+    }
+
+    fn add_symbol(&mut self, kind: SymbolKind, span: Span, name_span: Span, children: Vec<Symbol>) {
+        if let (Some(span), Some(name_span)) = (self.span(span), self.span(name_span)) {
+            self.symbols.push(Symbol {
+                name: self.text[name_span.start..name_span.end].to_owned(),
+                kind,
+                span,
+                name_span,
+                children,
+            });
+        }
+    }
+
+    /// Resolves `path` using the aliases in scope, or relative to `base` if none of them matches.
+    fn resolve_path(
+        &self,
+        path: ParsedPath<'_>,
+        aliases: &[ParsedAlias<'_>],
+        base: &[String],
+    ) -> Vec<String> {
+        let Some((alias, resolved)) = aliases
+            .iter()
+            .find_map(|alias| Some((alias, alias.expand(&path, base)?)))
+        else {
+            return path.to_zngur(base);
         };
+        if let Some(definition) = alias.state.definition.get() {
+            self.add_reference(path.span, ReferenceTarget::Alias(definition.clone()));
+        }
+        resolved
+    }
+
+    /// Records the location of the aliases of the module that is about to be processed.
+    fn declare_aliases(&self, aliases: &[ParsedAlias<'_>]) {
+        for alias in aliases {
+            if let Some(span) = self.span(alias.span) {
+                _ = alias.state.definition.set(span);
+            }
+        }
+    }
+
+    fn check_unused_aliases(&mut self, aliases: &[ParsedAlias<'_>]) {
+        for alias in aliases {
+            if !alias.state.used.get() {
+                let message = format!("Alias `{}` is never used", alias.name);
+                self.add_diagnostic(Diagnostic::lint(Lint::UnusedAlias, &message).with_label(
+                    LabelKind::Primary,
+                    &self.file,
+                    alias.span,
+                    &message,
+                ));
+            }
+        }
+    }
+
+    fn has_errors(&self) -> bool {
+        self.diagnostics.iter().any(|d| d.is_error())
+    }
+
+    /// Parses `text` as the content of the current file, and passes the result to `f`. Syntax
+    /// errors are recovered from when possible, in which case `f` receives a partial AST.
+    fn parse_text<R>(
+        &mut self,
+        text: &str,
+        f: impl FnOnce(&mut Self, ParsedZngFile<'_>) -> R,
+    ) -> Option<R> {
+        self.text = text.to_owned();
+        let (tokens, errs) = lexer_with_comments().parse(text).into_output_errors();
+        self.diagnostics
+            .extend(errs.iter().map(|e| Diagnostic::from_rich(&self.file, e)));
+        self.docs = tokens.as_deref().map(doc_comments).unwrap_or_default();
+        let tokens = tokens?
+            .into_iter()
+            .filter(|(t, _)| !matches!(t, Token::Comment(_)))
+            .collect::<Vec<_>>();
         let tokens: ParserInput<'_> = tokens
             .as_slice()
             .map((text.len()..text.len()).into(), Box::new(|(t, s)| (t, s)));
@@ -617,24 +1194,270 @@ impl<'a> ParsedZngFile<'a> {
             .map_with(|ast, extra| (ast, extra.span()))
             .parse(tokens)
             .into_output_errors();
-        let Some(ast) = ast else {
-            let errs = errs.into_iter().map(|e| e.map_token(|c| c.to_string()));
-            emit_error(errs);
+        self.diagnostics
+            .extend(errs.iter().map(|e| Diagnostic::from_rich(&self.file, e)));
+        Some(f(self, ast?.0))
+    }
+
+    fn import(
+        &mut self,
+        path: Spanned<&str>,
+        r: &mut ZngurFile,
+        aliases: &[ParsedAlias],
+        base: &[String],
+    ) {
+        let resolved = Path::new(&self.file)
+            .parent()
+            .unwrap_or(Path::new(""))
+            .join(path.inner);
+        let canonical = match resolved.canonicalize() {
+            Ok(x) => x,
+            Err(e) => {
+                self.add_error(
+                    &format!("Failed to read imported file `{}`: {e}", resolved.display()),
+                    path.span,
+                );
+                return;
+            }
         };
-        ast.0.into_zngur_file()
+        if let Some(cycle_start) = self.import_stack.iter().position(|x| *x == canonical) {
+            let cycle = self.import_stack[cycle_start..]
+                .iter()
+                .chain(Some(&canonical))
+                .map(|x| x.display().to_string())
+                .join(" -> ");
+            self.add_error(&format!("Import cycle detected: {cycle}"), path.span);
+            return;
+        }
+        let text = match std::fs::read_to_string(&canonical) {
+            Ok(x) => x,
+            Err(e) => {
+                self.add_error(
+                    &format!("Failed to read imported file `{}`: {e}", resolved.display()),
+                    path.span,
+                );
+                return;
+            }
+        };
+        self.imported_files.push(resolved.clone());
+        let importer = std::mem::replace(&mut self.file, resolved.to_string_lossy().into_owned());
+        let importer_text = std::mem::take(&mut self.text);
+        let importer_docs = std::mem::take(&mut self.docs);
+        self.import_stack.push(canonical);
+        let importer_symbols = std::mem::take(&mut self.symbols);
+        self.parse_text(&text, |this, ast| {
+            let ProcessedZngFile {
+                aliases: mut file_aliases,
+                items,
+            } = ast.process(this);
+            this.declare_aliases(&file_aliases);
+            let own_aliases = file_aliases.len();
+            file_aliases.extend_from_slice(aliases);
+            for item in items {
+                item.add_to_zngur_file(r, &file_aliases, base, this);
+            }
+            this.check_unused_aliases(&file_aliases[..own_aliases]);
+        });
+        self.import_stack.pop();
+        let symbols = std::mem::replace(&mut self.symbols, importer_symbols);
+        self.index.get_mut().symbols.extend(symbols);
+        self.file = importer;
+        self.text = importer_text;
+        self.docs = importer_docs;
+    }
+}
+
+/// Parser for zng files, which keeps track of the files pulled in by `import` items, the
+/// warnings of successful parses and the symbols of every parse.
+#[derive(Debug)]
+pub struct ZngParser {
+    cfg: Cfg,
+    imported_files: Vec<PathBuf>,
+    warnings: Vec<Diagnostic>,
+    index: SymbolIndex,
+}
+
+impl Default for ZngParser {
+    fn default() -> Self {
+        ZngParser {
+            cfg: Cfg::host(),
+            imported_files: vec![],
+            warnings: vec![],
+            index: SymbolIndex::default(),
+        }
+    }
+}
+
+impl ZngParser {
+    /// A parser that evaluates `#cfg(...)` attributes against [`Cfg::host`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the configuration that `#cfg(...)` attributes are evaluated against.
+    pub fn with_cfg(mut self, cfg: Cfg) -> Self {
+        self.cfg = cfg;
+        self
+    }
+
+    /// Parses a zng file and converts it into a [`ZngurFile`].
+    ///
+    /// `filename` is used for reporting diagnostics and for resolving `import` items, which are
+    /// relative to the directory of the importing file. On failure, every problem found is
+    /// returned and can be rendered using [`AriadneRenderer`].
+    ///
+    /// A successfully parsed file is also checked by [`lint`]. Warnings don't fail the parse, and
+    /// are available from [`ZngParser::warnings`] instead.
+    pub fn parse(&mut self, filename: &str, text: &str) -> Result<ZngurFile, Vec<Diagnostic>> {
+        let mut ctx = ParseContext::new(filename, self.cfg.clone());
+        if let Ok(canonical) = Path::new(filename).canonicalize() {
+            ctx.import_stack.push(canonical);
+        }
+        let r = ctx.parse_text(text, |ctx, ast| ast.into_zngur_file(ctx));
+        self.imported_files.append(&mut ctx.imported_files);
+        let index = ctx.index.get_mut();
+        self.index.symbols.append(&mut index.symbols);
+        self.index.symbols.append(&mut ctx.symbols);
+        self.index.references.append(&mut index.references);
+        self.index.types.append(&mut index.types);
+        let Some(r) = r else {
+            return Err(ctx.diagnostics);
+        };
+        if !ctx.has_errors() {
+            ctx.diagnostics.extend(lint(&r));
+        }
+        ctx.diagnostics.retain(|d| match d.lint {
+            Some(lint) => !r.allowed_lints.iter().any(|x| x == lint.name()),
+            None => true,
+        });
+        if ctx.has_errors() {
+            return Err(ctx.diagnostics);
+        }
+        self.warnings.append(&mut ctx.diagnostics);
+        Ok(r)
+    }
+
+    /// Warnings of every file that was parsed successfully so far.
+    pub fn warnings(&self) -> &[Diagnostic] {
+        &self.warnings
+    }
+
+    /// Items and paths of every file parsed so far, including the ones with errors.
+    pub fn index(&self) -> &SymbolIndex {
+        &self.index
+    }
+
+    /// Paths of every file that was pulled in by an `import` item so far.
+    pub fn imported_files(&self) -> &[PathBuf] {
+        &self.imported_files
+    }
+}
+
+/// Parses `text` as a type, in the scope of the root of a zng file.
+fn parse_rust_type(text: &str) -> Result<RustType, String> {
+    let tokens = lex_fragment(text)?;
+    let ty = parse_fragment(&tokens, text.len(), rust_type())?;
+    Ok(ty.to_zngur(&[], &[], &fragment_context(text)))
+}
+
+/// Parses `text` as the signature of a method in a type block, like `fn len(&self) -> usize`.
+fn parse_method(text: &str) -> Result<ZngurMethod, String> {
+    let tokens = lex_fragment(text)?;
+    let method = parse_fragment(
+        &tokens,
+        text.len(),
+        method().then_ignore(just(Token::Semicolon).or_not()),
+    )?;
+    Ok(ZngurMethod {
+        doc: None,
+        span: None,
+        ..method.to_zngur(&[], &[], &fragment_context(text))
+    })
+}
+
+/// Parses `text` as the signature of a free function with its full path, like
+/// `fn crate::add(i32, i32) -> i32`.
+fn parse_fn(text: &str) -> Result<ZngurFn, String> {
+    let tokens = lex_fragment(text)?;
+    let (path, (inputs, output)) = parse_fragment(
+        &tokens,
+        text.len(),
+        just(Token::KwFn)
+            .ignore_then(rust_path_and_generics(rust_type()))
+            .then(fn_args(rust_type()))
+            .then_ignore(just(Token::Semicolon).or_not()),
+    )?;
+    let ctx = fragment_context(text);
+    Ok(ZngurFn {
+        path: path.to_zngur(&[], &[], &ctx),
+        inputs: inputs
+            .into_iter()
+            .map(|x| x.to_zngur(&[], &[], &ctx))
+            .collect(),
+        output: output.to_zngur(&[], &[], &ctx),
+        doc: None,
+        span: None,
+    })
+}
+
+fn lex_fragment(text: &str) -> Result<Vec<(Token<'_>, Span)>, String> {
+    lexer()
+        .parse(text)
+        .into_result()
+        .map_err(|e| e[0].to_string())
+}
+
+fn parse_fragment<'a, T>(
+    tokens: &'a [(Token<'a>, Span)],
+    len: usize,
+    parser: impl Parser<'a, ParserInput<'a>, T, extra::Err<Rich<'a, Token<'a>, Span>>>,
+) -> Result<T, String> {
+    let tokens: ParserInput<'a> = tokens.map((len..len).into(), Box::new(|(t, s)| (t, s)));
+    parser
+        .then_ignore(end())
+        .parse(tokens)
+        .into_result()
+        .map_err(|e| e[0].to_string())
+}
+
+/// Context for converting a fragment parsed from `text`, which is not a part of any file.
+fn fragment_context(text: &str) -> ParseContext {
+    let mut ctx = ParseContext::new("", Cfg::new());
+    ctx.text = text.to_owned();
+    ctx
+}
+
+impl<'a> ParsedZngFile<'a> {
+    /// Parses a zng file and converts it into a [`ZngurFile`]. See [`ZngParser::parse`].
+    pub fn parse(filename: &str, text: &str) -> Result<ZngurFile, Vec<Diagnostic>> {
+        ZngParser::new().parse(filename, text)
     }
 
-    pub fn process(self) -> ProcessedZngFile<'a> {
-        let (aliases, items) = self.0.into_iter().partition_map(partition_parsed_item_vec);
+    fn process(self, ctx: &ParseContext) -> ProcessedZngFile<'a> {
+        let (aliases, items) = partition_parsed_items(self.0, ctx);
         ProcessedZngFile::new(aliases, items)
     }
 
-    pub fn into_zngur_file(self) -> ZngurFile {
-        self.process().into_zngur_file()
+    fn into_zngur_file(self, ctx: &mut ParseContext) -> ZngurFile {
+        self.process(ctx).into_zngur_file(ctx)
     }
 }
 
-fn partition_parsed_item_vec(item: ParsedItem<'_>) -> Either<ParsedAlias<'_>, ProcessedItem<'_>> {
+/// Separates the aliases from the other items, dropping the items whose `#cfg(...)` doesn't hold.
+fn partition_parsed_items<'a>(
+    items: Vec<ParsedItem<'a>>,
+    ctx: &ParseContext,
+) -> (Vec<ParsedAlias<'a>>, Vec<ProcessedItem<'a>>) {
+    items
+        .into_iter()
+        .filter_map(|x| ctx.cfg_item(x))
+        .partition_map(|x| partition_parsed_item_vec(x, ctx))
+}
+
+fn partition_parsed_item_vec<'a>(
+    item: ParsedItem<'a>,
+    ctx: &ParseContext,
+) -> Either<ParsedAlias<'a>, ProcessedItem<'a>> {
     match item {
         ParsedItem::Alias(alias) => Either::Left(alias),
         ParsedItem::ConvertPanicToException => {
@@ -643,19 +1466,41 @@ fn partition_parsed_item_vec(item: ParsedItem<'_>) -> Either<ParsedAlias<'_>, Pr
         ParsedItem::CppAdditionalInclude(inc) => {
             Either::Right(ProcessedItem::CppAdditionalInclude(inc))
         }
-        ParsedItem::Mod { path, items } => {
-            let (aliases, items): (Vec<ParsedAlias<'_>>, Vec<ProcessedItem<'_>>) =
-                items.into_iter().partition_map(partition_parsed_item_vec);
+        ParsedItem::Mod { path, items, span } => {
+            let (aliases, items) = partition_parsed_items(items, ctx);
             Either::Right(ProcessedItem::Mod {
                 path,
                 items,
                 aliases,
+                span,
             })
         }
-        ParsedItem::Type { ty, items } => Either::Right(ProcessedItem::Type { ty, items }),
-        ParsedItem::Trait { tr, methods } => Either::Right(ProcessedItem::Trait { tr, methods }),
+        ParsedItem::Type { ty, items, span } => {
+            Either::Right(ProcessedItem::Type { ty, items, span })
+        }
+        ParsedItem::Trait { tr, methods, span } => {
+            Either::Right(ProcessedItem::Trait { tr, methods, span })
+        }
         ParsedItem::Fn(method) => Either::Right(ProcessedItem::Fn(method)),
         ParsedItem::ExternCpp(items) => Either::Right(ProcessedItem::ExternCpp(items)),
+        ParsedItem::Import(path) => Either::Right(ProcessedItem::Import(path)),
+        ParsedItem::Allow(lints) => Either::Right(ProcessedItem::Allow(lints)),
+        ParsedItem::For {
+            params,
+            rows,
+            items,
+            span: _,
+        } => {
+            let (aliases, items) = partition_parsed_items(items, ctx);
+            Either::Right(ProcessedItem::For {
+                params,
+                rows,
+                items,
+                aliases,
+            })
+        }
+        ParsedItem::Cfg { .. } => unreachable!("removed by `ParseContext::cfg_item`"),
+        ParsedItem::Error => Either::Right(ProcessedItem::Error),
     }
 }
 
@@ -664,66 +1509,18 @@ impl<'a> ProcessedZngFile<'a> {
         ProcessedZngFile { aliases, items }
     }
 
-    pub fn into_zngur_file(self) -> ZngurFile {
+    fn into_zngur_file(self, ctx: &mut ParseContext) -> ZngurFile {
         let mut r = ZngurFile::default();
+        ctx.declare_aliases(&self.aliases);
         for item in self.items {
-            item.add_to_zngur_file(&mut r, &self.aliases, &[]);
+            item.add_to_zngur_file(&mut r, &self.aliases, &[], ctx);
         }
+        ctx.check_unused_aliases(&self.aliases);
+        r.cfgs = ctx.cfgs.take();
         r
     }
 }
 
-fn create_and_emit_error(error: &str, span: Span) -> ! {
-    emit_error([Rich::custom(span, error)].into_iter())
-}
-
-#[cfg(test)]
-fn emit_ariadne_error(err: Report<'_, (String, std::ops::Range<usize>)>) -> ! {
-    let mut r = Vec::<u8>::new();
-    // Block needed to drop lock guards before panic
-    {
-        let filename = &**LATEST_FILENAME.lock().unwrap();
-        let text = &**LATEST_TEXT.lock().unwrap();
-
-        err.write(sources([(filename.to_string(), text)]), &mut r)
-            .unwrap();
-    }
-    std::panic::resume_unwind(Box::new(tests::ErrorText(
-        String::from_utf8(strip_ansi_escapes::strip(r)).unwrap(),
-    )));
-}
-
-#[cfg(not(test))]
-fn emit_ariadne_error(err: Report<'_, (String, std::ops::Range<usize>)>) -> ! {
-    let filename = &**LATEST_FILENAME.lock().unwrap();
-    let text = &**LATEST_TEXT.lock().unwrap();
-
-    err.eprint(sources([(filename.to_string(), text)])).unwrap();
-    exit(101);
-}
-
-fn emit_error<'a>(errs: impl Iterator<Item = Rich<'a, String>>) -> ! {
-    let filename = LATEST_FILENAME.lock().unwrap().to_owned();
-    for e in errs {
-        emit_ariadne_error(
-            Report::build(ReportKind::Error, &filename, e.span().start)
-                .with_message(e.to_string())
-                .with_label(
-                    Label::new((filename.to_string(), e.span().into_range()))
-                        .with_message(e.reason().to_string())
-                        .with_color(Color::Red),
-                )
-                .with_labels(e.contexts().map(|(label, span)| {
-                    Label::new((filename.to_string(), span.into_range()))
-                        .with_message(format!("while parsing this {}", label))
-                        .with_color(Color::Yellow)
-                }))
-                .finish(),
-        )
-    }
-    exit(101);
-}
-
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 enum Token<'a> {
     Arrow,
@@ -743,6 +1540,7 @@ enum Token<'a> {
     Plus,
     Eq,
     Question,
+    Bang,
     Comma,
     Semicolon,
     KwAs,
@@ -761,6 +1559,8 @@ enum Token<'a> {
     Ident(&'a str),
     Str(&'a str),
     Number(usize),
+    /// A `//` comment, only produced by [`lexer_with_comments`].
+    Comment(&'a str),
 }
 
 impl<'a> Token<'a> {
@@ -804,6 +1604,7 @@ impl Display for Token<'_> {
             Token::Plus => write!(f, "+"),
             Token::Eq => write!(f, "="),
             Token::Question => write!(f, "?"),
+            Token::Bang => write!(f, "!"),
             Token::Comma => write!(f, ","),
             Token::Semicolon => write!(f, ";"),
             Token::KwAs => write!(f, "as"),
@@ -822,13 +1623,67 @@ impl Display for Token<'_> {
             Token::Ident(i) => write!(f, "{i}"),
             Token::Number(n) => write!(f, "{n}"),
             Token::Str(s) => write!(f, r#""{s}""#),
+            Token::Comment(c) => write!(f, "{c}"),
         }
     }
 }
 
 fn lexer<'src>()
 -> impl Parser<'src, &'src str, Vec<(Token<'src>, Span)>, extra::Err<Rich<'src, char, Span>>> {
+    lexer_with_comments().map(|tokens| {
+        tokens
+            .into_iter()
+            .filter(|(t, _)| !matches!(t, Token::Comment(_)))
+            .collect()
+    })
+}
+
+/// Attaches each run of `///` comments to the start of the token after it, skipping the `#cfg(...)`
+/// attributes in between.
+fn doc_comments(tokens: &[(Token<'_>, Span)]) -> Vec<(usize, String)> {
+    let mut docs = vec![];
+    let mut lines = vec![];
+    let mut i = 0;
+    while i < tokens.len() {
+        match &tokens[i].0 {
+            Token::Comment(c) => match c.strip_prefix("///").filter(|x| !x.starts_with('/')) {
+                Some(text) => lines.push(text.strip_prefix(' ').unwrap_or(text)),
+                None => lines.clear(),
+            },
+            Token::Sharp if tokens.get(i + 1).map(|x| &x.0) == Some(&Token::Ident("cfg")) => {
+                // Skips to the `)` closing the predicate.
+                let mut depth = 0;
+                for (t, _) in &tokens[i..] {
+                    match t {
+                        Token::ParenOpen => depth += 1,
+                        Token::ParenClose if depth == 1 => break,
+                        Token::ParenClose => depth -= 1,
+                        _ => (),
+                    }
+                    i += 1;
+                }
+            }
+            _ => {
+                if !lines.is_empty() {
+                    docs.push((tokens[i].1.start, lines.join("\n")));
+                    lines.clear();
+                }
+            }
+        }
+        i += 1;
+    }
+    docs
+}
+
+fn lexer_with_comments<'src>()
+-> impl Parser<'src, &'src str, Vec<(Token<'src>, Span)>, extra::Err<Rich<'src, char, Span>>> {
+    let comment = just("//")
+        .then(any().and_is(just('\n').not()).repeated())
+        .to_slice()
+        .map(|c: &str| Token::Comment(c.trim_end()));
+
     let token = choice((
+        comment,
         choice([
             just("->").to(Token::Arrow),
             just("<").to(Token::AngleOpen),
@@ -847,23 +1702,27 @@ fn lexer<'src>()
             just("+").to(Token::Plus),
             just("=").to(Token::Eq),
             just("?").to(Token::Question),
+            just("!").to(Token::Bang),
             just(",").to(Token::Comma),
             just(";").to(Token::Semicolon),
         ]),
         text::ident().map(Token::ident_or_kw),
-        text::int(10).map(|x: &str| Token::Number(x.parse().unwrap())),
+        text::int(10).validate(|x: &str, extra, emitter| {
+            Token::Number(x.parse().unwrap_or_else(|_| {
+                emitter.emit(Rich::custom(
+                    extra.span(),
+                    format!("Number `{x}` is too large"),
+                ));
+                0
+            }))
+        }),
         just('"')
             .ignore_then(none_of('"').repeated().to_slice().map(Token::Str))
             .then_ignore(just('"')),
     ));
 
-    let comment = just("//")
-        .then(any().and_is(just('\n').not()).repeated())
-        .padded();
-
     token
         .map_with(|tok, extra| (tok, extra.span()))
-        .padded_by(comment.repeated())
         .padded()
         .repeated()
         .collect()
@@ -883,15 +1742,90 @@ fn alias<'a>()
                 name,
                 path,
                 span: extra.span(),
+                state: Rc::default(),
             })
         })
         .boxed()
 }
 
+/// Skips a broken item up to and including the next `;` or a balanced `{ ... }` block, whichever
+/// comes first. Used for recovering from syntax errors.
+fn skip_item<'a>()
+-> impl Parser<'a, ParserInput<'a>, (), extra::Err<Rich<'a, Token<'a>, Span>>> + Clone {
+    let block = recursive(|block| {
+        block
+            .or(none_of([Token::BraceOpen, Token::BraceClose]).ignored())
+            .repeated()
+            .delimited_by(just(Token::BraceOpen), just(Token::BraceClose))
+    });
+    // The `;` of array types like `[u8; 32]` doesn't end the item.
+    let brackets = recursive(|brackets| {
+        brackets
+            .or(none_of([
+                Token::Semicolon,
+                Token::BracketOpen,
+                Token::BracketClose,
+                Token::BraceOpen,
+                Token::BraceClose,
+            ])
+            .ignored())
+            .or(just(Token::Semicolon).ignored())
+            .repeated()
+            .delimited_by(just(Token::BracketOpen), just(Token::BracketClose))
+    });
+    brackets
+        .or(none_of([Token::Semicolon, Token::BraceOpen, Token::BraceClose]).ignored())
+        .repeated()
+        .then(just(Token::Semicolon).ignored().or(block))
+        .ignored()
+        .boxed()
+}
+
+/// Parses `{ item; item; ... }` where broken items are skipped and reported. A skipped item is
+/// replaced with what `skipped` returns for its span, if anything.
+fn recovering_block<'a, T: Clone + 'a>(
+    item: impl Parser<'a, ParserInput<'a>, T, extra::Err<Rich<'a, Token<'a>, Span>>> + Clone + 'a,
+    skipped: impl Fn(Span) -> Option<T> + Clone + 'a,
+) -> impl Parser<'a, ParserInput<'a>, Vec<T>, extra::Err<Rich<'a, Token<'a>, Span>>> + Clone {
+    item.map(Some)
+        .recover_with(via_parser(
+            skip_item().map_with(move |(), extra| skipped(extra.span())),
+        ))
+        .repeated()
+        .collect::<Vec<_>>()
+        .delimited_by(just(Token::BraceOpen), just(Token::BraceClose))
+        .map(|items| items.into_iter().flatten().collect())
+        .boxed()
+}
+
+/// Parser for an item that starts with `keyword`. Once the keyword is found, a syntax error in the
+/// rest of the item is recovered from by skipping the item.
+fn keyword_item<'a, T: Clone + 'a>(
+    keyword: impl chumsky::container::OrderedSeq<'a, Token<'a>> + Clone + 'a,
+    rest: impl Parser<'a, ParserInput<'a>, T, extra::Err<Rich<'a, Token<'a>, Span>>> + Clone + 'a,
+    build: impl Fn(T, Span) -> ParsedItem<'a> + Clone + 'a,
+) -> impl Parser<'a, ParserInput<'a>, ParsedItem<'a>, extra::Err<Rich<'a, Token<'a>, Span>>> + Clone
+{
+    spanned(
+        just(keyword).ignore_then(
+            rest.map(Some)
+                .recover_with(via_parser(skip_item().to(None))),
+        ),
+    )
+    .map(move |item| match item.inner {
+        Some(x) => build(x, item.span),
+        None => ParsedItem::Error,
+    })
+}
+
 fn file_parser<'a>()
 -> impl Parser<'a, ParserInput<'a>, ParsedZngFile<'a>, extra::Err<Rich<'a, Token<'a>, Span>>> + Clone
 {
-    item().repeated().collect::<Vec<_>>().map(ParsedZngFile)
+    item()
+        .recover_with(via_parser(skip_item().to(ParsedItem::Error)))
+        .repeated()
+        .collect::<Vec<_>>()
+        .map(ParsedZngFile)
 }
 
 fn rust_type<'a>()
@@ -903,9 +1837,11 @@ fn rust_type<'a>()
 
     let scalar = select! {
         Token::Ident("bool") => PrimitiveRustType::Bool,
+        Token::Ident("char") => PrimitiveRustType::Char,
         Token::Ident("str") => PrimitiveRustType::Str,
         Token::Ident("ZngurCppOpaqueOwnedObject") => PrimitiveRustType::ZngurCppOpaqueOwnedObject,
         Token::Ident("usize") => PrimitiveRustType::Usize,
+        Token::Ident("isize") => PrimitiveRustType::Isize,
         Token::Ident(c) if as_scalar(c, 'u').is_some() => PrimitiveRustType::Uint(as_scalar(c, 'u').unwrap()),
         Token::Ident(c) if as_scalar(c, 'i').is_some() => PrimitiveRustType::Int(as_scalar(c, 'i').unwrap()),
         Token::Ident(c) if as_scalar(c, 'f').is_some() => PrimitiveRustType::Float(as_scalar(c, 'f').unwrap()),
@@ -929,9 +1865,18 @@ fn rust_type<'a>()
             .map(|(x, y)| ParsedRustType::Dyn(x, y));
         let boxed = just(Token::Ident("Box"))
             .then(rust_generics(parser.clone()))
-            .map(|(_, x)| {
-                assert_eq!(x.len(), 1);
-                ParsedRustType::Boxed(Box::new(x.into_iter().next().unwrap().right().unwrap()))
+            .validate(|(_, x), extra, emitter| {
+                let ty = match <[_; 1]>::try_from(x) {
+                    Ok([Either::Right(ty)]) => ty,
+                    _ => {
+                        emitter.emit(Rich::custom(
+                            extra.span(),
+                            "`Box` takes exactly one type argument, like `Box<T>`",
+                        ));
+                        ParsedRustType::Tuple(vec![])
+                    }
+                };
+                ParsedRustType::Boxed(Box::new(ty))
             });
         let unit = just(Token::ParenOpen)
             .then(just(Token::ParenClose))
@@ -942,10 +1887,20 @@ fn rust_type<'a>()
             .allow_trailing()
             .collect::<Vec<_>>()
             .delimited_by(just(Token::ParenOpen), just(Token::ParenClose))
-            .map(|xs| ParsedRustType::Tuple(xs));
+            .map(ParsedRustType::Tuple);
         let slice = parser
             .clone()
-            .map(|x| ParsedRustType::Slice(Box::new(x)))
+            .then(
+                just(Token::Semicolon)
+                    .ignore_then(select! {
+                        Token::Number(n) => n,
+                    })
+                    .or_not(),
+            )
+            .map(|(x, n)| match n {
+                Some(n) => ParsedRustType::Array(Box::new(x), n),
+                None => ParsedRustType::Slice(Box::new(x)),
+            })
             .delimited_by(just(Token::BracketOpen), just(Token::BracketClose));
         let reference = just(Token::And)
             .ignore_then(
@@ -961,15 +1916,23 @@ fn rust_type<'a>()
                     .to(Mutability::Mut)
                     .or(just(Token::KwConst).to(Mutability::Not)),
             )
-            .then(parser)
+            .then(parser.clone())
             .map(|(m, x)| ParsedRustType::Raw(m, Box::new(x)));
+        let fn_ptr = just(Token::KwFn)
+            .ignore_then(fn_args(parser))
+            .map(|(inputs, output)| ParsedRustType::FnPtr {
+                inputs,
+                output: Box::new(output),
+            });
+        let never = just(Token::Bang).to(ParsedRustType::Primitive(PrimitiveRustType::Never));
         choice((
-            scalar, boxed, unit, tuple, slice, adt, reference, raw_ptr, dyn_trait,
+            scalar, boxed, unit, tuple, slice, adt, reference, raw_ptr, dyn_trait, fn_ptr, never,
         ))
     })
     .boxed()
 }
 
+#[allow(clippy::type_complexity)]
 fn rust_generics<'a>(
     rust_type: Boxed<
         'a,
@@ -1085,8 +2048,7 @@ fn rust_trait<'a>(
         output: Box::new(x.1.1),
     });
 
-    let rust_trait = fn_trait.or(rust_path_and_generics(rust_type).map(ParsedRustTrait::Normal));
-    rust_trait
+    fn_trait.or(rust_path_and_generics(rust_type).map(ParsedRustTrait::Normal))
 }
 
 fn method<'a>()
@@ -1104,19 +2066,19 @@ fn method<'a>()
                 .or(empty().to(vec![])),
         )
         .then(fn_args(rust_type()))
-        .map(|((name, generics), args)| {
+        .map_with(|((name, generics), args), extra| {
             let is_self = |c: &ParsedRustType<'_>| {
                 if let ParsedRustType::Adt(c) = c {
                     c.path.start == ParsedPathStart::Relative
-                        && &c.path.segments == &["self"]
+                        && c.path.segments == ["self"]
                         && c.generics.is_empty()
                 } else {
                     false
                 }
             };
-            let (inputs, receiver) = match args.0.get(0) {
-                Some(x) if is_self(&x) => (args.0[1..].to_vec(), ZngurMethodReceiver::Move),
-                Some(ParsedRustType::Ref(m, x)) if is_self(&x) => {
+            let (inputs, receiver) = match args.0.first() {
+                Some(x) if is_self(x) => (args.0[1..].to_vec(), ZngurMethodReceiver::Move),
+                Some(ParsedRustType::Ref(m, x)) if is_self(x) => {
                     (args.0[1..].to_vec(), ZngurMethodReceiver::Ref(*m))
                 }
                 _ => (args.0, ZngurMethodReceiver::Static),
@@ -1127,10 +2089,18 @@ fn method<'a>()
                 generics,
                 inputs,
                 output: args.1,
+                span: extra.span(),
             }
         })
 }
 
+/// The operator trait of `std::ops` called `name`.
+fn operator_trait(name: &str) -> Option<ZngurOperatorTrait> {
+    ZngurOperatorTrait::ALL
+        .into_iter()
+        .find(|x| x.name() == name)
+}
+
 fn type_item<'a>()
 -> impl Parser<'a, ParserInput<'a>, ParsedItem<'a>, extra::Err<Rich<'a, Token<'a>, Span>>> + Clone {
     fn inner_item<'a>()
@@ -1140,29 +2110,83 @@ fn type_item<'a>()
             Token::Ident(c) => c,
         }))
         .then_ignore(just(Token::Eq))
-        .then(select! {
-            Token::Number(c) => c,
-        });
+        .then(spanned(select! {
+            Token::Number(c) => ParsedNumber::Literal(c),
+            Token::Ident(c) => ParsedNumber::Param(c),
+        }));
+        let properties = property_item
+            .separated_by(just(Token::Comma))
+            .collect::<Vec<_>>()
+            .delimited_by(just(Token::ParenOpen), just(Token::ParenClose));
         let layout = just([Token::Sharp, Token::Ident("layout")])
             .ignore_then(
-                property_item
-                    .separated_by(just(Token::Comma))
-                    .collect::<Vec<_>>()
-                    .delimited_by(just(Token::ParenOpen), just(Token::ParenClose)),
+                just([Token::ParenOpen, Token::Ident("auto"), Token::ParenClose])
+                    .to(ParsedLayoutPolicy::Auto),
             )
-            .map(ParsedLayoutPolicy::StackAllocated)
+            .or(just([Token::Sharp, Token::Ident("layout")])
+                .ignore_then(properties.clone().map(ParsedLayoutPolicy::StackAllocated)))
+            .or(just([Token::Sharp, Token::Ident("layout_conservative")])
+                .ignore_then(properties.map(ParsedLayoutPolicy::Conservative)))
             .or(just([Token::Sharp, Token::Ident("only_by_ref")]).to(ParsedLayoutPolicy::OnlyByRef))
             .or(just([Token::Sharp, Token::Ident("heap_allocated")])
                 .to(ParsedLayoutPolicy::HeapAllocated))
             .map_with(|x, extra| ParsedTypeItem::Layout(extra.span(), x))
             .boxed();
+        let output = just([Token::Ident("Output"), Token::Eq]).ignore_then(rust_type());
+        let operator_args = output
+            .clone()
+            .map(|output| (None, Some(output)))
+            .or(rust_type()
+                .then(just(Token::Comma).ignore_then(output).or_not())
+                .map(|(rhs, output)| (Some(rhs), output)))
+            .delimited_by(just(Token::AngleOpen), just(Token::AngleClose))
+            .or_not()
+            .map(Option::unwrap_or_default);
+        let operator_item = select! {
+            Token::Ident(c) if operator_trait(c).is_some() => operator_trait(c).unwrap(),
+        }
+        .then(operator_args)
+        .map(|(op, (rhs, output))| ParsedWellknownTrait::Operator { op, rhs, output });
+        let into_iterator_item = just(Token::And)
+            .ignore_then(
+                just(Token::KwMut)
+                    .to(Mutability::Mut)
+                    .or(empty().to(Mutability::Not)),
+            )
+            .or_not()
+            .then_ignore(just(Token::Ident("IntoIterator")))
+            .then(
+                just([Token::Ident("Item"), Token::Eq])
+                    .ignore_then(rust_type())
+                    .delimited_by(just(Token::AngleOpen), just(Token::AngleClose)),
+            )
+            .map(|(by_ref, item)| ParsedWellknownTrait::IntoIterator { by_ref, item });
+        let hash_item = just(Token::Ident("Hash"))
+            .ignore_then(
+                just([Token::Ident("Hasher"), Token::Eq])
+                    .ignore_then(rust_type())
+                    .delimited_by(just(Token::AngleOpen), just(Token::AngleClose))
+                    .or_not(),
+            )
+            .map(|hasher| ParsedWellknownTrait::Hash { hasher });
         let trait_item = select! {
             Token::Ident("Debug") => ZngurWellknownTrait::Debug,
+            Token::Ident("Display") => ZngurWellknownTrait::Display,
             Token::Ident("Copy") => ZngurWellknownTrait::Copy,
+            Token::Ident("Clone") => ZngurWellknownTrait::Clone,
+            Token::Ident("Default") => ZngurWellknownTrait::Default,
+            Token::Ident("PartialEq") => ZngurWellknownTrait::PartialEq,
+            Token::Ident("Eq") => ZngurWellknownTrait::Eq,
+            Token::Ident("PartialOrd") => ZngurWellknownTrait::PartialOrd,
+            Token::Ident("Ord") => ZngurWellknownTrait::Ord,
         }
         .or(just(Token::Question)
             .then(just(Token::Ident("Sized")))
-            .to(ZngurWellknownTrait::Unsized));
+            .to(ZngurWellknownTrait::Unsized))
+        .map(ParsedWellknownTrait::Simple)
+        .or(operator_item)
+        .or(into_iterator_item)
+        .or(hash_item);
         let traits = just(Token::Ident("wellknown_traits"))
             .ignore_then(
                 spanned(trait_item)
@@ -1236,7 +2260,7 @@ fn type_item<'a>()
                 Token::Str(c) => c,
             })
             .map(|x| ParsedTypeItem::CppRef { cpp_type: x });
-        choice((
+        let item = choice((
             layout,
             traits,
             constructor,
@@ -1263,32 +2287,36 @@ fn type_item<'a>()
                 }),
         ))
         .then_ignore(just(Token::Semicolon))
+        .boxed();
+        cfg_attribute()
+            .then(item.clone())
+            .map(|(predicate, item)| ParsedTypeItem::Cfg(predicate, Box::new(item)))
+            .or(item)
     }
-    just(Token::KwType)
-        .ignore_then(spanned(rust_type()))
-        .then(
-            spanned(inner_item())
-                .repeated()
-                .collect::<Vec<_>>()
-                .delimited_by(just(Token::BraceOpen), just(Token::BraceClose)),
-        )
-        .map(|(ty, items)| ParsedItem::Type { ty, items })
-        .boxed()
+    keyword_item(
+        Token::KwType,
+        spanned(rust_type()).then(recovering_block(spanned(inner_item()), |span| {
+            Some(Spanned {
+                inner: ParsedTypeItem::Error,
+                span,
+            })
+        })),
+        |(ty, items), span| ParsedItem::Type { ty, items, span },
+    )
+    .boxed()
 }
 
 fn trait_item<'a>()
 -> impl Parser<'a, ParserInput<'a>, ParsedItem<'a>, extra::Err<Rich<'a, Token<'a>, Span>>> + Clone {
-    just(Token::KwTrait)
-        .ignore_then(rust_trait(rust_type()))
-        .then(
-            method()
-                .then_ignore(just(Token::Semicolon))
-                .repeated()
-                .collect::<Vec<_>>()
-                .delimited_by(just(Token::BraceOpen), just(Token::BraceClose)),
-        )
-        .map(|(tr, methods)| ParsedItem::Trait { tr, methods })
-        .boxed()
+    keyword_item(
+        Token::KwTrait,
+        spanned(rust_trait(rust_type())).then(recovering_block(
+            method().then_ignore(just(Token::Semicolon)),
+            |_| None,
+        )),
+        |(tr, methods), span| ParsedItem::Trait { tr, methods, span },
+    )
+    .boxed()
 }
 
 fn fn_item<'a>()
@@ -1307,11 +2335,35 @@ fn additional_include_item<'a>()
                     Token::Str(c) => ParsedItem::CppAdditionalInclude(c),
                 })
                 .or(just(Token::Ident("convert_panic_to_exception"))
-                    .to(ParsedItem::ConvertPanicToException)),
+                    .to(ParsedItem::ConvertPanicToException))
+                .or(just(Token::Ident("allow"))
+                    .ignore_then(
+                        spanned(select! {
+                            Token::Ident(c) => c,
+                        })
+                        .separated_by(just(Token::Comma))
+                        .allow_trailing()
+                        .at_least(1)
+                        .collect::<Vec<_>>()
+                        .delimited_by(just(Token::ParenOpen), just(Token::ParenClose)),
+                    )
+                    .then_ignore(just(Token::Semicolon))
+                    .map(ParsedItem::Allow)),
         )
         .boxed()
 }
 
+fn import_item<'a>()
+-> impl Parser<'a, ParserInput<'a>, ParsedItem<'a>, extra::Err<Rich<'a, Token<'a>, Span>>> + Clone {
+    just(Token::Ident("import"))
+        .ignore_then(spanned(select! {
+            Token::Str(c) => c,
+        }))
+        .then_ignore(just(Token::Semicolon))
+        .map(ParsedItem::Import)
+        .boxed()
+}
+
 fn extern_cpp_item<'a>()
 -> impl Parser<'a, ParserInput<'a>, ParsedItem<'a>, extra::Err<Rich<'a, Token<'a>, Span>>> + Clone {
     let function = method()
@@ -1323,47 +2375,164 @@ fn extern_cpp_item<'a>()
                 .then_ignore(just(Token::KwFor))
                 .map(Some)
                 .or(empty().to(None))
-                .then(rust_type()),
-        )
-        .then(
-            method()
-                .then_ignore(just(Token::Semicolon))
-                .repeated()
-                .collect::<Vec<_>>()
-                .delimited_by(just(Token::BraceOpen), just(Token::BraceClose)),
+                .then(spanned(rust_type())),
         )
+        .then(recovering_block(
+            method().then_ignore(just(Token::Semicolon)),
+            |_| None,
+        ))
         .map(|((tr, ty), methods)| ParsedExternCppItem::Impl { tr, ty, methods });
     just(Token::KwExtern)
         .then(just(Token::Str("C++")))
         .ignore_then(
-            function
-                .or(impl_block)
+            recovering_block(function.or(impl_block), |_| None)
+                .map(ParsedItem::ExternCpp)
+                .recover_with(via_parser(skip_item().to(ParsedItem::Error))),
+        )
+        .boxed()
+}
+
+/// Parser for `#for T in [A, B] { ... }` and `#for (T, N) in [(A, 1), (B, 2)] { ... }` blocks.
+fn for_item<'a>(
+    item: impl Parser<'a, ParserInput<'a>, ParsedItem<'a>, extra::Err<Rich<'a, Token<'a>, Span>>>
+    + Clone
+    + 'a,
+) -> impl Parser<'a, ParserInput<'a>, ParsedItem<'a>, extra::Err<Rich<'a, Token<'a>, Span>>> + Clone
+{
+    let param = spanned(select! {
+        Token::Ident(c) => c,
+    });
+    let arg = select! {
+        Token::Number(c) => ParsedTemplateArg::Number(c),
+    }
+    .or(rust_type().map(ParsedTemplateArg::Type));
+    let single = param
+        .clone()
+        .map(|x| vec![x])
+        .then_ignore(just(Token::Ident("in")))
+        .then(
+            spanned(arg.clone().map(|x| vec![x]))
+                .separated_by(just(Token::Comma))
+                .allow_trailing()
+                .collect::<Vec<_>>()
+                .delimited_by(just(Token::BracketOpen), just(Token::BracketClose)),
+        );
+    let multiple = param
+        .separated_by(just(Token::Comma))
+        .at_least(1)
+        .collect::<Vec<_>>()
+        .delimited_by(just(Token::ParenOpen), just(Token::ParenClose))
+        .then_ignore(just(Token::Ident("in")))
+        .then(
+            spanned(
+                arg.separated_by(just(Token::Comma))
+                    .collect::<Vec<_>>()
+                    .delimited_by(just(Token::ParenOpen), just(Token::ParenClose)),
+            )
+            .separated_by(just(Token::Comma))
+            .allow_trailing()
+            .collect::<Vec<_>>()
+            .delimited_by(just(Token::BracketOpen), just(Token::BracketClose)),
+        );
+    keyword_item(
+        [Token::Sharp, Token::KwFor],
+        multiple.or(single).then(
+            item.recover_with(via_parser(skip_item().to(ParsedItem::Error)))
                 .repeated()
                 .collect::<Vec<_>>()
-                .delimited_by(just(Token::BraceOpen), just(Token::BraceClose))
-                .boxed(),
+                .delimited_by(just(Token::BraceOpen), just(Token::BraceClose)),
+        ),
+        |((params, rows), items), span| ParsedItem::For {
+            params,
+            rows,
+            items,
+            span,
+        },
+    )
+    .boxed()
+}
+
+/// Parser for the predicate of `#cfg(...)` attributes, like `any(unix, feature = "x")`.
+fn cfg_predicate<'a>()
+-> impl Parser<'a, ParserInput<'a>, CfgPredicate, extra::Err<Rich<'a, Token<'a>, Span>>> + Clone {
+    recursive(|predicate| {
+        let list = predicate
+            .clone()
+            .separated_by(just(Token::Comma))
+            .allow_trailing()
+            .collect::<Vec<_>>()
+            .delimited_by(just(Token::ParenOpen), just(Token::ParenClose));
+        let option = select! {
+            Token::Ident(c) => c,
+        }
+        .then(
+            just(Token::Eq)
+                .ignore_then(select! {
+                    Token::Str(c) => c,
+                })
+                .or_not(),
         )
-        .map(ParsedItem::ExternCpp)
-        .boxed()
+        .map(|(name, value)| CfgPredicate::Option {
+            name: name.to_owned(),
+            value: value.map(str::to_owned),
+        });
+        choice((
+            just(Token::Ident("any"))
+                .ignore_then(list.clone())
+                .map(CfgPredicate::Any),
+            just(Token::Ident("all"))
+                .ignore_then(list)
+                .map(CfgPredicate::All),
+            just(Token::Ident("not"))
+                .ignore_then(
+                    predicate.delimited_by(just(Token::ParenOpen), just(Token::ParenClose)),
+                )
+                .map(|x| CfgPredicate::Not(Box::new(x))),
+            option,
+        ))
+    })
+    .boxed()
+}
+
+/// Parser for `#cfg(predicate)`.
+fn cfg_attribute<'a>()
+-> impl Parser<'a, ParserInput<'a>, CfgPredicate, extra::Err<Rich<'a, Token<'a>, Span>>> + Clone {
+    just([Token::Sharp, Token::Ident("cfg")])
+        .ignore_then(cfg_predicate().delimited_by(just(Token::ParenOpen), just(Token::ParenClose)))
 }
 
 fn item<'a>()
 -> impl Parser<'a, ParserInput<'a>, ParsedItem<'a>, extra::Err<Rich<'a, Token<'a>, Span>>> + Clone {
     recursive(|item| {
         choice((
-            just(Token::KwMod)
-                .ignore_then(path())
-                .then(
-                    item.repeated()
+            keyword_item(
+                Token::KwMod,
+                path().then(
+                    item.clone()
+                        .recover_with(via_parser(skip_item().to(ParsedItem::Error)))
+                        .repeated()
                         .collect::<Vec<_>>()
                         .delimited_by(just(Token::BraceOpen), just(Token::BraceClose)),
-                )
-                .map(|(path, items)| ParsedItem::Mod { path, items }),
+                ),
+                |(path, items), span| ParsedItem::Mod { path, items, span },
+            ),
             type_item(),
             trait_item(),
             extern_cpp_item(),
             fn_item(),
+            for_item(item.clone()),
+            keyword_item(
+                [Token::Sharp, Token::Ident("cfg")],
+                cfg_predicate()
+                    .delimited_by(just(Token::ParenOpen), just(Token::ParenClose))
+                    .then(item.clone()),
+                |(predicate, item), _| ParsedItem::Cfg {
+                    predicate,
+                    item: Box::new(item),
+                },
+            ),
             additional_include_item(),
+            import_item(),
             alias(),
         ))
     })
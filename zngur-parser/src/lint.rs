@@ -0,0 +1,193 @@
+use std::{collections::HashMap, fmt::Display};
+
+use zngur_def::*;
+
+use crate::{Diagnostic, LabelKind};
+
+/// Warnings about zng files that are valid, but probably not what the user meant. Each of them can
+/// be silenced with `#allow(name);` anywhere in the zng file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Lint {
+    /// A type is used in a signature, but is not declared with a `type` block.
+    UndeclaredType,
+    /// A `use ... as ...` alias is never used.
+    UnusedAlias,
+}
+
+impl Lint {
+    pub const ALL: [Lint; 2] = [Lint::UndeclaredType, Lint::UnusedAlias];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Lint::UndeclaredType => "undeclared_type",
+            Lint::UnusedAlias => "unused_alias",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Lint> {
+        Lint::ALL.into_iter().find(|x| x.name() == name)
+    }
+}
+
+impl Display for Lint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Finds mistakes in a [`ZngurFile`] that would otherwise only show up when compiling the generated
+/// code. Warnings of the lints in `zng.allowed_lints` are not reported.
+///
+/// [`ZngParser::parse`](crate::ZngParser::parse) runs this automatically, and additionally reports
+/// unused aliases, which are not visible in a `ZngurFile`.
+pub fn lint(zng: &ZngurFile) -> Vec<Diagnostic> {
+    let mut linter = Linter {
+        zng,
+        declared: HashMap::new(),
+        reported_types: vec![],
+        diagnostics: vec![],
+    };
+    linter.check_duplicate_types();
+    for ty_def in &zng.types {
+        linter.check_constructor_fields(ty_def);
+        for method in &ty_def.methods {
+            linter.check_signature(&method.data);
+        }
+        for constructor in &ty_def.constructors {
+            for (_, ty) in &constructor.inputs {
+                linter.check_declared(ty, constructor.span.as_ref());
+            }
+        }
+        for field in &ty_def.fields {
+            linter.check_declared(&field.ty, field.span.as_ref());
+        }
+    }
+    let mut traits = zng.traits.values().collect::<Vec<_>>();
+    traits.sort_by_key(|x| x.span.as_ref().map(|x| (x.file.clone(), x.start)));
+    for tr in traits {
+        for method in &tr.methods {
+            linter.check_signature(method);
+        }
+    }
+    for func in &zng.funcs {
+        for ty in func.inputs.iter().chain(Some(&func.output)) {
+            linter.check_declared(ty, func.span.as_ref());
+        }
+    }
+    for func in &zng.extern_cpp_funcs {
+        for ty in func.inputs.iter().chain(Some(&func.output)) {
+            linter.check_declared(ty, func.span.as_ref());
+        }
+    }
+    for impl_block in &zng.extern_cpp_impls {
+        linter.check_declared(&impl_block.ty, impl_block.span.as_ref());
+        for method in &impl_block.methods {
+            linter.check_signature(method);
+        }
+    }
+    linter.diagnostics.retain(|d| match d.lint {
+        Some(lint) => !zng.allowed_lints.iter().any(|x| x == lint.name()),
+        None => true,
+    });
+    linter.diagnostics
+}
+
+struct Linter<'a> {
+    zng: &'a ZngurFile,
+    /// The first declaration of each type.
+    declared: HashMap<&'a RustType, &'a ZngurType>,
+    /// Undeclared types that are already reported, so that each of them is reported only once.
+    reported_types: Vec<&'a RustType>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> Linter<'a> {
+    fn check_duplicate_types(&mut self) {
+        for ty_def in &self.zng.types {
+            let Some(first) = self.declared.get(&ty_def.ty) else {
+                self.declared.insert(&ty_def.ty, ty_def);
+                continue;
+            };
+            let message = format!("Type `{}` is declared more than once", ty_def.ty);
+            self.diagnostics.push(
+                Diagnostic::error(&message)
+                    .with_zngur_label(LabelKind::Primary, ty_def.span.as_ref(), &message)
+                    .with_zngur_label(
+                        LabelKind::Secondary,
+                        first.span.as_ref(),
+                        "First declared here",
+                    ),
+            );
+        }
+    }
+
+    /// Fields declared with `field` items are known to exist, so struct constructors must take all
+    /// of them with the same types.
+    fn check_constructor_fields(&mut self, ty_def: &ZngurType) {
+        // Fields of enum variants can't be declared with `field` items.
+        for constructor in ty_def.constructors.iter().filter(|x| x.name.is_none()) {
+            let span = constructor.span.as_ref();
+            for field in &ty_def.fields {
+                let message = match constructor.inputs.iter().find(|x| x.0 == field.name) {
+                    None => format!(
+                        "Constructor of `{}` doesn't take field `{}`",
+                        ty_def.ty, field.name
+                    ),
+                    Some((_, ty)) if *ty != field.ty => format!(
+                        "Constructor of `{}` takes field `{}` as `{ty}`, but it is declared as `{}`",
+                        ty_def.ty, field.name, field.ty
+                    ),
+                    Some(_) => continue,
+                };
+                self.diagnostics.push(
+                    Diagnostic::error(&message)
+                        .with_zngur_label(LabelKind::Primary, span, &message)
+                        .with_zngur_label(
+                            LabelKind::Secondary,
+                            field.span.as_ref(),
+                            "Field declared here",
+                        ),
+                );
+            }
+        }
+    }
+
+    fn check_signature(&mut self, method: &'a ZngurMethod) {
+        for ty in method.inputs.iter().chain(Some(&method.output)) {
+            self.check_declared(ty, method.span.as_ref());
+        }
+    }
+
+    /// Checks that the C++ side will know about `ty`. Scalars and pointers to them are built in,
+    /// the unit type is declared implicitly, function pointers only need their argument and return
+    /// types, and everything else needs a `type` block.
+    fn check_declared(&mut self, ty: &'a RustType, span: Option<&ZngurSpan>) {
+        match ty {
+            RustType::Primitive(
+                PrimitiveRustType::Bool | PrimitiveRustType::Char | PrimitiveRustType::Str,
+            ) => (),
+            RustType::Primitive(_) | RustType::Raw(..) => return,
+            RustType::Ref(_, inner) => return self.check_declared(inner, span),
+            RustType::Tuple(fields) if fields.is_empty() => return,
+            RustType::FnPtr { inputs, output } => {
+                for ty in inputs.iter().chain(Some(&**output)) {
+                    self.check_declared(ty, span);
+                }
+                return;
+            }
+            _ => (),
+        }
+        if self.declared.contains_key(ty) || self.reported_types.contains(&ty) {
+            return;
+        }
+        self.reported_types.push(ty);
+        let message = format!("Type `{ty}` is used, but it is not declared with a `type` block");
+        self.diagnostics.push(
+            Diagnostic::lint(Lint::UndeclaredType, &message).with_zngur_label(
+                LabelKind::Primary,
+                span,
+                &message,
+            ),
+        );
+    }
+}
@@ -1,26 +1,38 @@
-use std::panic::catch_unwind;
-
 use expect_test::{Expect, expect};
-use zngur_def::{RustPathAndGenerics, RustType};
+use zngur_def::{
+    LayoutPolicy, Mutability, PrimitiveRustType, RustPathAndGenerics, RustType, ZngurFile,
+    ZngurOperator, ZngurOperatorTrait, ZngurWellknownTrait,
+};
 
-use crate::ParsedZngFile;
+use crate::{
+    AriadneRenderer, Cfg, Diagnostic, ParsedZngFile, ReferenceTarget, Symbol, ZngParser,
+    ZngurFileExt, format_zng,
+};
 
-fn check_success(zng: &str) {
-    let _ = ParsedZngFile::parse("main.zng", zng);
+fn render(zng: &str, diagnostics: &[Diagnostic]) -> String {
+    let mut r = Vec::<u8>::new();
+    AriadneRenderer::new()
+        .with_source("main.zng", zng)
+        .with_color(false)
+        .write(diagnostics, &mut r)
+        .unwrap();
+    String::from_utf8(strip_ansi_escapes::strip(r)).unwrap()
 }
 
-pub struct ErrorText(pub String);
+fn check_success(zng: &str) -> ZngurFile {
+    match ParsedZngFile::parse("main.zng", zng) {
+        Ok(r) => r,
+        Err(diagnostics) => panic!(
+            "Parsing failed but we expected success:\n{}",
+            render(zng, &diagnostics)
+        ),
+    }
+}
 
 fn check_fail(zng: &str, error: Expect) {
-    let r = catch_unwind(|| {
-        let _ = ParsedZngFile::parse("main.zng", zng);
-    });
-    match r {
+    match ParsedZngFile::parse("main.zng", zng) {
         Ok(_) => panic!("Parsing succeeded but we expected fail"),
-        Err(e) => match e.downcast::<ErrorText>() {
-            Ok(t) => error.assert_eq(&t.0),
-            Err(e) => std::panic::resume_unwind(e),
-        },
+        Err(diagnostics) => error.assert_eq(&render(zng, &diagnostics)),
     }
 }
 
@@ -56,6 +68,64 @@ type (i8, u8) {
     );
 }
 
+#[test]
+fn parse_array() {
+    let parsed = check_success(
+        r#"
+type [[u8; 4]; 2] {
+    #layout(size = 8, align = 1);
+}
+mod crate {
+    fn hash(&[u8]) -> [u8; 32];
+}
+    "#,
+    );
+    assert_eq!(parsed.types[0].ty.to_string(), "[[u8; 4]; 2]");
+    assert_eq!(parsed.funcs[0].output.to_string(), "[u8; 32]");
+}
+
+#[test]
+fn parse_fn_pointer() {
+    let parsed = check_success(
+        r#"
+mod crate {
+    fn sort_by(&mut [u32], fn(&u32, &u32) -> bool);
+    fn on_exit(fn());
+    fn comparator() -> fn(&u32, &u32) -> bool;
+}
+    "#,
+    );
+    assert_eq!(
+        parsed.funcs[0].inputs[1].to_string(),
+        "fn(&u32, &u32) -> bool"
+    );
+    assert_eq!(parsed.funcs[1].inputs[0].to_string(), "fn()");
+    assert_eq!(parsed.funcs[2].output.to_string(), "fn(&u32, &u32) -> bool");
+}
+
+#[test]
+fn parse_primitives() {
+    let parsed = check_success(
+        r#"
+mod crate {
+    fn f(isize, char, i128, u128) -> !;
+}
+    "#,
+    );
+    assert_eq!(
+        parsed.funcs[0]
+            .inputs
+            .iter()
+            .map(|x| x.to_string())
+            .collect::<Vec<_>>(),
+        ["isize", "char", "i128", "u128"]
+    );
+    assert_eq!(
+        parsed.funcs[0].output,
+        RustType::Primitive(PrimitiveRustType::Never)
+    );
+}
+
 #[test]
 fn typo_in_wellknown_trait() {
     check_fail(
@@ -66,12 +136,19 @@ type () {
 }
     "#,
         expect![[r#"
-            Error: found 'welcome_traits' expected 'layout', '#', 'wellknown_traits', 'constructor', 'fn', or '}'
+            Error: found 'welcome_traits' expected '#', 'wellknown_traits', 'constructor', 'field', or 'fn'
                ╭─[main.zng:4:5]
                │
              4 │     welcome_traits(Copy);
                │     ───────┬──────  
-               │            ╰──────── found 'welcome_traits' expected 'layout', '#', 'wellknown_traits', 'constructor', 'fn', or '}'
+               │            ╰──────── found 'welcome_traits' expected '#', 'wellknown_traits', 'constructor', 'field', or 'fn'
+            ───╯
+            Error: Unit type is declared implicitly. Remove this entirely.
+               ╭─[main.zng:2:6]
+               │
+             2 │ type () {
+               │      ─┬  
+               │       ╰── Unit type is declared implicitly. Remove this entirely.
             ───╯
         "#]],
     );
@@ -98,6 +175,283 @@ type ::std::string::String {
     );
 }
 
+#[test]
+fn layout_auto() {
+    let parsed = check_success(
+        r#"
+type crate::Foo {
+    #layout(auto);
+    wellknown_traits(Copy);
+}
+    "#,
+    );
+    assert_eq!(parsed.types[0].layout, LayoutPolicy::Auto);
+    check_fail(
+        r#"
+type crate::Foo {
+    #layout(auto);
+    #layout(size = 1, align = 1);
+}
+    "#,
+        expect![[r#"
+            Error: Duplicate layout policy found
+               ╭─[main.zng:4:5]
+               │
+             4 │     #layout(size = 1, align = 1);
+               │     ──────────────┬─────────────  
+               │                   ╰─────────────── Duplicate layout policy found
+            ───╯
+        "#]],
+    );
+}
+
+#[test]
+fn layout_conservative() {
+    let parsed = check_success(
+        r#"
+#for(N)in[(32)]{
+type crate::Foo {
+    #layout_conservative(size = N, align = 8);
+}
+}
+    "#,
+    );
+    assert_eq!(
+        parsed.types[0].layout,
+        LayoutPolicy::Conservative { size: 32, align: 8 }
+    );
+    check_fail(
+        r#"
+type crate::Foo {
+    #layout_conservative(size = 32);
+}
+    "#,
+        expect![[r#"
+            Error: Align is not declared for this type
+               ╭─[main.zng:2:6]
+               │
+             2 │ type crate::Foo {
+               │      ─────┬────  
+               │           ╰────── Align is not declared for this type
+            ───╯
+        "#]],
+    );
+}
+
+#[test]
+fn comparison_traits() {
+    let parsed = check_success(
+        r#"
+type crate::Foo {
+    #layout(size = 8, align = 8);
+    wellknown_traits(PartialEq, Eq, PartialOrd, Ord);
+}
+    "#,
+    );
+    assert_eq!(
+        parsed.types[0].wellknown_traits,
+        [
+            ZngurWellknownTrait::PartialEq,
+            ZngurWellknownTrait::Eq,
+            ZngurWellknownTrait::PartialOrd,
+            ZngurWellknownTrait::Ord,
+            ZngurWellknownTrait::Drop,
+        ]
+    );
+    check_fail(
+        r#"
+type crate::Foo {
+    #layout(size = 8, align = 8);
+    wellknown_traits(PartialOrd, Ord);
+}
+    "#,
+        expect![[r#"
+            Error: `PartialOrd` requires `PartialEq`
+               ╭─[main.zng:4:22]
+               │
+             4 │     wellknown_traits(PartialOrd, Ord);
+               │                      ─────┬────  
+               │                           ╰────── `PartialOrd` requires `PartialEq`
+            ───╯
+            Error: `Ord` requires `Eq`
+               ╭─[main.zng:4:34]
+               │
+             4 │     wellknown_traits(PartialOrd, Ord);
+               │                                  ─┬─  
+               │                                   ╰─── `Ord` requires `Eq`
+            ───╯
+        "#]],
+    );
+}
+
+#[test]
+fn operator_traits() {
+    let parsed = check_success(
+        r#"
+mod crate {
+    type Money {
+        #layout(size = 8, align = 8);
+        wellknown_traits(Add<Output = Money>, Mul<i64, Output = Money>, Neg<Output = Money>);
+        wellknown_traits(AddAssign, ShlAssign<u32>);
+    }
+}
+    "#,
+    );
+    let traits = parsed.types[0]
+        .wellknown_traits
+        .iter()
+        .map(|x| x.to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        traits,
+        [
+            "Add<Output = crate::Money>",
+            "Mul<i64, Output = crate::Money>",
+            "Neg<Output = crate::Money>",
+            "AddAssign",
+            "ShlAssign<u32>",
+            "Drop",
+        ]
+    );
+    check_fail(
+        r#"
+type crate::Money {
+    #layout(size = 8, align = 8);
+    wellknown_traits(Add, Not<bool, Output = bool>, SubAssign<i64, Output = i64>);
+}
+    "#,
+        expect![[r#"
+            Error: The `Output` type of `Add` is not declared, like `Add<Output = T>`
+               ╭─[main.zng:4:22]
+               │
+             4 │     wellknown_traits(Add, Not<bool, Output = bool>, SubAssign<i64, Output = i64>);
+               │                      ─┬─  
+               │                       ╰─── The `Output` type of `Add` is not declared, like `Add<Output = T>`
+            ───╯
+            Error: `Not` has no `Rhs` type
+               ╭─[main.zng:4:27]
+               │
+             4 │     wellknown_traits(Add, Not<bool, Output = bool>, SubAssign<i64, Output = i64>);
+               │                           ────────────┬───────────  
+               │                                       ╰───────────── `Not` has no `Rhs` type
+            ───╯
+            Error: `SubAssign` has no `Output` type
+               ╭─[main.zng:4:53]
+               │
+             4 │     wellknown_traits(Add, Not<bool, Output = bool>, SubAssign<i64, Output = i64>);
+               │                                                     ──────────────┬─────────────  
+               │                                                                   ╰─────────────── `SubAssign` has no `Output` type
+            ───╯
+        "#]],
+    );
+}
+
+#[test]
+fn index_traits() {
+    let parsed = check_success(
+        r#"
+type ::std::vec::Vec<i32> {
+    #layout(size = 24, align = 8);
+    wellknown_traits(Index<usize, Output = i32>, IndexMut<usize, Output = i32>);
+}
+    "#,
+    );
+    assert_eq!(
+        parsed.types[0].wellknown_traits[1],
+        ZngurWellknownTrait::Operator(ZngurOperator {
+            op: ZngurOperatorTrait::IndexMut,
+            rhs: Some(RustType::Primitive(PrimitiveRustType::Usize)),
+            output: Some(RustType::Primitive(PrimitiveRustType::Int(32))),
+        })
+    );
+    check_fail(
+        r#"
+type ::std::vec::Vec<i32> {
+    #layout(size = 24, align = 8);
+    wellknown_traits(Index<Output = i32>);
+}
+    "#,
+        expect![[r#"
+            Error: The `Idx` type of `Index` is not declared, like `Index<usize, Output = T>`
+               ╭─[main.zng:4:22]
+               │
+             4 │     wellknown_traits(Index<Output = i32>);
+               │                      ─────────┬─────────  
+               │                               ╰─────────── The `Idx` type of `Index` is not declared, like `Index<usize, Output = T>`
+            ───╯
+        "#]],
+    );
+}
+
+#[test]
+fn into_iterator_trait() {
+    let parsed = check_success(
+        r#"
+type ::std::vec::Vec<i32> {
+    #layout(size = 24, align = 8);
+    wellknown_traits(IntoIterator<Item = i32>, &IntoIterator<Item = &i32>);
+    wellknown_traits(&mut IntoIterator<Item = &mut i32>);
+}
+    "#,
+    );
+    let traits = parsed.types[0]
+        .wellknown_traits
+        .iter()
+        .map(|x| x.to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        traits,
+        [
+            "IntoIterator<Item = i32>",
+            "&IntoIterator<Item = &i32>",
+            "&mut IntoIterator<Item = &mut i32>",
+            "Drop",
+        ]
+    );
+}
+
+#[test]
+fn hash_trait() {
+    let parsed = check_success(
+        r#"
+type crate::Foo {
+    #layout(size = 8, align = 8);
+    wellknown_traits(PartialEq, Eq, Hash);
+}
+
+type crate::Bar {
+    #layout(size = 8, align = 8);
+    wellknown_traits(PartialEq, Eq, Hash<Hasher = crate::FxHasher>);
+}
+    "#,
+    );
+    assert_eq!(
+        parsed.types[0].wellknown_traits[2],
+        ZngurWellknownTrait::Hash { hasher: None }
+    );
+    assert_eq!(
+        parsed.types[1].wellknown_traits[2].to_string(),
+        "Hash<Hasher = crate::FxHasher>"
+    );
+    check_fail(
+        r#"
+type crate::Foo {
+    #layout(size = 8, align = 8);
+    wellknown_traits(PartialEq, Hash);
+}
+    "#,
+        expect![[r#"
+            Error: `Hash` requires `Eq`
+               ╭─[main.zng:4:33]
+               │
+             4 │     wellknown_traits(PartialEq, Hash);
+               │                                 ──┬─  
+               │                                   ╰─── `Hash` requires `Eq`
+            ───╯
+        "#]],
+    );
+}
+
 #[test]
 fn cpp_ref_should_not_need_layout_info() {
     check_fail(
@@ -113,8 +467,8 @@ type crate::Way {
                ╭─[main.zng:3:5]
                │
              3 │     #layout(size = 1, align = 2);
-               │     ─────────────┬─────────────  
-               │                  ╰─────────────── Duplicate layout policy found
+               │     ──────────────┬─────────────  
+               │                   ╰─────────────── Duplicate layout policy found
             ───╯
         "#]],
     );
@@ -129,8 +483,7 @@ type crate::Way {
 
 #[test]
 fn alias_expands_correctly() {
-    let parsed = ParsedZngFile::parse(
-        "main.zng",
+    let parsed = check_success(
         r#"
 use ::std::string::String as MyString;
 type MyString {
@@ -147,8 +500,7 @@ type MyString {
 
 #[test]
 fn alias_expands_nearest_scope_first() {
-    let parsed = ParsedZngFile::parse(
-        "main.zng",
+    let parsed = check_success(
         r#"
 use ::std::string::String as MyString;
 mod crate {
@@ -165,3 +517,1092 @@ mod crate {
     };
     assert_eq!(p.as_slice(), ["crate", "MyLocalString"]);
 }
+
+#[test]
+fn for_instantiates_items() {
+    let parsed = check_success(
+        r#"
+mod ::std {
+    #for (T, SIZE, ALIGN) in [(i32, 8, 4), (u8, 2, 1)] {
+        type option::Option<T> {
+            #layout(size = SIZE, align = ALIGN);
+            fn unwrap(self) -> T;
+        }
+    }
+    #for T in [i32, &u8] {
+        type vec::Vec<T> {
+            #layout(size = 24, align = 8);
+            fn push(&mut self, T);
+        }
+    }
+}
+    "#,
+    );
+    let types = parsed
+        .types
+        .iter()
+        .map(|x| {
+            let m = &x.methods[0].data;
+            let sig = m.inputs.iter().chain([&m.output]).map(|t| t.to_string());
+            format!(
+                "{} {:?} {}",
+                x.ty,
+                x.layout,
+                sig.collect::<Vec<_>>().join(",")
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        types,
+        [
+            "::std::option::Option::<i32> StackAllocated { size: 8, align: 4 } i32",
+            "::std::option::Option::<u8> StackAllocated { size: 2, align: 1 } u8",
+            "::std::vec::Vec::<i32> StackAllocated { size: 24, align: 8 } i32,()",
+            "::std::vec::Vec::<&u8> StackAllocated { size: 24, align: 8 } &u8,()",
+        ]
+    );
+}
+
+#[test]
+fn for_errors_point_to_instantiation() {
+    check_fail(
+        r#"
+#for (T, SIZE) in [(i32, 4), (u8, u8), (u16)] {
+    type crate::Foo<T> {
+        #layout(size = SIZE, align = 1);
+    }
+}
+    "#,
+        expect![[r#"
+            Error: `SIZE` is not a `#for` parameter with a number value
+               ╭─[main.zng:4:24]
+               │
+             2 │ #for (T, SIZE) in [(i32, 4), (u8, u8), (u16)] {
+               │                              ────┬───  
+               │                                  ╰───── In this instantiation of `#for`
+               │ 
+             4 │         #layout(size = SIZE, align = 1);
+               │                        ──┬─  
+               │                          ╰─── `SIZE` is not a `#for` parameter with a number value
+            ───╯
+            Error: Expected 2 values for the parameters of `#for`, found 1
+               ╭─[main.zng:2:40]
+               │
+             2 │ #for (T, SIZE) in [(i32, 4), (u8, u8), (u16)] {
+               │                                        ──┬──  
+               │                                          ╰──── Expected 2 values for the parameters of `#for`, found 1
+            ───╯
+        "#]],
+    );
+}
+
+#[test]
+fn cfg_selects_items() {
+    let zng = r#"
+mod crate {
+    type Foo {
+        #cfg(target_pointer_width = "64")
+        #layout(size = 16, align = 8);
+        #cfg(target_pointer_width = "32")
+        #layout(size = 8, align = 4);
+
+        #cfg(any(feature = "fast-path", windows))
+        fn fast(&self);
+    }
+    #cfg(not(feature = "fast-path"))
+    type Slow {
+        #layout(size = 1, align = 1);
+    }
+}
+    "#;
+    let summary = |cfg: Cfg| {
+        let parsed = ZngParser::new()
+            .with_cfg(cfg)
+            .parse("main.zng", zng)
+            .unwrap();
+        let types = parsed.types.iter().map(|x| {
+            let methods = x.methods.iter().map(|m| format!(" {}", m.data.name));
+            format!("{} {:?}{}", x.ty, x.layout, methods.collect::<String>())
+        });
+        let cfgs = parsed.cfgs.iter().map(|(p, r)| format!("{p}: {r}"));
+        types.chain(cfgs).collect::<Vec<_>>()
+    };
+    let mut cfg = Cfg::new();
+    cfg.insert("target_pointer_width", Some("32"));
+    cfg.insert("feature", Some("FAST_PATH"));
+    assert_eq!(
+        summary(cfg),
+        [
+            "crate::Foo StackAllocated { size: 8, align: 4 } fast",
+            "not(feature = \"fast-path\"): false",
+            "target_pointer_width = \"64\": false",
+            "target_pointer_width = \"32\": true",
+            "any(feature = \"fast-path\", windows): true",
+        ]
+    );
+    let mut cfg = Cfg::new();
+    cfg.insert("target_pointer_width", Some("64"));
+    assert_eq!(
+        summary(cfg)[..2],
+        [
+            "crate::Foo StackAllocated { size: 16, align: 8 }",
+            "crate::Slow StackAllocated { size: 1, align: 1 }",
+        ]
+    );
+}
+
+#[test]
+fn builder_matches_zng_file() {
+    let parsed = check_success(
+        r#"
+type ::std::vec::Vec<i32> {
+    #layout(size = 24, align = 8);
+    wellknown_traits(Debug, Display, Clone, Default, PartialEq, Eq, Hash, &IntoIterator<Item = &i32>);
+    fn new() -> ::std::vec::Vec<i32>;
+    fn push(&mut self, i32);
+}
+
+type crate::Point {
+    #heap_allocated;
+    wellknown_traits(Add<Output = crate::Point>, MulAssign<i32>);
+    constructor { x: i32, y: i32 };
+    field x (offset = 0, type = i32);
+}
+
+type str {
+    wellknown_traits(?Sized);
+}
+
+mod crate {
+    fn sum(&[i32]) -> i32;
+}
+    "#,
+    );
+    let built = ZngurFile::builder()
+        .ty("::std::vec::Vec<i32>")
+        .layout(24, 8)
+        .wellknown_traits(&[
+            ZngurWellknownTrait::Debug,
+            ZngurWellknownTrait::Display,
+            ZngurWellknownTrait::Clone,
+            ZngurWellknownTrait::Default,
+            ZngurWellknownTrait::PartialEq,
+            ZngurWellknownTrait::Eq,
+        ])
+        .hash(None)
+        .into_iterator(Some(Mutability::Not), "&i32")
+        .method("fn new() -> ::std::vec::Vec<i32>")
+        .method("fn push(&mut self, i32);")
+        .ty("crate::Point")
+        .heap_allocated()
+        .operator(ZngurOperatorTrait::Add, None, Some("crate::Point"))
+        .operator(ZngurOperatorTrait::MulAssign, Some("i32"), None)
+        .constructor(None, &[("x", "i32"), ("y", "i32")])
+        .field("x", 0, "i32")
+        .ty("str")
+        .wellknown_traits(&[ZngurWellknownTrait::Unsized])
+        .func("fn crate::sum(&[i32]) -> i32")
+        .build()
+        .unwrap();
+    // Built items have no location.
+    assert_eq!(built, parsed.without_spans());
+}
+
+#[test]
+fn builder_errors() {
+    let errors = ZngurFile::builder()
+        .ty("Vec<i32")
+        .layout(24, 8)
+        .method("fn len(self: &Self)")
+        .ty("crate::Foo")
+        .func("crate::bar()")
+        .ty("crate::Bar")
+        .layout(1, 1)
+        .heap_allocated()
+        .build()
+        .unwrap_err();
+    expect![[r#"
+        error: Invalid type `Vec<i32`: found end of input expected '=', ',', or '>'
+        error: Invalid method signature `fn len(self: &Self)`: found ':' expected '::', '<', ',', or ')'
+        error: No layout policy found for type `crate::Foo`. Use one of `layout`, `heap_allocated` or `only_by_ref`.
+        error: Invalid function signature `crate::bar()`: found 'crate' expected 'fn'
+        error: Duplicate layout policy found for type `crate::Bar`
+    "#]]
+    .assert_eq(&errors.iter().map(|x| format!("{x}\n")).collect::<String>());
+}
+
+#[test]
+fn json_round_trip() {
+    let parsed = check_success(
+        r#"
+/// A point.
+type crate::Point {
+    #layout(size = 8, align = 4);
+    wellknown_traits(Copy, Debug);
+    constructor { x: i32, y: i32 };
+    field x (offset = 0, type = i32);
+    fn norm(&self) -> i32;
+}
+
+type Box<dyn Fn(i32) -> i32> {
+    #heap_allocated;
+}
+
+mod crate {
+    fn origin() -> Point;
+}
+    "#,
+    );
+    let json = parsed.to_json();
+    assert!(json.starts_with("{\n  \"version\": 1,\n  \"file\": {"));
+    let read = ZngurFile::from_json(&json).unwrap();
+    assert_eq!(read.to_json(), json);
+    expect![[r#"
+        Version 2 of the zngur JSON format is not supported, expected version 1
+    "#]]
+    .assert_eq(&format!(
+        "{}\n",
+        ZngurFile::from_json(r#"{ "version": 2, "file": {} }"#).unwrap_err()
+    ));
+}
+
+#[test]
+fn doc_comments_are_attached_to_items() {
+    let parsed = check_success(
+        r#"
+/// A point.
+///
+/// Lives in the crate root.
+type crate::Point {
+    #layout(size = 8, align = 4);
+    // Not a doc comment.
+    constructor { x: i32, y: i32 };
+    /// The x coordinate.
+    field x (offset = 0, type = i32);
+    //// Not a doc comment either.
+    fn norm(&self) -> i32;
+    /// Only documents `abs`.
+    fn abs(&self) -> i32; fn no_doc(&self) -> i32;
+}
+
+mod crate {
+    /// After an attribute on the same line.
+    #cfg(any(unix, not(unix))) fn same_line() -> Point;
+    /// After an attribute split over lines.
+    #cfg(any(
+        unix,
+        not(unix),
+    ))
+    fn split() -> Point;
+    /// Only on 64-bit targets.
+    #cfg(target_pointer_width = "64")
+    fn origin() -> Point;
+}
+    "#,
+    );
+    let ty = &parsed.types[0];
+    assert_eq!(
+        ty.doc.as_deref(),
+        Some("A point.\n\nLives in the crate root.")
+    );
+    assert_eq!(ty.constructors[0].doc, None);
+    assert_eq!(ty.fields[0].doc.as_deref(), Some("The x coordinate."));
+    assert_eq!(ty.methods[0].data.doc, None);
+    assert_eq!(
+        ty.methods[1].data.doc.as_deref(),
+        Some("Only documents `abs`.")
+    );
+    assert_eq!(ty.methods[2].data.doc, None);
+    assert_eq!(
+        parsed.funcs[0].doc.as_deref(),
+        Some("After an attribute on the same line.")
+    );
+    assert_eq!(
+        parsed.funcs[1].doc.as_deref(),
+        Some("After an attribute split over lines.")
+    );
+    if cfg!(target_pointer_width = "64") {
+        assert_eq!(
+            parsed.funcs[2].doc.as_deref(),
+            Some("Only on 64-bit targets.")
+        );
+    }
+}
+
+#[test]
+fn reports_every_semantic_error() {
+    check_fail(
+        r#"
+type ::std::string::String {
+    #layout(size = 24);
+}
+
+type ::std::vec::Vec<i32> {
+    fn len(&self) -> usize;
+}
+    "#,
+        expect![[r#"
+            Error: Align is not declared for this type
+               ╭─[main.zng:2:6]
+               │
+             2 │ type ::std::string::String {
+               │      ──────────┬──────────  
+               │                ╰──────────── Align is not declared for this type
+            ───╯
+            Error: No layout policy found for this type. Use one of `#layout(size = X, align = Y)`, `#heap_allocated` or `#only_by_ref`.
+               ╭─[main.zng:6:6]
+               │
+             6 │ type ::std::vec::Vec<i32> {
+               │      ──────────┬─────────  
+               │                ╰─────────── No layout policy found for this type. Use one of `#layout(size = X, align = Y)`, `#heap_allocated` or `#only_by_ref`.
+            ───╯
+        "#]],
+    );
+}
+
+#[test]
+fn parse_is_thread_safe() {
+    let threads = (0..8)
+        .map(|i| {
+            std::thread::spawn(move || {
+                let zng = format!(
+                    "type crate::T{i} {{ #layout(size = {i}, align = 1); }}\ntype crate::U{i} {{}}"
+                );
+                let filename = format!("file{i}.zng");
+                let diagnostics = ParsedZngFile::parse(&filename, &zng).unwrap_err();
+                assert_eq!(diagnostics.len(), 1);
+                assert_eq!(diagnostics[0].labels[0].file, filename);
+            })
+        })
+        .collect::<Vec<_>>();
+    for t in threads {
+        t.join().unwrap();
+    }
+}
+
+/// Creates a fresh directory with the given files in it, for testing `import` items.
+fn write_files(name: &str, files: &[(&str, &str)]) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("zngur-parser-{name}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    for (path, content) in files {
+        let path = dir.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+    dir
+}
+
+fn parse_dir(dir: &std::path::Path) -> (Result<ZngurFile, Vec<Diagnostic>>, ZngParser) {
+    let main = dir.join("main.zng");
+    let text = std::fs::read_to_string(&main).unwrap();
+    let mut parser = ZngParser::new();
+    (parser.parse(main.to_str().unwrap(), &text), parser)
+}
+
+#[test]
+fn import_splices_items_at_import_site() {
+    let dir = write_files(
+        "import-splice",
+        &[
+            (
+                "main.zng",
+                r#"
+use ::std::string::String as MyString;
+mod crate {
+    import "sub/types.zng";
+}
+"#,
+            ),
+            (
+                "sub/types.zng",
+                r#"
+import "more.zng";
+type Foo {
+    #layout(size = 8, align = 8);
+    fn name(&self) -> MyString;
+}
+"#,
+            ),
+            (
+                "sub/more.zng",
+                r#"
+type Bar {
+    #only_by_ref;
+}
+"#,
+            ),
+        ],
+    );
+    let (parsed, parser) = parse_dir(&dir);
+    let parsed = parsed.unwrap();
+    let types = parsed
+        .types
+        .iter()
+        .map(|x| x.ty.to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(types, ["crate::Bar", "crate::Foo"]);
+    assert_eq!(
+        parsed.types[1].methods[0].data.output,
+        RustType::Adt(RustPathAndGenerics {
+            path: vec![
+                "std".to_string(),
+                "string".to_string(),
+                "String".to_string()
+            ],
+            generics: vec![],
+            named_generics: vec![],
+        })
+    );
+    assert_eq!(
+        parser.imported_files(),
+        [dir.join("sub/types.zng"), dir.join("sub/more.zng")]
+    );
+}
+
+#[test]
+fn import_reports_errors_in_imported_file() {
+    let dir = write_files(
+        "import-errors",
+        &[
+            ("main.zng", "import \"other.zng\";\nimport \"missing.zng\";"),
+            ("other.zng", "type crate::Foo {}"),
+        ],
+    );
+    let diagnostics = parse_dir(&dir).0.unwrap_err();
+    assert_eq!(diagnostics.len(), 2);
+    assert!(diagnostics[0].message.starts_with("No layout policy found"));
+    assert_eq!(
+        diagnostics[0].labels[0].file,
+        dir.join("other.zng").to_str().unwrap()
+    );
+    assert!(
+        diagnostics[1]
+            .message
+            .starts_with("Failed to read imported file")
+    );
+    assert_eq!(
+        diagnostics[1].labels[0].file,
+        dir.join("main.zng").to_str().unwrap()
+    );
+}
+
+#[test]
+fn import_cycle_is_an_error() {
+    let dir = write_files(
+        "import-cycle",
+        &[
+            ("main.zng", "import \"a.zng\";"),
+            ("a.zng", "import \"b.zng\";"),
+            ("b.zng", "import \"main.zng\";"),
+        ],
+    );
+    let diagnostics = parse_dir(&dir).0.unwrap_err();
+    assert_eq!(diagnostics.len(), 1);
+    let canonical = |x: &str| dir.join(x).canonicalize().unwrap().display().to_string();
+    assert_eq!(
+        diagnostics[0].message,
+        format!(
+            "Import cycle detected: {} -> {} -> {} -> {}",
+            canonical("main.zng"),
+            canonical("a.zng"),
+            canonical("b.zng"),
+            canonical("main.zng"),
+        )
+    );
+    assert_eq!(
+        diagnostics[0].labels[0].file,
+        dir.join("b.zng").to_str().unwrap()
+    );
+}
+
+#[test]
+fn reports_every_syntax_error() {
+    check_fail(
+        r#"
+type crate::A {
+    #layout(size = 8 align = 8);
+    fn ok(&self);
+}
+trait crate::B {
+    fn broken(&self) -> ;
+}
+extern "C++" {
+    fn broken(i32 -> i32;
+    impl crate::A {
+        fn ok(&self);
+    }
+}
+mod crate {
+    typ C {}
+    type D {
+        #layout(size = 1, align = 1);
+    }
+}
+type crate::E {
+    fn no_semicolon(&self)
+}
+"#,
+        expect![[r#"
+            Error: found 'align' expected ',', or ')'
+               ╭─[main.zng:3:22]
+               │
+             3 │     #layout(size = 8 align = 8);
+               │                      ──┬──  
+               │                        ╰──── found 'align' expected ',', or ')'
+            ───╯
+            Error: found ';' expected something else, 'Box', '(', '[', '::', 'crate', '&', '*', 'dyn', 'fn', or '!'
+               ╭─[main.zng:7:25]
+               │
+             7 │     fn broken(&self) -> ;
+               │                         ┬  
+               │                         ╰── found ';' expected something else, 'Box', '(', '[', '::', 'crate', '&', '*', 'dyn', 'fn', or '!'
+            ───╯
+            Error: found '->' expected ',', or ')'
+                ╭─[main.zng:10:19]
+                │
+             10 │     fn broken(i32 -> i32;
+                │                   ─┬  
+                │                    ╰── found '->' expected ',', or ')'
+            ────╯
+            Error: found 'typ' expected 'mod', 'type', 'trait', 'extern', 'fn', '#', 'import', or 'use'
+                ╭─[main.zng:16:5]
+                │
+             16 │     typ C {}
+                │     ─┬─  
+                │      ╰─── found 'typ' expected 'mod', 'type', 'trait', 'extern', 'fn', '#', 'import', or 'use'
+            ────╯
+            Error: found '}' expected '->', 'use', 'deref', or ';'
+                ╭─[main.zng:23:1]
+                │
+             23 │ }
+                │ ┬  
+                │ ╰── found '}' expected '->', 'use', 'deref', or ';'
+            ────╯
+        "#]],
+    );
+}
+
+#[test]
+fn recovered_items_are_checked_semantically() {
+    check_fail(
+        r#"
+type crate::A {
+    #layout(size = 8, align = 8);
+    fn broken(;
+}
+type crate::B {
+    fn ok(&self);
+}
+"#,
+        expect![[r#"
+            Error: found ';' expected something else, 'Box', '(', '[', '::', 'crate', '&', '*', 'dyn', 'fn', '!', or ')'
+               ╭─[main.zng:4:15]
+               │
+             4 │     fn broken(;
+               │               ┬  
+               │               ╰── found ';' expected something else, 'Box', '(', '[', '::', 'crate', '&', '*', 'dyn', 'fn', '!', or ')'
+            ───╯
+            Error: No layout policy found for this type. Use one of `#layout(size = X, align = Y)`, `#heap_allocated` or `#only_by_ref`.
+               ╭─[main.zng:6:6]
+               │
+             6 │ type crate::B {
+               │      ────┬───  
+               │          ╰───── No layout policy found for this type. Use one of `#layout(size = X, align = Y)`, `#heap_allocated` or `#only_by_ref`.
+            ───╯
+        "#]],
+    );
+}
+
+fn check_format(zng: &str, formatted: Expect) {
+    let r = match format_zng("main.zng", zng) {
+        Ok(r) => r,
+        Err(diagnostics) => panic!(
+            "Formatting failed but we expected success:\n{}",
+            render(zng, &diagnostics)
+        ),
+    };
+    formatted.assert_eq(&r);
+    assert_eq!(
+        format_zng("main.zng", &r).unwrap(),
+        r,
+        "formatting is not idempotent"
+    );
+}
+
+#[test]
+fn format_canonical_style() {
+    check_format(
+        r#"
+#convert_panic_to_exception
+mod   ::std{
+type option::Option < i32 >{
+      fn unwrap(self)->i32;
+  constructor   Some( i32 );
+    wellknown_traits( Copy,Debug ) ;
+    #layout( size=8 ,align=4 );
+}
+
+
+    type ::std::vec::Vec<Box<dyn Fn(i32)->i32+Send>>{#heap_allocated;
+    fn get(&self,usize)->::std::option::Option<&i32>deref[i32];
+    fn as_ptr(&self) -> *const i32;
+    fn from_fn<i32, Box<dyn Fn() -> ::std::option::Option<crate::PrintOnDrop>>>(Box<dyn Fn() -> ::std::option::Option<crate::PrintOnDrop>>, i32) -> i32;
+    fn map<i32, Box<dyn Fn(i32) -> i32>>(self, Box<dyn Fn(i32) -> i32>) -> ::std::iter::Map<::std::vec::IntoIter<i32>, Box<dyn Fn(i32) -> i32>>;
+    }
+}
+type crate::Foo {
+    #layout(size = 16, align = 8);
+    constructor { a: i32,b: ::std::string::String };
+    field a (offset=0, type=i32);
+    fn hash(&self)->[u8;32];
+    fn set_callback(&mut self,fn ( i32 )->i32);
+    fn   f<i32>(&mut self) use crate::bar;
+}
+type crate::Baz{#layout( auto );}
+type crate::Qux{fn f(&self);#layout_conservative(size=32,align=8);wellknown_traits(Add<i32,Output=crate::Qux>,SubAssign,&mut IntoIterator<Item=&mut i32>,PartialEq,Eq,Hash<Hasher=crate::H>);}
+trait ::std::iter::Iterator::<Item=i32>{fn next(&mut self)->::std::option::Option<i32>;}
+#for(T,N)in[(i32,4),(u8,1)]{type ::std::cell::Cell<T>{#layout(size=N,align=N);fn get(&self)->T;}}
+#cfg(any(unix,feature="x"))type crate::Bar{#cfg(not(target_pointer_width="64"))#layout(size=4,align=4);#cfg(target_pointer_width="64")
+#layout(size=8,align=8);}
+extern "C++"{
+    impl crate::Foo{}
+}
+"#,
+        expect![[r##"
+            #convert_panic_to_exception
+            mod ::std {
+                type option::Option<i32> {
+                    #layout(size = 8, align = 4);
+                    wellknown_traits(Copy, Debug);
+
+                    fn unwrap(self) -> i32;
+                    constructor Some(i32);
+                }
+
+                type ::std::vec::Vec<Box<dyn Fn(i32) -> i32 + Send>> {
+                    #heap_allocated;
+
+                    fn get(&self, usize) -> ::std::option::Option<&i32> deref [i32];
+                    fn as_ptr(&self) -> *const i32;
+                    fn from_fn<i32, Box<dyn Fn() -> ::std::option::Option<crate::PrintOnDrop>>>(
+                        Box<dyn Fn() -> ::std::option::Option<crate::PrintOnDrop>>,
+                        i32
+                    ) -> i32;
+                    fn map<i32, Box<dyn Fn(i32) -> i32>>(self, Box<dyn Fn(i32) -> i32>)
+                        -> ::std::iter::Map<::std::vec::IntoIter<i32>, Box<dyn Fn(i32) -> i32>>;
+                }
+            }
+            type crate::Foo {
+                #layout(size = 16, align = 8);
+
+                constructor { a: i32, b: ::std::string::String };
+                field a (offset = 0, type = i32);
+                fn hash(&self) -> [u8; 32];
+                fn set_callback(&mut self, fn(i32) -> i32);
+                fn f<i32>(&mut self) use crate::bar;
+            }
+            type crate::Baz {
+                #layout(auto);
+            }
+            type crate::Qux {
+                #layout_conservative(size = 32, align = 8);
+                wellknown_traits(Add<i32, Output = crate::Qux>, SubAssign, &mut IntoIterator<Item = &mut i32>, PartialEq, Eq, Hash<Hasher = crate::H>);
+
+                fn f(&self);
+            }
+            trait ::std::iter::Iterator::<Item = i32> {
+                fn next(&mut self) -> ::std::option::Option<i32>;
+            }
+            #for (T, N) in [(i32, 4), (u8, 1)] {
+                type ::std::cell::Cell<T> {
+                    #layout(size = N, align = N);
+
+                    fn get(&self) -> T;
+                }
+            }
+            #cfg(any(unix, feature = "x"))
+            type crate::Bar {
+                #cfg(not(target_pointer_width = "64"))
+                #layout(size = 4, align = 4);
+                #cfg(target_pointer_width = "64")
+                #layout(size = 8, align = 8);
+            }
+            extern "C++" {
+                impl crate::Foo {}
+            }
+        "##]],
+    );
+}
+
+#[test]
+fn format_keeps_comments() {
+    check_format(
+        r#"
+// Types from the standard library.
+mod ::std { // trailing after brace
+
+    // The famous vector.
+
+    type vec::Vec<i32> {
+        fn len(&self) -> usize; // trailing
+        // the layout
+        #layout(size = 24, align = 8);
+        // nothing after this
+    }
+}
+// end of file
+"#,
+        expect![[r#"
+            // Types from the standard library.
+            mod ::std { // trailing after brace
+                // The famous vector.
+
+                type vec::Vec<i32> {
+                    // the layout
+                    #layout(size = 24, align = 8);
+
+                    fn len(&self) -> usize; // trailing
+                    // nothing after this
+                }
+            }
+            // end of file
+        "#]],
+    );
+}
+
+#[test]
+fn format_examples_round_trip() {
+    let examples = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("../examples");
+    for entry in std::fs::read_dir(examples).unwrap() {
+        let path = entry.unwrap().path().join("main.zng");
+        let Ok(text) = std::fs::read_to_string(&path) else {
+            continue;
+        };
+        let path = path.to_str().unwrap();
+        let formatted = format_zng(path, &text)
+            .unwrap_or_else(|d| panic!("Formatting {path} failed:\n{}", render(&text, &d)));
+        assert_eq!(format_zng(path, &formatted).unwrap(), formatted);
+    }
+}
+
+#[test]
+fn spans_point_to_items() {
+    let parsed = check_success(
+        r#"
+mod crate {
+    type Foo {
+        #layout(size = 8, align = 8);
+        fn get(&self) -> i32;
+    }
+    fn bar();
+}
+"#,
+    );
+    let location = |x: &Option<zngur_def::ZngurSpan>| x.as_ref().unwrap().to_string();
+    assert_eq!(location(&parsed.types[0].span), "main.zng:3:10");
+    assert_eq!(
+        location(&parsed.types[0].methods[0].data.span),
+        "main.zng:5:9"
+    );
+    assert_eq!(location(&parsed.funcs[0].span), "main.zng:7:5");
+}
+
+#[test]
+fn compare_ignoring_spans() {
+    let zng = r#"
+mod crate {
+    type Foo {
+        #layout(size = 8, align = 8);
+        fn get(&self) -> i32;
+    }
+    fn bar();
+}
+"#;
+    let parsed = check_success(zng);
+    let moved = check_success(&format!("\n\n{zng}"));
+    assert_ne!(parsed, moved);
+    assert!(parsed.eq_ignoring_spans(&moved));
+    let changed = check_success(&zng.replace("-> i32", "-> i64"));
+    assert!(!parsed.eq_ignoring_spans(&changed));
+}
+
+fn check_warnings(zng: &str, warnings: Expect) {
+    let mut parser = ZngParser::new();
+    if let Err(diagnostics) = parser.parse("main.zng", zng) {
+        panic!(
+            "Parsing failed but we expected success:\n{}",
+            render(zng, &diagnostics)
+        );
+    }
+    warnings.assert_eq(&render(zng, parser.warnings()));
+}
+
+#[test]
+fn lint_reports_undeclared_types_and_unused_aliases() {
+    check_warnings(
+        r#"
+use ::std::string::String as S;
+use ::std::vec::Vec as V;
+use ::std::option::Option as O;
+
+type S {
+    #layout(size = 24, align = 8);
+
+    fn len(&self) -> usize;
+    fn as_str(&self) -> &str;
+    fn into_bytes(self) -> V<u8>;
+    fn clone(&self) -> S;
+}
+
+mod ::std::env {
+    fn args() -> Args;
+}
+"#,
+        expect![[r#"
+            Warning: Alias `O` is never used
+               ╭─[main.zng:4:1]
+               │
+             4 │ use ::std::option::Option as O;
+               │ ───────────────┬───────────────  
+               │                ╰───────────────── Alias `O` is never used
+               │ 
+               │ Note: use `#allow(unused_alias);` to silence this warning
+            ───╯
+            Warning: Type `str` is used, but it is not declared with a `type` block
+                ╭─[main.zng:10:5]
+                │
+             10 │     fn as_str(&self) -> &str;
+                │     ────────────┬───────────  
+                │                 ╰───────────── Type `str` is used, but it is not declared with a `type` block
+                │ 
+                │ Note: use `#allow(undeclared_type);` to silence this warning
+            ────╯
+            Warning: Type `::std::vec::Vec::<u8>` is used, but it is not declared with a `type` block
+                ╭─[main.zng:11:5]
+                │
+             11 │     fn into_bytes(self) -> V<u8>;
+                │     ──────────────┬─────────────  
+                │                   ╰─────────────── Type `::std::vec::Vec::<u8>` is used, but it is not declared with a `type` block
+                │ 
+                │ Note: use `#allow(undeclared_type);` to silence this warning
+            ────╯
+            Warning: Type `::std::env::Args` is used, but it is not declared with a `type` block
+                ╭─[main.zng:16:5]
+                │
+             16 │     fn args() -> Args;
+                │     ────────┬────────  
+                │             ╰────────── Type `::std::env::Args` is used, but it is not declared with a `type` block
+                │ 
+                │ Note: use `#allow(undeclared_type);` to silence this warning
+            ────╯
+        "#]],
+    );
+}
+
+#[test]
+fn allow_silences_lints() {
+    check_warnings(
+        r#"
+#allow(undeclared_type, unused_alias, unknown_lint);
+
+use ::std::string::String as S;
+
+mod ::std::env {
+    fn args() -> Args;
+}
+"#,
+        expect![[r#"
+            Warning: Unknown lint `unknown_lint`
+               ╭─[main.zng:2:39]
+               │
+             2 │ #allow(undeclared_type, unused_alias, unknown_lint);
+               │                                       ──────┬─────  
+               │                                             ╰─────── Expected one of `undeclared_type`, `unused_alias`
+            ───╯
+        "#]],
+    );
+}
+
+#[test]
+fn heap_allocated_types_cant_be_copy() {
+    check_fail(
+        r#"
+type ::std::string::String {
+    #heap_allocated;
+    wellknown_traits(Copy);
+}
+"#,
+        expect![[r#"
+            Error: Heap allocated types can't be `Copy`.
+               ╭─[main.zng:4:22]
+               │
+             3 │     #heap_allocated;
+               │     ───────┬───────  
+               │            ╰───────── Type declared as heap allocated here.
+             4 │     wellknown_traits(Copy);
+               │                      ──┬─  
+               │                        ╰─── Copying the type would copy the pointer to the heap allocation.
+            ───╯
+        "#]],
+    );
+}
+
+#[test]
+fn lint_reports_inconsistent_declarations() {
+    check_fail(
+        r#"
+mod crate {
+    type Foo {
+        #layout(size = 32, align = 8);
+
+        constructor { a: i32 };
+        field a (offset = 0, type = u32);
+        field b (offset = 8, type = ::std::string::String);
+    }
+
+    type Foo {
+        #layout(size = 32, align = 8);
+    }
+}
+
+type ::std::string::String {
+    #layout(size = 24, align = 8);
+}
+"#,
+        expect![[r#"
+            Error: Type `crate::Foo` is declared more than once
+                ╭─[main.zng:11:10]
+                │
+              3 │     type Foo {
+                │          ─┬─  
+                │           ╰─── First declared here
+                │ 
+             11 │     type Foo {
+                │          ─┬─  
+                │           ╰─── Type `crate::Foo` is declared more than once
+            ────╯
+            Error: Constructor of `crate::Foo` takes field `a` as `i32`, but it is declared as `u32`
+               ╭─[main.zng:6:9]
+               │
+             6 │         constructor { a: i32 };
+               │         ───────────┬───────────  
+               │                    ╰───────────── Constructor of `crate::Foo` takes field `a` as `i32`, but it is declared as `u32`
+             7 │         field a (offset = 0, type = u32);
+               │         ────────────────┬────────────────  
+               │                         ╰────────────────── Field declared here
+            ───╯
+            Error: Constructor of `crate::Foo` doesn't take field `b`
+               ╭─[main.zng:6:9]
+               │
+             6 │         constructor { a: i32 };
+               │         ───────────┬───────────  
+               │                    ╰───────────── Constructor of `crate::Foo` doesn't take field `b`
+               │ 
+             8 │         field b (offset = 8, type = ::std::string::String);
+               │         ─────────────────────────┬─────────────────────────  
+               │                                  ╰─────────────────────────── Field declared here
+            ───╯
+        "#]],
+    );
+}
+
+#[test]
+fn index_records_symbols_and_references() {
+    let zng = r#"
+use ::std::string::String as S;
+mod crate {
+    type Foo {
+        #layout(size = 8, align = 8);
+        fn name(&self) -> S;
+    }
+    trait Bar {
+        fn baz(&self) -> Foo;
+    }
+    type Broken {
+        fn;
+    }
+}
+"#;
+    let mut parser = ZngParser::new();
+    assert!(parser.parse("main.zng", zng).is_err());
+    let index = parser.index();
+    fn symbols(zng: &str, list: &[Symbol], depth: usize, out: &mut String) {
+        for x in list {
+            let line = zng[x.span.start..x.span.end].lines().next().unwrap();
+            *out += &format!("{}{:?} {}: {line}\n", "  ".repeat(depth), x.kind, x.name);
+            symbols(zng, &x.children, depth + 1, out);
+        }
+    }
+    let mut out = String::new();
+    symbols(zng, &index.symbols, 0, &mut out);
+    for r in &index.references {
+        let target = match &r.target {
+            ReferenceTarget::Type(ty) => ty.to_string(),
+            ReferenceTarget::Alias(x) => zng[x.start..x.end].to_owned(),
+        };
+        out += &format!("{} -> {target}\n", &zng[r.span.start..r.span.end]);
+    }
+    expect![[r#"
+        Mod crate: mod crate {
+          Type Foo: type Foo {
+          Trait Bar: trait Bar {
+          Type Broken: type Broken {
+        Foo -> crate::Foo
+        S -> use ::std::string::String as S;
+        S -> ::std::string::String
+        Foo -> crate::Foo
+        Broken -> crate::Broken
+    "#]]
+    .assert_eq(&out);
+}
+
+#[test]
+fn number_too_large() {
+    check_fail(
+        r#"
+type [u8; 99999999999999999999] {
+    #layout(size = 1, align = 1);
+}
+"#,
+        expect![[r#"
+            Error: Number `99999999999999999999` is too large
+               ╭─[main.zng:2:11]
+               │
+             2 │ type [u8; 99999999999999999999] {
+               │           ──────────┬─────────  
+               │                     ╰─────────── Number `99999999999999999999` is too large
+            ───╯
+        "#]],
+    );
+}
+
+#[test]
+fn box_with_wrong_generics() {
+    check_fail(
+        r#"
+type Box<i32, i32> {
+    #heap_allocated;
+}
+
+type Box<T = i32> {
+    #heap_allocated;
+}
+"#,
+        expect![[r#"
+            Error: `Box` takes exactly one type argument, like `Box<T>`
+               ╭─[main.zng:2:6]
+               │
+             2 │ type Box<i32, i32> {
+               │      ──────┬──────  
+               │            ╰──────── `Box` takes exactly one type argument, like `Box<T>`
+            ───╯
+            Error: `Box` takes exactly one type argument, like `Box<T>`
+               ╭─[main.zng:6:6]
+               │
+             6 │ type Box<T = i32> {
+               │      ──────┬─────  
+               │            ╰─────── `Box` takes exactly one type argument, like `Box<T>`
+            ───╯
+        "#]],
+    );
+}
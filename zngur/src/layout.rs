@@ -0,0 +1,281 @@
+//! Computing the layout of types with `#layout(auto)`.
+//!
+//! The layouts are computed by checking the crate that includes the generated Rust file with
+//! cargo, using a version of the file that is rendered by
+//! [`ZngurGenerator::render_layout_probe`]. Nothing is run, so this works when cross compiling
+//! too. The results are cached next to the target directory of that check, until one of the
+//! source files of the crate changes.
+
+use std::{
+    collections::hash_map::DefaultHasher,
+    ffi::OsString,
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use zngur_generator::{LayoutPolicy, ZngurFile, ZngurGenerator, read_layout_probe};
+
+/// Set to the manifest directory of the crate being checked, so that its build script writes the
+/// layout probe instead of the normal files.
+const PROBE_ENV: &str = "ZNGUR_LAYOUT_PROBE";
+
+/// Whether this build script runs for computing the layouts of its own crate.
+pub(crate) fn is_probe() -> bool {
+    match (
+        std::env::var_os(PROBE_ENV),
+        std::env::var_os("CARGO_MANIFEST_DIR"),
+    ) {
+        (Some(probed), Some(manifest_dir)) => probed == manifest_dir,
+        _ => false,
+    }
+}
+
+/// How the crate that includes the generated Rust file is checked.
+struct Probe {
+    cargo: OsString,
+    manifest_dir: PathBuf,
+    args: Vec<String>,
+    target_dir: PathBuf,
+    /// Files whose changes might change the layouts.
+    inputs: Vec<PathBuf>,
+}
+
+impl Probe {
+    /// For the crate whose build script is running, with its target and features.
+    fn from_build_script(rs_file: &Path) -> Self {
+        let var = |name| std::env::var(name).unwrap_or_default();
+        let manifest_dir = PathBuf::from(var("CARGO_MANIFEST_DIR"));
+        let mut args = vec!["--target".to_owned(), var("TARGET")];
+        if var("PROFILE") == "release" {
+            args.push("--release".to_owned());
+        }
+        if let Ok(features) = std::env::var("CARGO_CFG_FEATURE") {
+            args.push("--no-default-features".to_owned());
+            args.push(format!("--features={features}"));
+        }
+        Probe {
+            cargo: std::env::var_os("CARGO").unwrap_or("cargo".into()),
+            inputs: crate_inputs(&manifest_dir, rs_file),
+            target_dir: PathBuf::from(var("OUT_DIR")).join("zngur-layout-probe"),
+            manifest_dir,
+            args,
+        }
+    }
+
+    /// For the closest crate that contains `rs_file`, with its default features for the host.
+    fn for_file(rs_file: &Path) -> Option<Self> {
+        let rs_file = canonicalize(rs_file)?;
+        let manifest_dir = rs_file
+            .ancestors()
+            .find(|x| x.join("Cargo.toml").is_file())?
+            .to_owned();
+        let mut hasher = DefaultHasher::new();
+        manifest_dir.hash(&mut hasher);
+        Some(Probe {
+            cargo: std::env::var_os("CARGO").unwrap_or("cargo".into()),
+            inputs: crate_inputs(&manifest_dir, &rs_file),
+            target_dir: std::env::temp_dir()
+                .join("zngur-layout-probe")
+                .join(format!("{:016x}", hasher.finish())),
+            manifest_dir,
+            args: vec![],
+        })
+    }
+
+    /// Identifies the results of checking the crate for the layouts of `types`.
+    fn cache_key(&self, types: &[String]) -> String {
+        let mut hasher = DefaultHasher::new();
+        types.hash(&mut hasher);
+        self.args.hash(&mut hasher);
+        for name in ["RUSTC", "CARGO_ENCODED_RUSTFLAGS", "RUSTFLAGS"] {
+            std::env::var_os(name).hash(&mut hasher);
+        }
+        for input in &self.inputs {
+            input.hash(&mut hasher);
+            if let Ok(metadata) = input.metadata() {
+                metadata.len().hash(&mut hasher);
+                metadata.modified().ok().hash(&mut hasher);
+            }
+        }
+        format!("{:016x}", hasher.finish())
+    }
+
+    /// Checks the crate, and returns the `(size, align)` of `types` that are found.
+    fn run(&self, types: &[String]) -> Result<Vec<(usize, usize)>, String> {
+        let output = Command::new(&self.cargo)
+            .arg("check")
+            .arg("--manifest-path")
+            .arg(self.manifest_dir.join("Cargo.toml"))
+            .arg("--target-dir")
+            .arg(&self.target_dir)
+            .arg("--message-format=json")
+            .args(&self.args)
+            .env(PROBE_ENV, &self.manifest_dir)
+            .output()
+            .map_err(|e| format!("Failed to run cargo for computing layouts: {e}"))?;
+        let probe = read_layout_probe(&String::from_utf8_lossy(&output.stdout), types.len());
+        let missing = types
+            .iter()
+            .zip(&probe.layouts)
+            .filter(|x| x.1.is_none())
+            .map(|x| format!("`{}`", x.0))
+            .collect::<Vec<_>>();
+        if missing.is_empty() {
+            return Ok(probe.layouts.into_iter().flatten().collect());
+        }
+        let reason = if output.status.success() {
+            "the crate doesn't include the generated Rust file".to_owned()
+        } else {
+            format!(
+                "checking it failed:\n{}{}",
+                probe.errors.concat(),
+                String::from_utf8_lossy(&output.stderr)
+            )
+        };
+        Err(format!(
+            "Failed to compute the layout of {} with `#layout(auto)` in the crate at `{}`, because {reason}",
+            missing.join(", "),
+            self.manifest_dir.display(),
+        ))
+    }
+}
+
+/// Replaces `#layout(auto)` in `zng` with the layouts computed by checking the crate that includes
+/// `rs_file`. Outside of build scripts, `render_probe` renders the Rust file of
+/// [`ZngurGenerator::render_layout_probe`], which replaces `rs_file` while the crate is checked.
+pub(crate) fn resolve_auto_layouts(
+    zng: &mut ZngurFile,
+    rs_file: &Path,
+    render_probe: impl FnOnce(ZngurGenerator) -> String,
+) -> Result<(), String> {
+    let types = zng
+        .types
+        .iter()
+        .filter(|x| x.layout == LayoutPolicy::Auto)
+        .map(|x| x.ty.to_string())
+        .collect::<Vec<_>>();
+    if types.is_empty() {
+        return Ok(());
+    }
+    let in_build_script = std::env::var_os("OUT_DIR").is_some();
+    let probe = if in_build_script {
+        Probe::from_build_script(rs_file)
+    } else {
+        Probe::for_file(rs_file).ok_or_else(|| {
+            format!(
+                "`#layout(auto)` needs the generated Rust file `{}` to be in a cargo package",
+                rs_file.display()
+            )
+        })?
+    };
+    if in_build_script {
+        for input in &probe.inputs {
+            println!("cargo:rerun-if-changed={}", input.display());
+        }
+    }
+    let key = probe.cache_key(&types);
+    let cache_file = probe.target_dir.join("zngur-layouts");
+    let layouts = match read_cache(&cache_file, &key, types.len()) {
+        Some(layouts) => layouts,
+        None => {
+            let layouts = if in_build_script {
+                probe.run(&types)?
+            } else {
+                let probe_file = render_probe(ZngurGenerator::build_from_zng(zng.clone()));
+                run_with_file(rs_file, &probe_file, || probe.run(&types))?
+            };
+            let cache = std::iter::once(key)
+                .chain(
+                    layouts
+                        .iter()
+                        .map(|(size, align)| format!("{size} {align}")),
+                )
+                .collect::<Vec<_>>();
+            // Failing to write the cache only makes the next generation slower.
+            _ = std::fs::write(cache_file, cache.join("\n"));
+            layouts
+        }
+    };
+    let auto_types = zng
+        .types
+        .iter_mut()
+        .filter(|x| x.layout == LayoutPolicy::Auto);
+    for (ty_def, (size, align)) in auto_types.zip(layouts) {
+        ty_def.layout = LayoutPolicy::StackAllocated { size, align };
+    }
+    Ok(())
+}
+
+/// Runs `f` while `path` contains `content`, and then puts back what it contained before.
+fn run_with_file<T>(
+    path: &Path,
+    content: &str,
+    f: impl FnOnce() -> Result<T, String>,
+) -> Result<T, String> {
+    let write_error = |e| format!("Failed to write `{}`: {e}", path.display());
+    let previous = std::fs::read(path).ok();
+    std::fs::write(path, content).map_err(write_error)?;
+    let result = f();
+    match previous {
+        Some(previous) => std::fs::write(path, previous),
+        None => std::fs::remove_file(path),
+    }
+    .map_err(write_error)?;
+    result
+}
+
+fn read_cache(path: &Path, key: &str, count: usize) -> Option<Vec<(usize, usize)>> {
+    let text = std::fs::read_to_string(path).ok()?;
+    let mut lines = text.lines();
+    if lines.next()? != key {
+        return None;
+    }
+    let layouts = lines
+        .map(|line| {
+            let (size, align) = line.split_once(' ')?;
+            Some((size.parse().ok()?, align.parse().ok()?))
+        })
+        .collect::<Option<Vec<_>>>()?;
+    (layouts.len() == count).then_some(layouts)
+}
+
+/// The manifest, the lock file and the sources of the crate at `manifest_dir`, except `rs_file`.
+fn crate_inputs(manifest_dir: &Path, rs_file: &Path) -> Vec<PathBuf> {
+    let mut inputs = vec![manifest_dir.join("Cargo.toml")];
+    inputs.extend(
+        manifest_dir
+            .ancestors()
+            .map(|x| x.join("Cargo.lock"))
+            .find(|x| x.is_file()),
+    );
+    let rs_file = canonicalize(rs_file);
+    let mut dirs = vec![manifest_dir.join("src")];
+    while let Some(dir) = dirs.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                dirs.push(path);
+            } else if canonicalize(&path) != rs_file {
+                inputs.push(path);
+            }
+        }
+    }
+    inputs.sort();
+    inputs
+}
+
+/// Canonical form of `path`, which might not exist yet.
+fn canonicalize(path: &Path) -> Option<PathBuf> {
+    let parent = match path.parent()? {
+        x if x.as_os_str().is_empty() => Path::new("."),
+        x => x,
+    };
+    Some(parent.canonicalize().ok()?.join(path.file_name()?))
+}
+
+#[cfg(test)]
+mod tests;
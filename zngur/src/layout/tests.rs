@@ -0,0 +1,139 @@
+use std::path::{Path, PathBuf};
+
+use zngur_generator::{LayoutPolicy, ParsedZngFile, ZngurFile};
+
+use super::{Probe, crate_inputs, read_cache, resolve_auto_layouts, run_with_file};
+
+/// Creates a fresh directory with the given files in it.
+fn write_files(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("zngur-layout-{name}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    for (path, content) in files {
+        let path = dir.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+    dir
+}
+
+const MANIFEST: &str = r#"
+[package]
+name = "probed"
+version = "0.1.0"
+edition = "2024"
+
+[workspace]
+"#;
+
+const ZNG: &str = r#"
+type crate::Point {
+    #layout(auto);
+    fn x(&self) -> u32;
+}
+
+type crate::Empty {
+    #layout(auto);
+}
+"#;
+
+const LIB: &str = r#"
+#[rustfmt::skip]
+mod generated;
+pub struct Point { x: u32, _y: u16 }
+impl Point { pub fn x(&self) -> u32 { self.x } }
+pub struct Empty;
+"#;
+
+fn resolve(rs_file: &Path) -> Result<ZngurFile, String> {
+    let mut zng = ParsedZngFile::parse("main.zng", ZNG).unwrap();
+    resolve_auto_layouts(&mut zng, rs_file, |generator| {
+        generator.render_layout_probe().unwrap().0
+    })?;
+    Ok(zng)
+}
+
+#[test]
+fn resolve_layouts() {
+    let dir = write_files("resolve", &[("Cargo.toml", MANIFEST), ("src/lib.rs", LIB)]);
+    let rs_file = dir.join("src/generated.rs");
+    let zng = resolve(&rs_file).unwrap();
+    let layouts = zng.types.iter().map(|x| x.layout).collect::<Vec<_>>();
+    assert_eq!(
+        layouts,
+        [
+            LayoutPolicy::StackAllocated { size: 8, align: 4 },
+            LayoutPolicy::StackAllocated { size: 0, align: 1 },
+        ]
+    );
+    // The probe doesn't stay in place of the generated file.
+    assert!(!rs_file.exists());
+    // The second time, the layouts come from the cache.
+    let probe = Probe::for_file(&rs_file).unwrap();
+    assert!(probe.target_dir.join("zngur-layouts").is_file());
+    assert_eq!(resolve(&rs_file).unwrap(), zng);
+}
+
+#[test]
+fn resolve_layouts_fails() {
+    let lib = LIB.replace("pub struct Empty;", "");
+    let dir = write_files(
+        "fail",
+        &[
+            ("Cargo.toml", MANIFEST),
+            ("src/lib.rs", &lib),
+            ("src/generated.rs", "// previous"),
+        ],
+    );
+    let rs_file = dir.join("src/generated.rs");
+    let error = resolve(&rs_file).unwrap_err();
+    assert!(
+        error.contains("layout of `crate::Empty`") && error.contains("cannot find type `Empty`"),
+        "{error}"
+    );
+    assert_eq!(std::fs::read_to_string(&rs_file).unwrap(), "// previous");
+}
+
+#[test]
+fn restore_file() {
+    let dir = write_files("restore", &[("a.rs", "a")]);
+    let a = dir.join("a.rs");
+    let b = dir.join("b.rs");
+    let read = |path: &Path| std::fs::read_to_string(path).ok();
+    let result = run_with_file(&a, "probe", || Ok(read(&a)));
+    assert_eq!(result, Ok(Some("probe".to_owned())));
+    assert_eq!(read(&a).as_deref(), Some("a"));
+    let result = run_with_file(&b, "probe", || Err::<(), _>("failed".to_owned()));
+    assert_eq!(result, Err("failed".to_owned()));
+    assert!(!b.exists());
+}
+
+#[test]
+fn cache() {
+    let dir = write_files("cache", &[("zngur-layouts", "key\n8 4\n0 1")]);
+    let path = dir.join("zngur-layouts");
+    assert_eq!(read_cache(&path, "key", 2), Some(vec![(8, 4), (0, 1)]));
+    assert_eq!(read_cache(&path, "other", 2), None);
+    assert_eq!(read_cache(&path, "key", 3), None);
+    assert_eq!(read_cache(&dir.join("missing"), "key", 2), None);
+}
+
+#[test]
+fn inputs() {
+    let dir = write_files(
+        "inputs",
+        &[
+            ("Cargo.toml", MANIFEST),
+            ("src/lib.rs", LIB),
+            ("src/generated.rs", ""),
+            ("src/a/b.rs", ""),
+            ("build.rs", ""),
+        ],
+    );
+    let dir = dir.canonicalize().unwrap();
+    let inputs = crate_inputs(&dir, &dir.join("src/generated.rs"));
+    let inputs = inputs
+        .iter()
+        .map(|x| x.strip_prefix(&dir).unwrap().to_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(inputs, ["Cargo.toml", "src/a/b.rs", "src/lib.rs"]);
+}
@@ -7,7 +7,13 @@ use std::{
     path::{Path, PathBuf},
 };
 
-use zngur_generator::{ParsedZngFile, ZngurGenerator};
+use zngur_generator::{AriadneRenderer, Diagnostic, ZngParser, ZngurGenerator};
+
+mod layout;
+
+pub use zngur_generator::{
+    Cfg, ZngurFile, ZngurFileBuilder, ZngurFileExt, ZngurTypeBuilder, ZngurWellknownTrait,
+};
 
 #[must_use]
 /// Builder for the Zngur generator.
@@ -23,19 +29,59 @@ use zngur_generator::{ParsedZngFile, ZngurGenerator};
 ///     .generate();
 /// ```
 pub struct Zngur {
-    zng_file: PathBuf,
+    input: Input,
     h_file_path: Option<PathBuf>,
     cpp_file_path: Option<PathBuf>,
     rs_file_path: Option<PathBuf>,
+    cfg: Option<Cfg>,
+}
+
+enum Input {
+    Zng(PathBuf),
+    /// A file in the JSON format of [`ZngurFile`].
+    #[cfg(feature = "serde")]
+    Json(PathBuf),
+    File(ZngurFile),
 }
 
 impl Zngur {
     pub fn from_zng_file(zng_file_path: impl AsRef<Path>) -> Self {
+        Self::new(Input::Zng(zng_file_path.as_ref().to_owned()))
+    }
+
+    /// Generates from a zng file in the JSON format written by [`ZngurFile::to_json`], instead of
+    /// a zng file.
+    #[cfg(feature = "serde")]
+    pub fn from_json_file(json_file_path: impl AsRef<Path>) -> Self {
+        Self::new(Input::Json(json_file_path.as_ref().to_owned()))
+    }
+
+    /// Generates from a file built in code, usually with [`ZngurFile::builder`](ZngurFileExt::builder),
+    /// instead of a zng file:
+    ///
+    /// ```ignore
+    /// let file = ZngurFile::builder()
+    ///     .ty("::std::vec::Vec<i32>")
+    ///     .layout(24, 8)
+    ///     .method("fn len(&self) -> usize")
+    ///     .build()
+    ///     .unwrap_or_else(|errors| panic!("{errors:?}"));
+    /// Zngur::from_zngur_file(file)
+    ///     .with_h_file(out_dir.join("generated.h"))
+    ///     .with_rs_file(out_dir.join("generated.rs"))
+    ///     .generate();
+    /// ```
+    pub fn from_zngur_file(file: ZngurFile) -> Self {
+        Self::new(Input::File(file))
+    }
+
+    fn new(input: Input) -> Self {
         Zngur {
-            zng_file: zng_file_path.as_ref().to_owned(),
+            input,
             h_file_path: None,
             cpp_file_path: None,
             rs_file_path: None,
+            cfg: None,
         }
     }
 
@@ -54,28 +100,115 @@ impl Zngur {
         self
     }
 
-    pub fn generate(self) {
-        let path = self.zng_file;
-        let file = std::fs::read_to_string(path).unwrap();
-        let file = ZngurGenerator::build_from_zng(ParsedZngFile::parse("main.zng", &file));
+    /// Sets the configuration that `#cfg(...)` attributes of the zng file are evaluated against.
+    ///
+    /// By default, it is [`Cfg::from_cargo_env`] inside build scripts, and [`Cfg::host`] otherwise.
+    pub fn with_cfg(mut self, cfg: Cfg) -> Self {
+        self.cfg = Some(cfg);
+        self
+    }
 
-        let (rust, h, cpp) = file.render();
+    /// Generates the output files.
+    ///
+    /// When running inside a build script, this also emits `cargo:rerun-if-changed` for every file
+    /// pulled in by `import` items of the zng file, and `cargo:warning` for every warning found in it.
+    ///
+    /// Types with `#layout(auto)` get the layout they have in the crate that includes the generated
+    /// Rust file, which is computed by checking that crate with cargo. In a build script, this is the
+    /// crate being built, with its target and features. Otherwise, it is the closest crate that
+    /// contains the Rust file, with its default features for the host.
+    pub fn generate(self) {
+        let mut renderer = AriadneRenderer::new();
+        let mut zng = match self.input {
+            Input::Zng(zng_file) => {
+                let path = zng_file.to_string_lossy();
+                let text = std::fs::read_to_string(&zng_file).unwrap();
+                renderer = renderer.with_source(&path, &text);
+                parse_zng(&path, &text, self.cfg, &renderer)
+            }
+            #[cfg(feature = "serde")]
+            Input::Json(json_file) => {
+                let text = std::fs::read_to_string(&json_file).unwrap();
+                ZngurFile::from_json(&text).unwrap_or_else(|e| {
+                    eprintln!("{}: {e}", json_file.display());
+                    std::process::exit(101);
+                })
+            }
+            Input::File(file) => file,
+        };
         let rs_file_path = self.rs_file_path.expect("No rs file path provided");
         let h_file_path = self.h_file_path.expect("No h file path provided");
-        File::create(rs_file_path)
-            .unwrap()
-            .write_all(rust.as_bytes())
-            .unwrap();
-        File::create(h_file_path)
-            .unwrap()
-            .write_all(h.as_bytes())
-            .unwrap();
-        if let Some(cpp) = cpp {
-            let cpp_file_path = self.cpp_file_path.expect("No cpp file path provided");
-            File::create(cpp_file_path)
-                .unwrap()
-                .write_all(cpp.as_bytes())
-                .unwrap();
+        let write = |(rust, h, cpp): (String, String, Option<String>)| {
+            write_if_changed(&rs_file_path, &rust);
+            write_if_changed(&h_file_path, &h);
+            if let Some(cpp) = cpp {
+                let cpp_file_path = self.cpp_file_path.as_ref();
+                write_if_changed(cpp_file_path.expect("No cpp file path provided"), &cpp);
+            }
+        };
+        if layout::is_probe() {
+            let generator = ZngurGenerator::build_from_zng(zng);
+            write(
+                generator
+                    .render_layout_probe()
+                    .unwrap_or_else(|d| report(&renderer, d)),
+            );
+            return;
         }
+        layout::resolve_auto_layouts(&mut zng, &rs_file_path, |generator| {
+            let (rust, _, _) = generator
+                .render_layout_probe()
+                .unwrap_or_else(|d| report(&renderer, d));
+            rust
+        })
+        .unwrap_or_else(|e| {
+            eprintln!("{e}");
+            std::process::exit(101);
+        });
+        let generator = ZngurGenerator::build_from_zng(zng);
+        write(generator.render().unwrap_or_else(|d| report(&renderer, d)));
     }
 }
+
+/// Writes `content` to `path`, unless it is already there, so that nothing is rebuilt for an
+/// unchanged file.
+fn write_if_changed(path: &Path, content: &str) {
+    if std::fs::read_to_string(path).is_ok_and(|x| x == content) {
+        return;
+    }
+    File::create(path)
+        .unwrap()
+        .write_all(content.as_bytes())
+        .unwrap();
+}
+
+/// Parses the zng file at `path`, and reports its diagnostics.
+fn parse_zng(path: &str, text: &str, cfg: Option<Cfg>, renderer: &AriadneRenderer) -> ZngurFile {
+    let cfg = cfg.unwrap_or_else(|| match std::env::var_os("OUT_DIR") {
+        Some(_) => Cfg::from_cargo_env(),
+        None => Cfg::host(),
+    });
+    let mut parser = ZngParser::new().with_cfg(cfg);
+    let parsed = parser.parse(path, text);
+    if std::env::var_os("OUT_DIR").is_some() {
+        for imported in parser.imported_files() {
+            println!("cargo:rerun-if-changed={}", imported.display());
+        }
+    }
+    let zng = parsed.unwrap_or_else(|d| report(renderer, d));
+    renderer.eprint(parser.warnings()).unwrap();
+    if std::env::var_os("OUT_DIR").is_some() {
+        for warning in parser.warnings() {
+            match warning.location() {
+                Some(l) => println!("cargo:warning={}: {}", l.file, warning.message),
+                None => println!("cargo:warning={}", warning.message),
+            }
+        }
+    }
+    zng
+}
+
+fn report(renderer: &AriadneRenderer, diagnostics: Vec<Diagnostic>) -> ! {
+    renderer.eprint(&diagnostics).unwrap();
+    std::process::exit(101);
+}